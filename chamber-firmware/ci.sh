@@ -24,7 +24,7 @@ rm -rf "$project"
 echo "Creating new project"
 # cargo generate -p . --name "$project"
 mkdir -p "$project"
-cp -r Cargo.toml LICENSE-* src/ rust-toolchain.toml .cargo/ "$project"
+cp -r Cargo.toml LICENSE-* build.rs memory.x src/ rust-toolchain.toml .cargo/ "$project"
 
 echo "Storing current config so that the child project will compile."
 mv Cargo.toml Cargo.toml.tmp
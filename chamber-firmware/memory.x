@@ -0,0 +1,17 @@
+MEMORY
+{
+  /* FLASH and RAM are mandatory memory regions */
+
+  /* STM32H7B0, whose last 64K of flash are left for storage */
+  FLASH  : ORIGIN = 0x08000000, LENGTH = 64K
+
+  /* Storage sectors, see STORAGE_FLASH_OFFSET in src/bin/minimal.rs */
+  STORAGE : ORIGIN = 0x08010000, LENGTH = 64K
+
+  /* DTCM  */
+  RAM    : ORIGIN = 0x20000000, LENGTH = 128K
+}
+
+/* The location of the stack can be overridden using the
+   `_stack_start` symbol.  Place the stack at the end of RAM */
+_stack_start = ORIGIN(RAM) + LENGTH(RAM);
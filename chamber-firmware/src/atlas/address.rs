@@ -0,0 +1,83 @@
+use stm32h7xx_hal::hal::blocking::i2c::{Read, Write};
+
+use super::{
+    i2c_mode_command, read_response, response_data, write_command, AtlasSensor, DeviceInfo,
+    SensorFault, IDENTIFY_DELAY_MS,
+};
+
+/// Time an EZO device needs to restart after its address is changed, in milliseconds.
+pub const ADDRESS_CHANGE_DELAY_MS: u32 = 1000;
+
+/// Ways in which moving an EZO device to a new address can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ProvisionError {
+    /// The new address is outside the range EZO devices accept.
+    InvalidAddress,
+    /// Another device already acknowledges the new address.
+    AddressInUse,
+    /// The device didn't accept the address change.
+    Fault(SensorFault),
+    /// Nothing identified itself at the new address after the device restarted.
+    NotResponding,
+    /// A different type of device identified itself at the new address.
+    WrongDevice,
+}
+
+/// Moves a sensor's device to `new_address` with `I2C,<address>`.
+///
+/// The new address must be free, so only the device being provisioned should
+/// be at its current address. Once the device restarts, it's identified at the
+/// new address to verify the change, and the sensor's address is updated.
+/// `wait_ms` is used to wait for the device to restart and identify itself.
+///
+/// This blocks while the device restarts, and is intended to run while
+/// provisioning a rack, such as before a second board of the same type is
+/// connected.
+pub fn provision_address<I2C>(
+    i2c: &mut I2C,
+    sensor: &mut dyn AtlasSensor,
+    new_address: u8,
+    mut wait_ms: impl FnMut(u32),
+) -> Result<(), ProvisionError>
+where
+    I2C: Write + Read,
+{
+    let command = i2c_mode_command(new_address).ok_or(ProvisionError::InvalidAddress)?;
+    let old_address = u8::try_from(sensor.address()).map_err(|_| ProvisionError::InvalidAddress)?;
+
+    if old_address == new_address {
+        return Ok(());
+    }
+
+    // Unpopulated addresses don't acknowledge the write
+    if i2c.write(new_address, b"i").is_ok() {
+        return Err(ProvisionError::AddressInUse);
+    }
+
+    // The device restarts as soon as it accepts the command, so there's no response to read
+    write_command(i2c, old_address, &command).map_err(ProvisionError::Fault)?;
+    wait_ms(ADDRESS_CHANGE_DELAY_MS);
+
+    write_command(i2c, new_address, b"i").map_err(|_| ProvisionError::NotResponding)?;
+    wait_ms(IDENTIFY_DELAY_MS);
+
+    let response = read_response(i2c, new_address).map_err(|_| ProvisionError::NotResponding)?;
+    let info = response_data(&response)
+        .and_then(DeviceInfo::parse)
+        .ok_or(ProvisionError::NotResponding)?;
+
+    if info.device_type != sensor.device_type() {
+        return Err(ProvisionError::WrongDevice);
+    }
+
+    defmt::info!(
+        "[provision_address] Moved {} from {} to {}.",
+        info.device_type,
+        old_address,
+        new_address
+    );
+
+    sensor.set_address(u32::from(new_address));
+
+    Ok(())
+}
@@ -0,0 +1,700 @@
+use heapless::HistoryBuffer;
+
+use super::{
+    format_command, response_data, strip_response_prefix, CommandBuffer, DeviceType, SensorFault,
+};
+use crate::clock::Timestamp;
+use crate::storage::{Flash, RecordStore, StoreError, MAX_RECORD_LEN};
+
+/// Key of the calibration log's record, next to [`crate::config::CONFIG_KEY`].
+pub const CALIBRATION_LOG_KEY: u8 = 2;
+
+/// Version of the calibration log's record layout.
+pub const CALIBRATION_LOG_VERSION: u8 = 1;
+
+/// Version of the layout of a device's calibration data record.
+pub const CALIBRATION_DATA_VERSION: u8 = 1;
+
+/// Length of an encoded [`CalibrationRecord`].
+const CALIBRATION_RECORD_LEN: usize = 16;
+
+/// Length the device type's code is padded to in an encoded [`CalibrationRecord`].
+const DEVICE_CODE_LEN: usize = 3;
+
+/// Maximum number of points in a calibration procedure.
+pub const MAX_CALIBRATION_POINTS: usize = 4;
+
+/// Maximum number of readings considered when waiting for a reading to stabilise.
+pub const MAX_STABILITY_WINDOW: usize = 16;
+
+/// Maximum number of strings in exported calibration data.
+pub const MAX_EXPORT_CHUNKS: usize = 16;
+
+/// Maximum length of a single string of exported calibration data.
+pub const EXPORT_CHUNK_LENGTH: usize = 16;
+
+/// Calibration data exported from an EZO device, one string per `Export` command.
+pub type CalibrationData = heapless::Vec<heapless::String<EXPORT_CHUNK_LENGTH>, MAX_EXPORT_CHUNKS>;
+
+/// A single point of a calibration procedure.
+#[derive(Clone)]
+pub struct CalibrationPoint {
+    /// Instruction shown to the operator before the point is calibrated.
+    pub instruction: &'static str,
+    /// Command that calibrates the point, such as `Cal,low,12880`.
+    pub command: CommandBuffer,
+}
+
+/// Criteria for a reading to be considered stable enough to calibrate against.
+#[derive(Clone, Copy)]
+pub struct StabilityCriteria {
+    /// Number of consecutive readings considered, at most [`MAX_STABILITY_WINDOW`].
+    pub window: usize,
+    /// Maximum standard deviation of the readings in the window.
+    pub max_deviation: f64,
+}
+
+impl StabilityCriteria {
+    /// Returns whether the most recent readings satisfy the criteria.
+    pub fn is_stable(&self, readings: &HistoryBuffer<f64, MAX_STABILITY_WINDOW>) -> bool {
+        let window = self.window.clamp(2, MAX_STABILITY_WINDOW);
+
+        if readings.len() < window {
+            return false;
+        }
+
+        let recent = readings.oldest_ordered().skip(readings.len() - window);
+        let mut mean = 0.0;
+        let mut sum_of_squares = 0.0;
+        let mut count = 0.0;
+
+        // Welford's algorithm, to avoid a second pass over the window
+        for value in recent {
+            count += 1.0;
+            let delta = value - mean;
+            mean += delta / count;
+            sum_of_squares += delta * (value - mean);
+        }
+
+        libm::sqrt(sum_of_squares / (count - 1.0)) <= self.max_deviation
+    }
+}
+
+/// Reasons a calibration procedure can fail.
+#[derive(Clone, Copy, defmt::Format)]
+pub enum CalibrationError {
+    /// The device rejected the calibration command for a point.
+    Rejected { point: usize },
+    /// `Cal,?` reported a different calibration than was performed.
+    VerificationFailed { expected: u8, reported: u8 },
+    /// `Cal,?` returned something that couldn't be parsed.
+    InvalidVerification,
+    /// A command couldn't be issued, or its response read.
+    Fault(SensorFault),
+    /// The operator cancelled the procedure.
+    Cancelled,
+}
+
+/// Progress of a calibration procedure.
+#[derive(Clone, Copy, defmt::Format)]
+pub enum CalibrationState {
+    /// Waiting for the operator to prepare the point, then confirm.
+    AwaitingOperator { point: usize },
+    /// Sampling the device until the reading is stable.
+    Stabilising { point: usize },
+    /// The calibration command for the point is being issued.
+    Calibrating { point: usize },
+    /// The calibration is being verified with `Cal,?`.
+    Verifying,
+    /// All points were calibrated and verified.
+    Complete,
+    /// The procedure failed, and the device may be partially calibrated.
+    Failed(CalibrationError),
+}
+
+/// Walks an operator through calibrating an EZO device, one point at a time.
+///
+/// Each point waits for the operator to confirm that the probe is prepared, then
+/// for the reading to stabilise before its calibration command is issued. Once
+/// all points are calibrated the result is verified with `Cal,?`.
+///
+/// The procedure doesn't talk to the device itself. Readings are passed to
+/// [`CalibrationProcedure::push_reading`], and the command returned by
+/// [`CalibrationProcedure::due_command`] is issued with its response passed to
+/// [`CalibrationProcedure::handle_response`].
+pub struct CalibrationProcedure {
+    pub address: u32,
+    pub device_type: DeviceType,
+    pub state: CalibrationState,
+    points: heapless::Vec<CalibrationPoint, MAX_CALIBRATION_POINTS>,
+    criteria: StabilityCriteria,
+    readings: HistoryBuffer<f64, MAX_STABILITY_WINDOW>,
+    expected_points: u8,
+}
+
+impl CalibrationProcedure {
+    /// Creates a procedure for the given points.
+    ///
+    /// `expected_points` is the value `Cal,?` should report once all points are
+    /// calibrated, which differs between device types.
+    pub fn new(
+        address: u32,
+        device_type: DeviceType,
+        points: heapless::Vec<CalibrationPoint, MAX_CALIBRATION_POINTS>,
+        criteria: StabilityCriteria,
+        expected_points: u8,
+    ) -> Self {
+        let state = if points.is_empty() {
+            CalibrationState::Verifying
+        } else {
+            defmt::info!(
+                "[calibration] Sensor {}: {}",
+                address,
+                points[0].instruction
+            );
+            CalibrationState::AwaitingOperator { point: 0 }
+        };
+
+        Self {
+            address,
+            device_type,
+            state,
+            points,
+            criteria,
+            readings: HistoryBuffer::new(),
+            expected_points,
+        }
+    }
+
+    /// Returns the instruction for the operator, if the procedure is waiting on them.
+    pub fn instruction(&self) -> Option<&'static str> {
+        match self.state {
+            CalibrationState::AwaitingOperator { point } => Some(self.points[point].instruction),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the procedure needs readings to be taken and pushed.
+    pub fn wants_readings(&self) -> bool {
+        matches!(self.state, CalibrationState::Stabilising { .. })
+    }
+
+    /// Returns whether the procedure has completed or failed.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state,
+            CalibrationState::Complete | CalibrationState::Failed(_)
+        )
+    }
+
+    /// Confirms that the operator has prepared the current point.
+    pub fn confirm(&mut self) {
+        if let CalibrationState::AwaitingOperator { point } = self.state {
+            defmt::info!(
+                "[calibration] Sensor {}: waiting for point {} to stabilise.",
+                self.address,
+                point
+            );
+
+            self.readings.clear();
+            self.state = CalibrationState::Stabilising { point };
+        }
+    }
+
+    /// Cancels the procedure.
+    pub fn cancel(&mut self) {
+        if !self.is_finished() {
+            self.state = CalibrationState::Failed(CalibrationError::Cancelled);
+        }
+    }
+
+    /// Records a reading taken while stabilising.
+    pub fn push_reading(&mut self, value: f64) {
+        let CalibrationState::Stabilising { point } = self.state else {
+            return;
+        };
+
+        self.readings.write(value);
+
+        if self.criteria.is_stable(&self.readings) {
+            defmt::info!(
+                "[calibration] Sensor {}: point {} stable at {}.",
+                self.address,
+                point,
+                value
+            );
+
+            self.state = CalibrationState::Calibrating { point };
+        }
+    }
+
+    /// Returns the command to issue for the current state, if any.
+    pub fn due_command(&self) -> Option<CommandBuffer> {
+        match self.state {
+            CalibrationState::Calibrating { point } => Some(self.points[point].command.clone()),
+            CalibrationState::Verifying => format_command(format_args!("Cal,?")),
+            _ => None,
+        }
+    }
+
+    /// Handles the response to the command returned by [`CalibrationProcedure::due_command`].
+    ///
+    /// `response` is the response data, or the fault that stopped it being read.
+    pub fn handle_response(&mut self, response: Result<&[u8], SensorFault>) {
+        let response = match (self.state, response) {
+            (_, Ok(response)) => response,
+            (CalibrationState::Calibrating { point }, Err(SensorFault::UnknownCommand)) => {
+                self.fail(CalibrationError::Rejected { point });
+                return;
+            }
+            (_, Err(fault)) => {
+                self.fail(CalibrationError::Fault(fault));
+                return;
+            }
+        };
+
+        match self.state {
+            CalibrationState::Calibrating { point } => {
+                let next = point + 1;
+
+                self.state = match self.points.get(next) {
+                    Some(next_point) => {
+                        defmt::info!(
+                            "[calibration] Sensor {}: {}",
+                            self.address,
+                            next_point.instruction
+                        );
+                        CalibrationState::AwaitingOperator { point: next }
+                    }
+                    None => CalibrationState::Verifying,
+                };
+            }
+            CalibrationState::Verifying => {
+                let reported = response_data(response)
+                    .and_then(|data| strip_response_prefix(data, "?CAL,"))
+                    .and_then(|points| points.trim().parse::<u8>().ok());
+
+                match reported {
+                    Some(reported) if reported == self.expected_points => {
+                        defmt::info!("[calibration] Sensor {}: calibrated.", self.address);
+                        self.state = CalibrationState::Complete;
+                    }
+                    Some(reported) => self.fail(CalibrationError::VerificationFailed {
+                        expected: self.expected_points,
+                        reported,
+                    }),
+                    None => self.fail(CalibrationError::InvalidVerification),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn fail(&mut self, error: CalibrationError) {
+        defmt::warn!(
+            "[calibration] Sensor {}: calibration failed, {}.",
+            self.address,
+            error
+        );
+
+        self.state = CalibrationState::Failed(error);
+    }
+}
+
+/// Reads a device's calibration data with `Export`, so it can be restored later.
+///
+/// `Export,?` reports how many strings there are, then each `Export` returns the
+/// next string until the device responds with `*DONE`.
+pub struct CalibrationExport {
+    pub data: CalibrationData,
+    expected_chunks: Option<usize>,
+    done: bool,
+    failed: bool,
+}
+
+impl CalibrationExport {
+    pub const fn new() -> Self {
+        Self {
+            data: heapless::Vec::new(),
+            expected_chunks: None,
+            done: false,
+            failed: false,
+        }
+    }
+
+    /// Returns whether the export finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.done || self.failed
+    }
+
+    /// Returns the exported data, if the export completed successfully.
+    pub fn result(&self) -> Option<&CalibrationData> {
+        (self.done && !self.failed).then_some(&self.data)
+    }
+
+    /// Returns the command to issue next, if any.
+    pub fn due_command(&self) -> Option<CommandBuffer> {
+        if self.is_finished() {
+            None
+        } else if self.expected_chunks.is_none() {
+            format_command(format_args!("Export,?"))
+        } else {
+            format_command(format_args!("Export"))
+        }
+    }
+
+    /// Handles the response to the command returned by [`CalibrationExport::due_command`].
+    ///
+    /// `response` is the response data, or the fault that stopped it being read.
+    pub fn handle_response(&mut self, response: Result<&[u8], SensorFault>) {
+        let Some(data) = response.ok().and_then(response_data) else {
+            self.failed = true;
+            return;
+        };
+
+        let Some(expected_chunks) = self.expected_chunks else {
+            // `Export,?` responds with `<strings>,<bytes>`
+            let chunks = strip_response_prefix(data, "?EXPORT,")
+                .unwrap_or(data)
+                .split(',')
+                .next()
+                .and_then(|chunks| chunks.trim().parse::<usize>().ok());
+
+            match chunks {
+                Some(chunks) if chunks <= MAX_EXPORT_CHUNKS => self.expected_chunks = Some(chunks),
+                _ => self.failed = true,
+            }
+
+            return;
+        };
+
+        if data == "*DONE" {
+            self.done = true;
+            self.failed = self.data.len() != expected_chunks;
+            return;
+        }
+
+        let mut chunk = heapless::String::new();
+
+        if chunk.push_str(data).is_err() || self.data.push(chunk).is_err() {
+            self.failed = true;
+        }
+    }
+}
+
+impl Default for CalibrationExport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Restores calibration data previously read with [`CalibrationExport`] using `Import`.
+pub struct CalibrationImport {
+    data: CalibrationData,
+    next_chunk: usize,
+    failed: bool,
+}
+
+impl CalibrationImport {
+    pub fn new(data: CalibrationData) -> Self {
+        Self {
+            data,
+            next_chunk: 0,
+            failed: false,
+        }
+    }
+
+    /// Returns whether the import finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.failed || self.next_chunk >= self.data.len()
+    }
+
+    /// Returns whether all data was imported.
+    pub fn succeeded(&self) -> bool {
+        !self.failed && self.next_chunk >= self.data.len()
+    }
+
+    /// Returns the command to issue next, if any.
+    pub fn due_command(&self) -> Option<CommandBuffer> {
+        if self.is_finished() {
+            return None;
+        }
+
+        format_command(format_args!("Import,{}", self.data[self.next_chunk]))
+    }
+
+    /// Handles the response to the command returned by [`CalibrationImport::due_command`].
+    ///
+    /// `response` is the response data, or the fault that stopped it being read.
+    pub fn handle_response(&mut self, response: Result<&[u8], SensorFault>) {
+        match response {
+            Ok(_) => self.next_chunk += 1,
+            Err(_) => self.failed = true,
+        }
+    }
+}
+
+/// Calibration work on a device, run by the sensor scheduler before its samples.
+pub enum CalibrationJob {
+    /// Calibrating the device, point by point.
+    Procedure(CalibrationProcedure),
+    /// Reading the device's calibration data, so it can be stored.
+    Export {
+        address: u32,
+        export: CalibrationExport,
+    },
+    /// Restoring stored calibration data to the device.
+    Import {
+        address: u32,
+        import: CalibrationImport,
+    },
+}
+
+impl CalibrationJob {
+    /// Returns the address of the device being worked on.
+    pub fn address(&self) -> u32 {
+        match self {
+            CalibrationJob::Procedure(procedure) => procedure.address,
+            CalibrationJob::Export { address, .. } | CalibrationJob::Import { address, .. } => {
+                *address
+            }
+        }
+    }
+
+    /// Returns the command to issue next, if any.
+    pub fn due_command(&self) -> Option<CommandBuffer> {
+        match self {
+            CalibrationJob::Procedure(procedure) => procedure.due_command(),
+            CalibrationJob::Export { export, .. } => export.due_command(),
+            CalibrationJob::Import { import, .. } => import.due_command(),
+        }
+    }
+
+    /// Handles the response to the command returned by [`CalibrationJob::due_command`].
+    pub fn handle_response(&mut self, response: Result<&[u8], SensorFault>) {
+        match self {
+            CalibrationJob::Procedure(procedure) => procedure.handle_response(response),
+            CalibrationJob::Export { export, .. } => export.handle_response(response),
+            CalibrationJob::Import { import, .. } => import.handle_response(response),
+        }
+    }
+
+    /// Records a reading of the device, taken while the job runs.
+    pub fn push_reading(&mut self, value: f64) {
+        if let CalibrationJob::Procedure(procedure) = self {
+            procedure.push_reading(value);
+        }
+    }
+
+    /// Returns whether the job has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        match self {
+            CalibrationJob::Procedure(procedure) => procedure.is_finished(),
+            CalibrationJob::Export { export, .. } => export.is_finished(),
+            CalibrationJob::Import { import, .. } => import.is_finished(),
+        }
+    }
+}
+
+/// Returns the key of the record holding the calibration data of the device at `address`.
+pub fn calibration_data_key(address: u8) -> u8 {
+    0x80 | address
+}
+
+/// Stores a device's calibration data, read with [`CalibrationExport`], in `store`.
+pub fn save_calibration_data<F: Flash>(
+    store: &mut RecordStore<F>,
+    address: u8,
+    data: &CalibrationData,
+) -> Result<(), StoreError> {
+    let mut record: heapless::Vec<u8, MAX_RECORD_LEN> = heapless::Vec::new();
+
+    // Each string is stored with its length
+    for chunk in data {
+        if record.push(chunk.len() as u8).is_err()
+            || record.extend_from_slice(chunk.as_bytes()).is_err()
+        {
+            return Err(StoreError::TooLarge);
+        }
+    }
+
+    store.store(
+        calibration_data_key(address),
+        CALIBRATION_DATA_VERSION,
+        &record,
+    )
+}
+
+/// Loads the calibration data of the device at `address` from `store`, if it's stored.
+pub fn load_calibration_data<F: Flash>(
+    store: &mut RecordStore<F>,
+    address: u8,
+) -> Result<Option<CalibrationData>, StoreError> {
+    let mut buffer = [0; MAX_RECORD_LEN];
+
+    let Some((CALIBRATION_DATA_VERSION, len)) =
+        store.fetch(calibration_data_key(address), &mut buffer)?
+    else {
+        return Ok(None);
+    };
+
+    let mut data = CalibrationData::new();
+    let mut record = &buffer[..len];
+
+    while let [len, rest @ ..] = record {
+        let Some((chunk, rest)) = rest.split_at_checked(usize::from(*len)) else {
+            return Ok(None);
+        };
+        record = rest;
+
+        let mut string = heapless::String::new();
+        let chunk = core::str::from_utf8(chunk).ok();
+
+        if chunk.is_none_or(|chunk| string.push_str(chunk).is_err()) || data.push(string).is_err() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(data))
+}
+
+/// A completed calibration of a device.
+#[derive(Clone, Copy)]
+pub struct CalibrationRecord {
+    pub address: u32,
+    pub device_type: DeviceType,
+    /// When the calibration completed, if the time was known.
+    pub calibrated_at: Option<Timestamp>,
+    /// Calibration status reported by `Cal,?`.
+    pub points: u8,
+}
+
+impl CalibrationRecord {
+    /// Encodes the record, little-endian.
+    ///
+    /// The layout is the address (u32), the calibration status, the time in
+    /// milliseconds since the Unix epoch (u64, all ones if it wasn't known)
+    /// and the device type's code, padded with spaces.
+    fn encode(&self) -> [u8; CALIBRATION_RECORD_LEN] {
+        let mut data = [b' '; CALIBRATION_RECORD_LEN];
+        let code = self.device_type.code().as_bytes();
+        let time = self.calibrated_at.map_or(u64::MAX, |at| at.millis());
+
+        data[0..4].copy_from_slice(&self.address.to_le_bytes());
+        data[4] = self.points;
+        data[5..13].copy_from_slice(&time.to_le_bytes());
+        data[13..13 + code.len().min(DEVICE_CODE_LEN)]
+            .copy_from_slice(&code[..code.len().min(DEVICE_CODE_LEN)]);
+
+        data
+    }
+
+    /// Decodes a record encoded by [`CalibrationRecord::encode`].
+    fn decode(data: &[u8; CALIBRATION_RECORD_LEN]) -> Option<Self> {
+        let (address, data) = data.split_first_chunk::<4>()?;
+        let (points, data) = data.split_first()?;
+        let (time, code) = data.split_first_chunk::<8>()?;
+        let time = u64::from_le_bytes(*time);
+
+        Some(Self {
+            address: u32::from_le_bytes(*address),
+            device_type: core::str::from_utf8(code).ok()?.trim_end().parse().ok()?,
+            calibrated_at: (time != u64::MAX).then(|| Timestamp::from_millis(time)),
+            points: *points,
+        })
+    }
+}
+
+/// Log of the most recent calibration events across all devices.
+pub struct CalibrationLog<const SIZE: usize> {
+    records: HistoryBuffer<CalibrationRecord, SIZE>,
+}
+
+impl<const SIZE: usize> CalibrationLog<SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            records: HistoryBuffer::new(),
+        }
+    }
+
+    /// Records a completed calibration procedure.
+    pub fn record(&mut self, procedure: &CalibrationProcedure, now: Option<Timestamp>) {
+        if !matches!(procedure.state, CalibrationState::Complete) {
+            return;
+        }
+
+        let record = CalibrationRecord {
+            address: procedure.address,
+            device_type: procedure.device_type,
+            calibrated_at: now,
+            points: procedure.expected_points,
+        };
+
+        defmt::info!(
+            "[calibration] Logged calibration of {} sensor {} at {}.",
+            record.device_type,
+            record.address,
+            record.calibrated_at
+        );
+
+        self.records.write(record);
+    }
+
+    /// Returns the most recent calibration of the device at `address`.
+    pub fn last_calibration(&self, address: u32) -> Option<&CalibrationRecord> {
+        self.records
+            .oldest_ordered()
+            .filter(|record| record.address == address)
+            .last()
+    }
+
+    /// Iterates over the logged calibrations, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &CalibrationRecord> {
+        self.records.oldest_ordered()
+    }
+
+    /// Loads the log from `store`, or returns an empty log if it can't be read.
+    pub fn load<F: Flash>(store: &mut RecordStore<F>) -> Self {
+        let mut log = Self::new();
+        let mut buffer = [0; MAX_RECORD_LEN];
+
+        match store.fetch(CALIBRATION_LOG_KEY, &mut buffer) {
+            Ok(Some((CALIBRATION_LOG_VERSION, len))) => {
+                for data in buffer[..len].as_chunks::<CALIBRATION_RECORD_LEN>().0 {
+                    match CalibrationRecord::decode(data) {
+                        Some(record) => log.records.write(record),
+                        None => defmt::warn!("[calibration] Skipping corrupt calibration record."),
+                    }
+                }
+            }
+            Ok(Some((version, _))) => defmt::warn!(
+                "[calibration] Calibration log version {} isn't supported, starting a new log.",
+                version
+            ),
+            Ok(None) => {}
+            Err(error) => {
+                defmt::error!("[calibration] Couldn't read calibration log: {}", error)
+            }
+        }
+
+        log
+    }
+
+    /// Stores the log in `store`, so it's kept across reboots.
+    pub fn save<F: Flash>(&self, store: &mut RecordStore<F>) -> Result<(), StoreError> {
+        let mut data: heapless::Vec<u8, MAX_RECORD_LEN> = heapless::Vec::new();
+
+        for record in self.records() {
+            data.extend_from_slice(&record.encode())
+                .map_err(|_| StoreError::TooLarge)?;
+        }
+
+        store.store(CALIBRATION_LOG_KEY, CALIBRATION_LOG_VERSION, &data)
+    }
+}
+
+impl<const SIZE: usize> Default for CalibrationLog<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,276 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::{
+    format_command, response_data, AtlasSensor, CommandBuffer, DeviceType, FilterConfig,
+    PendingAction,
+};
+
+/// Time after power-up during which the EZO-CO2's readings are invalid.
+pub const CO2_WARM_UP: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(10_000);
+
+/// Warm-up progress of an EZO-CO2 since it last powered up.
+#[derive(Clone, Copy)]
+pub enum WarmUp {
+    /// The device has powered up, but hasn't been sampled yet.
+    PoweredUp,
+    /// Readings are invalid until the deadline.
+    Until(Instant<u32, 1, 1000>),
+    /// Readings are valid.
+    Complete,
+}
+
+/// Alarm commands supported by the EZO-CO2.
+///
+/// The alarm pin is driven high when the concentration exceeds the set point,
+/// and released once it falls below the set point minus the tolerance.
+#[derive(Clone, Copy)]
+pub enum Co2Alarm {
+    /// Enables the alarm.
+    Enable,
+    /// Disables the alarm.
+    Disable,
+    /// Sets the alarm threshold in ppm.
+    SetPoint(u32),
+    /// Sets the alarm tolerance in ppm, between 0 and 500.
+    Tolerance(u32),
+}
+
+impl Co2Alarm {
+    /// Returns the command for this alarm setting.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            Co2Alarm::Enable => format_command(format_args!("Alarm,en,1")),
+            Co2Alarm::Disable => format_command(format_args!("Alarm,en,0")),
+            Co2Alarm::SetPoint(ppm) => format_command(format_args!("Alarm,set,{ppm}")),
+            Co2Alarm::Tolerance(ppm) if *ppm <= 500 => {
+                format_command(format_args!("Alarm,tol,{ppm}"))
+            }
+            Co2Alarm::Tolerance(_) => None,
+        }
+    }
+}
+
+/// A single reading from an EZO-CO2.
+#[derive(Clone, Copy)]
+pub struct Co2Reading {
+    /// Gaseous CO2 concentration in ppm.
+    pub ppm: f64,
+    /// Internal temperature of the sensor in °C, if its output is enabled.
+    pub internal_temperature: Option<f64>,
+    /// Whether the reading was taken during warm-up, and is invalid.
+    pub warming_up: bool,
+}
+
+impl Co2Reading {
+    /// Parses a reading in the form `<ppm>[,<temperature>]`.
+    pub fn parse(data: &str, internal_temperature: bool, warming_up: bool) -> Option<Self> {
+        let mut fields = data.split(',');
+
+        let ppm = fields.next()?.trim().parse().ok()?;
+        let internal_temperature = if internal_temperature {
+            Some(fields.next()?.trim().parse().ok()?)
+        } else {
+            None
+        };
+
+        Some(Self {
+            ppm,
+            internal_temperature,
+            warming_up,
+        })
+    }
+}
+
+/// Atlas Scientific EZO-CO2 embedded gaseous CO2 sensor.
+pub struct Co2Sensor {
+    /// I2C address of the device.
+    pub address: u32,
+    /// Whether the internal temperature output is enabled.
+    pub internal_temperature: bool,
+    pub warm_up: WarmUp,
+    pub last_reading: Option<Co2Reading>,
+    pub action: PendingAction,
+}
+
+impl Co2Sensor {
+    pub const fn new() -> Self {
+        Self {
+            address: DeviceType::Co2.default_address() as u32,
+            internal_temperature: true,
+            warm_up: WarmUp::PoweredUp,
+            last_reading: None,
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Records the internal temperature output being toggled, returning the command that applies it.
+    ///
+    /// Readings are parsed according to the recorded output, so the returned
+    /// command must be sent before the next sample.
+    pub fn set_internal_temperature(&mut self, enabled: bool) -> &'static [u8] {
+        self.internal_temperature = enabled;
+
+        if enabled {
+            b"O,t,1"
+        } else {
+            b"O,t,0"
+        }
+    }
+
+    /// Returns the latest reading, unless it was taken during warm-up.
+    pub fn reported_reading(&self) -> Option<&Co2Reading> {
+        self.last_reading
+            .as_ref()
+            .filter(|reading| !reading.warming_up)
+    }
+}
+
+impl Default for Co2Sensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasSensor for Co2Sensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Co2
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn setup_command(&self, index: usize) -> Option<&'static [u8]> {
+        // The internal temperature output as it was last set
+        (index == 0).then_some(if self.internal_temperature {
+            b"O,t,1"
+        } else {
+            b"O,t,0"
+        })
+    }
+
+    fn reset_command(&self) -> Option<&'static [u8]> {
+        Some(b"Factory")
+    }
+
+    fn sleeps_between_samples(&self) -> bool {
+        // Every wake needs another warm-up, so sleeping rarely pays off
+        false
+    }
+
+    fn after_wake(&mut self) {
+        self.warm_up = WarmUp::PoweredUp;
+    }
+
+    fn after_restart(&mut self) {
+        self.warm_up = WarmUp::PoweredUp;
+    }
+
+    fn before_sample(&mut self, now: Instant<u32, 1, 1000>) {
+        // Warm-up is measured from the first sample, which is never earlier than power-up
+        self.warm_up = match self.warm_up {
+            WarmUp::PoweredUp => WarmUp::Until(now + CO2_WARM_UP),
+            WarmUp::Until(deadline) if now >= deadline => WarmUp::Complete,
+            warm_up => warm_up,
+        };
+    }
+
+    fn warming_up(&self) -> bool {
+        !matches!(self.warm_up, WarmUp::Complete)
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 10.0,
+            stable_deviation: 25.0,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        let warming_up = self.warming_up();
+
+        if let Some(reading) = response_data(response)
+            .and_then(|data| Co2Reading::parse(data, self.internal_temperature, warming_up))
+        {
+            self.last_reading = Some(reading);
+        }
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: u32) -> Instant<u32, 1, 1000> {
+        Instant::<u32, 1, 1000>::from_ticks(millis)
+    }
+
+    /// Samples `sensor` at `millis`, as the firmware does, and returns whether the reading is reported.
+    fn sample(sensor: &mut Co2Sensor, millis: u32) -> bool {
+        sensor.before_sample(at(millis));
+        sensor.handle_response(b"412,25.1\0");
+        sensor.reported_reading().is_some()
+    }
+
+    #[test]
+    fn readings_are_held_back_during_warm_up() {
+        let mut sensor = Co2Sensor::new();
+
+        // Warm-up starts from the first sample
+        assert!(!sample(&mut sensor, 5_000));
+        assert!(!sample(&mut sensor, 14_999));
+        assert!(sample(&mut sensor, 15_000));
+
+        let reading = sensor.reported_reading().unwrap();
+        assert_eq!(reading.ppm, 412.0);
+        assert_eq!(reading.internal_temperature, Some(25.1));
+    }
+
+    #[test]
+    fn a_restart_starts_warm_up_over() {
+        let mut sensor = Co2Sensor::new();
+        assert!(!sample(&mut sensor, 0));
+        assert!(sample(&mut sensor, 10_000));
+
+        // Such as after the Factory reset while recovering from a fault
+        sensor.after_restart();
+        assert!(sensor.warming_up());
+        assert!(!sample(&mut sensor, 20_000));
+        assert!(!sample(&mut sensor, 29_999));
+        assert!(sample(&mut sensor, 30_000));
+    }
+
+    #[test]
+    fn waking_starts_warm_up_over() {
+        let mut sensor = Co2Sensor::new();
+        assert!(!sample(&mut sensor, 0));
+        assert!(sample(&mut sensor, 10_000));
+
+        sensor.after_wake();
+        assert!(!sample(&mut sensor, 60_000));
+        assert!(sample(&mut sensor, 70_000));
+    }
+}
@@ -0,0 +1,290 @@
+use super::{
+    format_command, response_data, AtlasSensor, CommandBuffer, DeviceType, FilterConfig,
+    PendingAction,
+};
+
+/// Cell constant of the conductivity probe attached to the EZO-EC.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProbeK {
+    /// K 0.1, for ultra pure water up to ~400 µS/cm.
+    PointOne,
+    /// K 1.0, for general purpose use such as nutrient solution.
+    One,
+    /// K 10, for sea water and brines.
+    Ten,
+}
+
+impl ProbeK {
+    /// Returns the command used to configure the EZO-EC for this probe.
+    pub fn command(&self) -> &'static [u8] {
+        match self {
+            ProbeK::PointOne => b"K,0.1",
+            ProbeK::One => b"K,1.0",
+            ProbeK::Ten => b"K,10",
+        }
+    }
+}
+
+/// Individual values that the EZO-EC can include in a reading.
+///
+/// Values are always reported in this order, with disabled values omitted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConductivityOutput {
+    /// Electrical conductivity in µS/cm.
+    Conductivity,
+    /// Total dissolved solids in ppm.
+    TotalDissolvedSolids,
+    /// Salinity in PSU (ppt).
+    Salinity,
+    /// Specific gravity, only meaningful for sea water.
+    SpecificGravity,
+}
+
+impl ConductivityOutput {
+    /// Every output, in the order values are reported.
+    pub const ALL: [ConductivityOutput; 4] = [
+        ConductivityOutput::Conductivity,
+        ConductivityOutput::TotalDissolvedSolids,
+        ConductivityOutput::Salinity,
+        ConductivityOutput::SpecificGravity,
+    ];
+
+    /// Returns the command that enables or disables this output.
+    pub fn command(&self, enabled: bool) -> &'static [u8] {
+        match (self, enabled) {
+            (ConductivityOutput::Conductivity, true) => b"O,EC,1",
+            (ConductivityOutput::Conductivity, false) => b"O,EC,0",
+            (ConductivityOutput::TotalDissolvedSolids, true) => b"O,TDS,1",
+            (ConductivityOutput::TotalDissolvedSolids, false) => b"O,TDS,0",
+            (ConductivityOutput::Salinity, true) => b"O,S,1",
+            (ConductivityOutput::Salinity, false) => b"O,S,0",
+            (ConductivityOutput::SpecificGravity, true) => b"O,SG,1",
+            (ConductivityOutput::SpecificGravity, false) => b"O,SG,0",
+        }
+    }
+}
+
+/// Set of outputs currently enabled on an EZO-EC.
+#[derive(Clone, Copy)]
+pub struct ConductivityOutputs {
+    pub conductivity: bool,
+    pub total_dissolved_solids: bool,
+    pub salinity: bool,
+    pub specific_gravity: bool,
+}
+
+impl ConductivityOutputs {
+    /// All outputs enabled, matching the EZO-EC factory configuration.
+    pub const fn all() -> Self {
+        Self {
+            conductivity: true,
+            total_dissolved_solids: true,
+            salinity: true,
+            specific_gravity: true,
+        }
+    }
+
+    pub fn is_enabled(&self, output: ConductivityOutput) -> bool {
+        match output {
+            ConductivityOutput::Conductivity => self.conductivity,
+            ConductivityOutput::TotalDissolvedSolids => self.total_dissolved_solids,
+            ConductivityOutput::Salinity => self.salinity,
+            ConductivityOutput::SpecificGravity => self.specific_gravity,
+        }
+    }
+
+    fn set(&mut self, output: ConductivityOutput, enabled: bool) {
+        match output {
+            ConductivityOutput::Conductivity => self.conductivity = enabled,
+            ConductivityOutput::TotalDissolvedSolids => self.total_dissolved_solids = enabled,
+            ConductivityOutput::Salinity => self.salinity = enabled,
+            ConductivityOutput::SpecificGravity => self.specific_gravity = enabled,
+        }
+    }
+}
+
+impl Default for ConductivityOutputs {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// A single reading from an EZO-EC.
+///
+/// Values are `None` when their output is disabled on the device.
+#[derive(Clone, Copy, Default)]
+pub struct ConductivityReading {
+    /// Electrical conductivity in µS/cm.
+    pub conductivity: Option<f64>,
+    /// Total dissolved solids in ppm.
+    pub total_dissolved_solids: Option<f64>,
+    /// Salinity in PSU (ppt).
+    pub salinity: Option<f64>,
+    /// Specific gravity.
+    pub specific_gravity: Option<f64>,
+}
+
+impl ConductivityReading {
+    /// Parses the comma separated reading of an EZO-EC with the given outputs enabled.
+    pub fn parse(data: &str, outputs: ConductivityOutputs) -> Option<Self> {
+        let mut fields = data.split(',');
+        let mut reading = Self::default();
+
+        let mut next_field = |output| -> Option<Option<f64>> {
+            if outputs.is_enabled(output) {
+                Some(Some(fields.next()?.trim().parse().ok()?))
+            } else {
+                Some(None)
+            }
+        };
+
+        reading.conductivity = next_field(ConductivityOutput::Conductivity)?;
+        reading.total_dissolved_solids = next_field(ConductivityOutput::TotalDissolvedSolids)?;
+        reading.salinity = next_field(ConductivityOutput::Salinity)?;
+        reading.specific_gravity = next_field(ConductivityOutput::SpecificGravity)?;
+
+        Some(reading)
+    }
+}
+
+/// Calibration commands supported by the EZO-EC.
+///
+/// Dry calibration should always be performed first. Values are in µS/cm.
+#[derive(Clone, Copy)]
+pub enum ConductivityCalibration {
+    /// Calibrates the probe while dry, in open air.
+    Dry,
+    /// Single point calibration in a solution of known conductivity.
+    Single(f64),
+    /// Low point of a two point calibration.
+    Low(f64),
+    /// High point of a two point calibration.
+    High(f64),
+    /// Clears all calibration data.
+    Clear,
+}
+
+impl ConductivityCalibration {
+    /// Returns the command that performs this calibration step.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            ConductivityCalibration::Dry => format_command(format_args!("Cal,dry")),
+            ConductivityCalibration::Single(value) => format_command(format_args!("Cal,{value}")),
+            ConductivityCalibration::Low(value) => format_command(format_args!("Cal,low,{value}")),
+            ConductivityCalibration::High(value) => {
+                format_command(format_args!("Cal,high,{value}"))
+            }
+            ConductivityCalibration::Clear => format_command(format_args!("Cal,clear")),
+        }
+    }
+}
+
+/// Atlas Scientific EZO-EC conductivity circuit.
+pub struct ConductivitySensor {
+    /// I2C address of the device.
+    pub address: u32,
+    pub probe: ProbeK,
+    pub outputs: ConductivityOutputs,
+    pub last_reading: ConductivityReading,
+    pub action: PendingAction,
+}
+
+impl ConductivitySensor {
+    pub const fn new(probe: ProbeK) -> Self {
+        Self {
+            address: DeviceType::Conductivity.default_address() as u32,
+            probe,
+            outputs: ConductivityOutputs::all(),
+            last_reading: ConductivityReading {
+                conductivity: None,
+                total_dissolved_solids: None,
+                salinity: None,
+                specific_gravity: None,
+            },
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Records an output being enabled or disabled, returning the command that applies it.
+    ///
+    /// Readings are parsed according to the recorded outputs, so the returned
+    /// command must be sent before the next sample.
+    pub fn set_output(&mut self, output: ConductivityOutput, enabled: bool) -> &'static [u8] {
+        self.outputs.set(output, enabled);
+
+        output.command(enabled)
+    }
+
+    /// Returns the command setting the factor used to convert conductivity to TDS.
+    ///
+    /// The EZO-EC accepts factors between 0.01 and 1.00, defaulting to 0.54.
+    pub fn tds_factor_command(factor: f64) -> Option<CommandBuffer> {
+        if !(0.01..=1.0).contains(&factor) {
+            return None;
+        }
+
+        format_command(format_args!("TDS,{factor}"))
+    }
+}
+
+impl AtlasSensor for ConductivitySensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Conductivity
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn setup_command(&self, index: usize) -> Option<&'static [u8]> {
+        // The probe is set first, then each output as it was last set
+        match index {
+            0 => Some(self.probe.command()),
+            index => ConductivityOutput::ALL
+                .get(index - 1)
+                .map(|output| output.command(self.outputs.is_enabled(*output))),
+        }
+    }
+
+    fn temperature_compensated(&self) -> bool {
+        true
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 5.0,
+            stable_deviation: 10.0,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        if let Some(reading) =
+            response_data(response).and_then(|data| ConductivityReading::parse(data, self.outputs))
+        {
+            self.last_reading = reading;
+        }
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
@@ -0,0 +1,249 @@
+use super::{
+    format_command, response_data, AtlasSensor, CommandBuffer, DeviceType, FilterConfig,
+    PendingAction,
+};
+
+/// Individual values that the EZO-DO can include in a reading.
+///
+/// Values are always reported in this order, with disabled values omitted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DissolvedOxygenOutput {
+    /// Dissolved oxygen concentration in mg/L.
+    Concentration,
+    /// Dissolved oxygen as a percentage of saturation.
+    Saturation,
+}
+
+impl DissolvedOxygenOutput {
+    /// Every output, in the order values are reported.
+    pub const ALL: [DissolvedOxygenOutput; 2] = [
+        DissolvedOxygenOutput::Concentration,
+        DissolvedOxygenOutput::Saturation,
+    ];
+
+    /// Returns the command that enables or disables this output.
+    pub fn command(&self, enabled: bool) -> &'static [u8] {
+        match (self, enabled) {
+            (DissolvedOxygenOutput::Concentration, true) => b"O,mg,1",
+            (DissolvedOxygenOutput::Concentration, false) => b"O,mg,0",
+            (DissolvedOxygenOutput::Saturation, true) => b"O,%,1",
+            (DissolvedOxygenOutput::Saturation, false) => b"O,%,0",
+        }
+    }
+}
+
+/// Set of outputs currently enabled on an EZO-DO.
+#[derive(Clone, Copy)]
+pub struct DissolvedOxygenOutputs {
+    pub concentration: bool,
+    pub saturation: bool,
+}
+
+impl DissolvedOxygenOutputs {
+    /// All outputs enabled.
+    pub const fn all() -> Self {
+        Self {
+            concentration: true,
+            saturation: true,
+        }
+    }
+
+    pub fn is_enabled(&self, output: DissolvedOxygenOutput) -> bool {
+        match output {
+            DissolvedOxygenOutput::Concentration => self.concentration,
+            DissolvedOxygenOutput::Saturation => self.saturation,
+        }
+    }
+
+    fn set(&mut self, output: DissolvedOxygenOutput, enabled: bool) {
+        match output {
+            DissolvedOxygenOutput::Concentration => self.concentration = enabled,
+            DissolvedOxygenOutput::Saturation => self.saturation = enabled,
+        }
+    }
+}
+
+impl Default for DissolvedOxygenOutputs {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// A single reading from an EZO-DO.
+///
+/// Values are `None` when their output is disabled on the device.
+#[derive(Clone, Copy, Default)]
+pub struct DissolvedOxygenReading {
+    /// Dissolved oxygen concentration in mg/L.
+    pub concentration: Option<f64>,
+    /// Dissolved oxygen as a percentage of saturation.
+    pub saturation: Option<f64>,
+}
+
+impl DissolvedOxygenReading {
+    /// Parses the comma separated reading of an EZO-DO with the given outputs enabled.
+    pub fn parse(data: &str, outputs: DissolvedOxygenOutputs) -> Option<Self> {
+        let mut fields = data.split(',');
+
+        let mut next_field = |output| -> Option<Option<f64>> {
+            if outputs.is_enabled(output) {
+                Some(Some(fields.next()?.trim().parse().ok()?))
+            } else {
+                Some(None)
+            }
+        };
+
+        Some(Self {
+            concentration: next_field(DissolvedOxygenOutput::Concentration)?,
+            saturation: next_field(DissolvedOxygenOutput::Saturation)?,
+        })
+    }
+}
+
+/// Salinity of the solution, used by the EZO-DO for compensation.
+#[derive(Clone, Copy)]
+pub enum SalinityCompensation {
+    /// Salinity given as conductivity in µS/cm.
+    Conductivity(f64),
+    /// Salinity given in parts per thousand.
+    PartsPerThousand(f64),
+}
+
+/// Calibration commands supported by the EZO-DO.
+#[derive(Clone, Copy)]
+pub enum DissolvedOxygenCalibration {
+    /// Calibrates the probe in open air, against atmospheric oxygen.
+    Atmospheric,
+    /// Calibrates the probe in a zero dissolved oxygen solution.
+    Zero,
+    /// Clears all calibration data.
+    Clear,
+}
+
+impl DissolvedOxygenCalibration {
+    /// Returns the command that performs this calibration step.
+    pub fn command(&self) -> &'static [u8] {
+        match self {
+            DissolvedOxygenCalibration::Atmospheric => b"Cal",
+            DissolvedOxygenCalibration::Zero => b"Cal,0",
+            DissolvedOxygenCalibration::Clear => b"Cal,clear",
+        }
+    }
+}
+
+/// Atlas Scientific EZO-DO dissolved oxygen circuit.
+pub struct DissolvedOxygenSensor {
+    /// I2C address of the device.
+    pub address: u32,
+    pub outputs: DissolvedOxygenOutputs,
+    pub last_reading: DissolvedOxygenReading,
+    pub action: PendingAction,
+}
+
+impl DissolvedOxygenSensor {
+    pub const fn new() -> Self {
+        Self {
+            address: DeviceType::DissolvedOxygen.default_address() as u32,
+            outputs: DissolvedOxygenOutputs::all(),
+            last_reading: DissolvedOxygenReading {
+                concentration: None,
+                saturation: None,
+            },
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Records an output being enabled or disabled, returning the command that applies it.
+    ///
+    /// Readings are parsed according to the recorded outputs, so the returned
+    /// command must be sent before the next sample.
+    pub fn set_output(&mut self, output: DissolvedOxygenOutput, enabled: bool) -> &'static [u8] {
+        self.outputs.set(output, enabled);
+
+        output.command(enabled)
+    }
+
+    /// Returns the command setting the salinity used for compensation.
+    pub fn salinity_command(salinity: SalinityCompensation) -> Option<CommandBuffer> {
+        match salinity {
+            SalinityCompensation::Conductivity(value) => format_command(format_args!("S,{value}")),
+            SalinityCompensation::PartsPerThousand(value) => {
+                format_command(format_args!("S,{value},ppt"))
+            }
+        }
+    }
+
+    /// Returns the command setting the atmospheric pressure, in kPa, used for compensation.
+    ///
+    /// The EZO-DO defaults to 101.3 kPa.
+    pub fn pressure_command(kilopascals: f64) -> Option<CommandBuffer> {
+        format_command(format_args!("P,{kilopascals:.1}"))
+    }
+}
+
+impl Default for DissolvedOxygenSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasSensor for DissolvedOxygenSensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::DissolvedOxygen
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn setup_command(&self, index: usize) -> Option<&'static [u8]> {
+        // Each output as it was last set
+        DissolvedOxygenOutput::ALL
+            .get(index)
+            .map(|output| output.command(self.outputs.is_enabled(*output)))
+    }
+
+    fn temperature_compensated(&self) -> bool {
+        true
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        // Bubbles crossing the membrane read low, so reject outliers more eagerly
+        FilterConfig {
+            rejection_sigma: 3.0,
+            min_deviation: 0.05,
+            stable_deviation: 0.1,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        if let Some(reading) = response_data(response)
+            .and_then(|data| DissolvedOxygenReading::parse(data, self.outputs))
+        {
+            self.last_reading = reading;
+        }
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
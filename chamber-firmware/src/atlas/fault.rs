@@ -0,0 +1,140 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::{PendingAction, RestartReason};
+
+/// Number of times a failed action is retried before the device is reset.
+pub const MAX_RETRIES: u8 = 3;
+
+/// Number of times a response is re-read while the device reports it's still busy.
+pub const MAX_BUSY_READS: u8 = 5;
+
+/// Delay before retrying a failed action.
+pub const RETRY_DELAY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(1000);
+
+/// Time a device needs to restart after being reset.
+pub const RESET_DELAY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(2000);
+
+/// Interval at which an offline device is probed to see if it has come back.
+pub const REPROBE_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(60_000);
+
+/// Ways in which communicating with an EZO device can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorFault {
+    /// The device didn't acknowledge a command.
+    Nack,
+    /// The device didn't respond to a read.
+    Timeout,
+    /// The device didn't understand a command, reported with `*ER` or an I2C syntax error.
+    UnknownCommand,
+    /// The device reported its supply voltage is too high with `*OV`.
+    OverVolt,
+    /// The device reported its supply voltage is too low with `*UV`.
+    UnderVolt,
+    /// The device was still processing after [`MAX_BUSY_READS`] reads.
+    Busy,
+    /// The response couldn't be parsed.
+    InvalidResponse,
+}
+
+/// Fault transitions and health warnings of an EZO device, surfaced to the rest of the firmware.
+#[derive(Clone, Copy, defmt::Format)]
+pub enum AtlasEvent {
+    /// A command failed, and will be retried.
+    Retrying {
+        address: u32,
+        fault: SensorFault,
+        attempt: u8,
+    },
+    /// Retrying failed, so the device is being reset.
+    Resetting { address: u32, fault: SensorFault },
+    /// Recovery failed, so the device is offline until it's re-probed.
+    Offline { address: u32, fault: SensorFault },
+    /// A faulted or offline device is working again.
+    Recovered { address: u32 },
+    /// The device restarted unexpectedly, such as from a brown-out.
+    UnexpectedRestart { address: u32, reason: RestartReason },
+    /// The device's supply voltage, in V, left the healthy range.
+    SupplyVoltage { address: u32, vcc: f64 },
+}
+
+/// Tracks a device's consecutive faults, and escalates how it's recovered.
+///
+/// A fault is first retried up to [`MAX_RETRIES`] times. If that doesn't
+/// help, the device is reset, if it supports it. Once resetting has been
+/// tried too, the device is considered offline and re-probed periodically.
+#[derive(Clone, Copy, Default)]
+pub struct FaultTracker {
+    /// Number of faults since the device last worked.
+    pub consecutive: u8,
+    /// The most recent fault.
+    pub last_fault: Option<SensorFault>,
+    reset_attempted: bool,
+}
+
+impl FaultTracker {
+    pub const fn new() -> Self {
+        Self {
+            consecutive: 0,
+            last_fault: None,
+            reset_attempted: false,
+        }
+    }
+
+    /// Returns whether the device has faulted since it last worked.
+    pub fn is_faulted(&self) -> bool {
+        self.consecutive > 0
+    }
+
+    /// Records a fault, returning the action that recovers from it and the event to surface.
+    pub fn fault(
+        &mut self,
+        address: u32,
+        fault: SensorFault,
+        can_reset: bool,
+        now: Instant<u32, 1, 1000>,
+    ) -> (PendingAction, AtlasEvent) {
+        self.consecutive = self.consecutive.saturating_add(1);
+        self.last_fault = Some(fault);
+
+        if self.consecutive <= MAX_RETRIES {
+            (
+                PendingAction::Retry {
+                    fault,
+                    deadline: now + RETRY_DELAY,
+                },
+                AtlasEvent::Retrying {
+                    address,
+                    fault,
+                    attempt: self.consecutive,
+                },
+            )
+        } else if can_reset && !self.reset_attempted {
+            self.reset_attempted = true;
+
+            (
+                PendingAction::Reset {
+                    fault,
+                    deadline: now,
+                },
+                AtlasEvent::Resetting { address, fault },
+            )
+        } else {
+            (
+                PendingAction::Offline {
+                    fault,
+                    deadline: now + REPROBE_INTERVAL,
+                },
+                AtlasEvent::Offline { address, fault },
+            )
+        }
+    }
+
+    /// Records the device working, returning an event if it had been faulted.
+    pub fn recovered(&mut self, address: u32) -> Option<AtlasEvent> {
+        let was_faulted = self.is_faulted();
+
+        *self = Self::new();
+
+        was_faulted.then_some(AtlasEvent::Recovered { address })
+    }
+}
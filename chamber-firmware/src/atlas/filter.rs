@@ -0,0 +1,174 @@
+use heapless::HistoryBuffer;
+
+use crate::clock::Timestamp;
+
+/// Maximum number of recent readings kept by a [`ReadingFilter`].
+pub const MAX_FILTER_WINDOW: usize = 16;
+
+/// Scales the median absolute deviation to estimate the standard deviation of
+/// normally distributed readings.
+const MAD_TO_SIGMA: f64 = 1.4826;
+
+/// Minimum number of readings before outliers can be detected.
+const MIN_FILTER_READINGS: usize = 3;
+
+/// Configuration of a [`ReadingFilter`], in the units of the filtered reading.
+#[derive(Clone, Copy)]
+pub struct FilterConfig {
+    /// Number of recent readings considered, at most [`MAX_FILTER_WINDOW`].
+    pub window: usize,
+    /// Readings further than this many standard deviations from the median are rejected.
+    pub rejection_sigma: f64,
+    /// Lower bound on the estimated standard deviation.
+    ///
+    /// This is roughly the noise floor of the sensor, and stops a window of
+    /// identical readings from rejecting the smallest change.
+    pub min_deviation: f64,
+    /// Readings are stable once the estimated standard deviation is at most this.
+    pub stable_deviation: f64,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            window: 8,
+            rejection_sigma: 3.5,
+            min_deviation: 0.0,
+            stable_deviation: 0.0,
+        }
+    }
+}
+
+/// A reading that has passed through a [`ReadingFilter`].
+#[derive(Clone, Copy, defmt::Format)]
+pub struct FilteredReading {
+    /// The reading as reported by the sensor.
+    pub raw: f64,
+    /// Mean of the recent readings that weren't rejected.
+    pub smoothed: f64,
+    /// Whether the reading was rejected as an outlier, such as a bubble or splash.
+    pub rejected: bool,
+    /// Whether the recent readings have settled.
+    pub stable: bool,
+    /// When the reading was taken, if the time was known.
+    pub at: Option<Timestamp>,
+    /// Whether the clock had been synchronised with the coordinator when the
+    /// reading was taken. Earlier timestamps may be off.
+    pub synced: bool,
+}
+
+/// Rejects outliers and smooths a sensor's readings over a short window.
+///
+/// Outliers are detected against the median and median absolute deviation (MAD)
+/// of the window, which aren't skewed by the outliers themselves. Rejected
+/// readings still enter the window, so a genuine step change is accepted once
+/// it makes up half the window.
+pub struct ReadingFilter {
+    pub config: FilterConfig,
+    readings: HistoryBuffer<f64, MAX_FILTER_WINDOW>,
+    last: Option<FilteredReading>,
+}
+
+impl ReadingFilter {
+    pub const fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            readings: HistoryBuffer::new(),
+            last: None,
+        }
+    }
+
+    /// Returns the most recent filtered reading.
+    pub fn last(&self) -> Option<&FilteredReading> {
+        self.last.as_ref()
+    }
+
+    /// Discards all readings, such as after the sensor restarts.
+    pub fn reset(&mut self) {
+        self.readings.clear();
+        self.last = None;
+    }
+
+    /// Filters a new reading taken `at`, by a clock that was `synced` or not.
+    pub fn push(&mut self, raw: f64, at: Option<Timestamp>, synced: bool) -> FilteredReading {
+        let window = self.config.window.clamp(1, MAX_FILTER_WINDOW);
+
+        let mut values = [0.0; MAX_FILTER_WINDOW];
+        let mut count = 0;
+
+        for value in self
+            .readings
+            .oldest_ordered()
+            .skip(self.readings.len().saturating_sub(window - 1))
+        {
+            values[count] = *value;
+            count += 1;
+        }
+
+        values[count] = raw;
+        count += 1;
+
+        let values = &values[..count];
+
+        let median = median(values);
+        let mut deviations = [0.0; MAX_FILTER_WINDOW];
+        for (deviation, value) in deviations.iter_mut().zip(values.iter()) {
+            *deviation = libm::fabs(value - median);
+        }
+        let sigma =
+            (median_of(&mut deviations[..count]) * MAD_TO_SIGMA).max(self.config.min_deviation);
+
+        let limit = self.config.rejection_sigma * sigma;
+        let rejected = count >= MIN_FILTER_READINGS && libm::fabs(raw - median) > limit;
+
+        // Smooth over the readings that fall within the rejection limit
+        let (sum, accepted) = values
+            .iter()
+            .filter(|value| count < MIN_FILTER_READINGS || libm::fabs(*value - median) <= limit)
+            .fold((0.0, 0.0), |(sum, accepted), value| {
+                (sum + value, accepted + 1.0)
+            });
+        let smoothed = if accepted > 0.0 {
+            sum / accepted
+        } else {
+            median
+        };
+
+        let stable = count >= window && sigma <= self.config.stable_deviation;
+
+        self.readings.write(raw);
+
+        let reading = FilteredReading {
+            raw,
+            smoothed,
+            rejected,
+            stable,
+            at,
+            synced,
+        };
+
+        self.last = Some(reading);
+        reading
+    }
+}
+
+/// Returns the median of `values`, which must not be empty.
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = [0.0; MAX_FILTER_WINDOW];
+    sorted[..values.len()].copy_from_slice(values);
+
+    median_of(&mut sorted[..values.len()])
+}
+
+/// Returns the median of `values`, sorting them in place.
+fn median_of(values: &mut [f64]) -> f64 {
+    values.sort_unstable_by(|a, b| a.total_cmp(b));
+
+    let middle = values.len() / 2;
+
+    if values.len() % 2 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
@@ -0,0 +1,176 @@
+use rtic_monotonics::systick::fugit::Duration;
+
+use super::{
+    default_processing_delay, response_data, AtlasSensor, DeviceType, FilterConfig, PendingAction,
+};
+
+/// Returns the saturation vapour pressure of air at `temperature` °C, in kPa.
+///
+/// Uses the Tetens equation, which is accurate to a few Pa over the range a
+/// growth chamber operates in.
+pub fn saturation_vapour_pressure(temperature: f64) -> f64 {
+    0.61078 * libm::exp(17.27 * temperature / (temperature + 237.3))
+}
+
+/// Returns the vapour pressure deficit in kPa.
+///
+/// `leaf_offset` is the difference between leaf and air temperature in °C.
+/// Leaves are usually slightly cooler than the air around them, so a negative
+/// offset gives the deficit the leaf actually sees. An offset of zero gives
+/// the deficit of the air.
+pub fn vapour_pressure_deficit(
+    air_temperature: f64,
+    relative_humidity: f64,
+    leaf_offset: f64,
+) -> f64 {
+    let actual = saturation_vapour_pressure(air_temperature) * relative_humidity / 100.0;
+
+    saturation_vapour_pressure(air_temperature + leaf_offset) - actual
+}
+
+/// A single reading from an EZO-HUM.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct HumidityReading {
+    /// Relative humidity in %.
+    pub humidity: f64,
+    /// Air temperature in °C, if its output is enabled.
+    pub temperature: Option<f64>,
+    /// Dew point in °C, if its output is enabled.
+    pub dew_point: Option<f64>,
+    /// Vapour pressure deficit in kPa, derived from the humidity and air temperature.
+    pub vapour_pressure_deficit: Option<f64>,
+}
+
+impl HumidityReading {
+    /// Parses a reading in the form `<humidity>[,<temperature>][,Dew,<dew point>]`.
+    ///
+    /// The vapour pressure deficit is derived with the given leaf temperature offset.
+    pub fn parse(data: &str, leaf_offset: f64) -> Option<Self> {
+        let mut fields = data.split(',').map(str::trim);
+
+        let humidity = fields.next()?.parse().ok()?;
+        let mut temperature = None;
+        let mut dew_point = None;
+
+        while let Some(field) = fields.next() {
+            if field == "Dew" {
+                dew_point = Some(fields.next()?.parse().ok()?);
+            } else {
+                temperature = Some(field.parse().ok()?);
+            }
+        }
+
+        Some(Self {
+            humidity,
+            temperature,
+            dew_point,
+            vapour_pressure_deficit: temperature
+                .map(|temperature| vapour_pressure_deficit(temperature, humidity, leaf_offset)),
+        })
+    }
+}
+
+/// Atlas Scientific EZO-HUM embedded humidity sensor.
+pub struct HumiditySensor {
+    /// I2C address of the device.
+    pub address: u32,
+    /// Difference between leaf and air temperature in °C, used to derive the
+    /// vapour pressure deficit.
+    pub leaf_temperature_offset: f64,
+    pub last_reading: Option<HumidityReading>,
+    pub action: PendingAction,
+}
+
+impl HumiditySensor {
+    pub const fn new() -> Self {
+        Self {
+            address: DeviceType::Humidity.default_address() as u32,
+            leaf_temperature_offset: 0.0,
+            last_reading: None,
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+}
+
+impl Default for HumiditySensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasSensor for HumiditySensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Humidity
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn setup_command(&self, index: usize) -> Option<&'static [u8]> {
+        [b"O,T,1".as_slice(), b"O,HUM,1", b"O,Dew,1"]
+            .get(index)
+            .copied()
+    }
+
+    fn reset_command(&self) -> Option<&'static [u8]> {
+        Some(b"Factory")
+    }
+
+    fn air_temperature(&self) -> Option<f64> {
+        self.last_reading?.temperature
+    }
+
+    fn vapour_pressure_deficit(&self) -> Option<f64> {
+        self.last_reading?.vapour_pressure_deficit
+    }
+
+    fn processing_delay(&self, command: &[u8]) -> Duration<u32, 1, 1000> {
+        // The EZO-HUM takes readings much faster than the liquid circuits
+        if command == b"R" {
+            Duration::<u32, 1, 1000>::from_ticks(300)
+        } else {
+            default_processing_delay(command)
+        }
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 0.2,
+            stable_deviation: 0.5,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        if let Some(reading) = response_data(response)
+            .and_then(|data| HumidityReading::parse(data, self.leaf_temperature_offset))
+        {
+            defmt::debug!("[atlas_sensors] Humidity reading {}", reading);
+
+            self.last_reading = Some(reading);
+        }
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
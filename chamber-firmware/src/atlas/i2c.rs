@@ -0,0 +1,102 @@
+use rtic::Mutex;
+use stm32h7xx_hal::hal::blocking::i2c::{Read, Write};
+
+use super::{split_i2c_response, AtlasTransport, I2cStatus, ResponseCode, SensorFault};
+
+/// Buffer holding the data of a single response from an EZO device.
+pub type ResponseBuffer = heapless::Vec<u8, 64>;
+
+/// Writes a command to the EZO device at `address`.
+pub fn write_command<I2C: Write>(
+    i2c: &mut I2C,
+    address: u8,
+    command: &[u8],
+) -> Result<(), SensorFault> {
+    i2c.write(address, command).map_err(|_| SensorFault::Nack)
+}
+
+/// Reads the response to the last command from the EZO device at `address`.
+///
+/// The I2C status byte and any response code are checked, and only the
+/// response data is returned. [`SensorFault::Busy`] is returned while the
+/// device is still processing, in which case the read can be repeated.
+pub fn read_response<I2C: Read>(i2c: &mut I2C, address: u8) -> Result<ResponseBuffer, SensorFault> {
+    let mut buffer = [0u8; 64];
+
+    i2c.read(address, &mut buffer)
+        .map_err(|_| SensorFault::Timeout)?;
+
+    let (status, data) = split_i2c_response(&buffer).ok_or(SensorFault::InvalidResponse)?;
+
+    match status {
+        I2cStatus::Success => {}
+        // Commands that don't return anything respond with no data
+        I2cStatus::NoData => return Ok(ResponseBuffer::new()),
+        I2cStatus::SyntaxError => return Err(SensorFault::UnknownCommand),
+        I2cStatus::Pending => return Err(SensorFault::Busy),
+    }
+
+    match ResponseCode::try_from_probe_response(data) {
+        Some(ResponseCode::UnknownCommand) => Err(SensorFault::UnknownCommand),
+        Some(ResponseCode::OverVolt) => Err(SensorFault::OverVolt),
+        Some(ResponseCode::UnderVolt) => Err(SensorFault::UnderVolt),
+        _ => ResponseBuffer::from_slice(data).map_err(|_| SensorFault::InvalidResponse),
+    }
+}
+
+/// An EZO device in I2C mode, on a shared bus.
+pub struct I2cTransport<'a, I2C> {
+    pub i2c: &'a mut I2C,
+    pub address: u8,
+}
+
+impl<'a, I2C> I2cTransport<'a, I2C> {
+    pub fn new(i2c: &'a mut I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl<I2C: Read + Write> AtlasTransport for I2cTransport<'_, I2C> {
+    fn send_command(&mut self, command: &[u8]) -> Result<(), SensorFault> {
+        write_command(self.i2c, self.address, command)
+    }
+
+    fn read_response(&mut self) -> Result<ResponseBuffer, SensorFault> {
+        read_response(self.i2c, self.address)
+    }
+}
+
+/// An EZO device on an I2C bus shared between tasks.
+///
+/// The bus is only locked for each transfer, so other devices can be talked
+/// to while this one processes a command.
+pub struct SharedI2cTransport<'a, M> {
+    pub i2c: &'a mut M,
+    pub address: u8,
+}
+
+impl<'a, M> SharedI2cTransport<'a, M> {
+    pub fn new(i2c: &'a mut M, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl<M> AtlasTransport for SharedI2cTransport<'_, M>
+where
+    M: Mutex,
+    M::T: Read + Write,
+{
+    fn send_command(&mut self, command: &[u8]) -> Result<(), SensorFault> {
+        let address = self.address;
+
+        self.i2c
+            .lock(|i2c| I2cTransport::new(i2c, address).send_command(command))
+    }
+
+    fn read_response(&mut self) -> Result<ResponseBuffer, SensorFault> {
+        let address = self.address;
+
+        self.i2c
+            .lock(|i2c| I2cTransport::new(i2c, address).read_response())
+    }
+}
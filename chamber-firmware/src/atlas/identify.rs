@@ -0,0 +1,222 @@
+use stm32h7xx_hal::hal::blocking::i2c::{Read, Write};
+
+use super::{split_i2c_response, I2cStatus};
+
+/// Range of I2C addresses scanned for EZO devices, excluding reserved addresses.
+pub const SCAN_ADDRESSES: core::ops::RangeInclusive<u8> = 0x08..=0x77;
+
+/// Time an EZO device needs to process the `i` command, in milliseconds.
+pub const IDENTIFY_DELAY_MS: u32 = 300;
+
+/// Maximum number of devices recorded by a bus scan.
+pub const MAX_DETECTED_DEVICES: usize = 16;
+
+/// Types of EZO devices, as reported by the `i` command.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DeviceType {
+    Ph,
+    Orp,
+    DissolvedOxygen,
+    Conductivity,
+    Temperature,
+    Humidity,
+    Oxygen,
+    Co2,
+    Pump,
+    Flow,
+}
+
+impl DeviceType {
+    /// Returns the lowest firmware version the drivers in this crate support.
+    ///
+    /// The older circuits changed their command set with firmware 2.0.
+    pub fn minimum_firmware(&self) -> FirmwareVersion {
+        match self {
+            DeviceType::Ph
+            | DeviceType::Orp
+            | DeviceType::DissolvedOxygen
+            | DeviceType::Conductivity
+            | DeviceType::Temperature => FirmwareVersion { major: 2, minor: 0 },
+            DeviceType::Humidity
+            | DeviceType::Oxygen
+            | DeviceType::Co2
+            | DeviceType::Pump
+            | DeviceType::Flow => FirmwareVersion { major: 1, minor: 0 },
+        }
+    }
+
+    /// Returns the code the device reports its type with, in response to `i`.
+    pub const fn code(&self) -> &'static str {
+        match self {
+            DeviceType::Ph => "pH",
+            DeviceType::Orp => "ORP",
+            DeviceType::DissolvedOxygen => "DO",
+            DeviceType::Conductivity => "EC",
+            DeviceType::Temperature => "RTD",
+            DeviceType::Humidity => "HUM",
+            DeviceType::Oxygen => "O2",
+            DeviceType::Co2 => "CO2",
+            DeviceType::Pump => "PMP",
+            DeviceType::Flow => "FLO",
+        }
+    }
+
+    /// Returns the I2C address the device type leaves the factory with.
+    pub const fn default_address(&self) -> u8 {
+        match self {
+            DeviceType::Ph => 0x63,
+            DeviceType::Orp => 0x62,
+            DeviceType::DissolvedOxygen => 0x61,
+            DeviceType::Conductivity => 0x64,
+            DeviceType::Temperature => 0x66,
+            DeviceType::Humidity => 0x6F,
+            DeviceType::Oxygen => 0x6C,
+            DeviceType::Co2 => 0x69,
+            DeviceType::Pump => 0x67,
+            DeviceType::Flow => 0x68,
+        }
+    }
+}
+
+impl core::str::FromStr for DeviceType {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "pH" => DeviceType::Ph,
+            "ORP" => DeviceType::Orp,
+            "DO" => DeviceType::DissolvedOxygen,
+            "EC" => DeviceType::Conductivity,
+            "RTD" => DeviceType::Temperature,
+            "HUM" => DeviceType::Humidity,
+            "O2" => DeviceType::Oxygen,
+            "CO2" => DeviceType::Co2,
+            "PMP" => DeviceType::Pump,
+            "FLO" => DeviceType::Flow,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Firmware version of an EZO device.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl FirmwareVersion {
+    /// Parses a version in the form `<major>.<minor>`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (major, minor) = value.trim().split_once('.')?;
+
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+}
+
+/// Identity of an EZO device, as reported by the `i` command.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DeviceInfo {
+    pub device_type: DeviceType,
+    pub firmware: FirmwareVersion,
+}
+
+impl DeviceInfo {
+    /// Parses an `i` response in the form `?I,<type>,<firmware>`.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut fields = data.strip_prefix("?I,")?.split(',');
+
+        Some(Self {
+            device_type: fields.next()?.trim().parse().ok()?,
+            firmware: FirmwareVersion::parse(fields.next()?)?,
+        })
+    }
+
+    /// Returns whether the device's firmware is supported by its driver.
+    pub fn is_supported(&self) -> bool {
+        self.firmware >= self.device_type.minimum_firmware()
+    }
+}
+
+/// An EZO device found while scanning the bus.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct DetectedDevice {
+    pub address: u8,
+    pub info: DeviceInfo,
+}
+
+/// Scans the I2C bus for EZO devices and identifies each of them.
+///
+/// Every address in [`SCAN_ADDRESSES`] is sent `i`. Addresses that acknowledge
+/// are read back after `wait_ms` has waited for [`IDENTIFY_DELAY_MS`]. Devices
+/// that respond with something other than an EZO identity are skipped.
+///
+/// This blocks for the duration of the scan, and is intended to run at startup.
+pub fn scan_bus<I2C>(
+    i2c: &mut I2C,
+    mut wait_ms: impl FnMut(u32),
+) -> heapless::Vec<DetectedDevice, MAX_DETECTED_DEVICES>
+where
+    I2C: Write + Read,
+{
+    let mut responders = heapless::Vec::<u8, MAX_DETECTED_DEVICES>::new();
+
+    for address in SCAN_ADDRESSES {
+        // Unpopulated addresses don't acknowledge the write
+        if i2c.write(address, b"i").is_ok() && responders.push(address).is_err() {
+            defmt::warn!(
+                "[scan_bus] More than {} devices responded, ignoring {}.",
+                MAX_DETECTED_DEVICES,
+                address
+            );
+        }
+    }
+
+    if responders.is_empty() {
+        return heapless::Vec::new();
+    }
+
+    // All responders process `i` in parallel, so a single wait covers them
+    wait_ms(IDENTIFY_DELAY_MS);
+
+    let mut detected = heapless::Vec::new();
+
+    for address in responders {
+        let mut buffer = [0u8; 32];
+
+        if i2c.read(address, &mut buffer).is_err() {
+            defmt::warn!(
+                "[scan_bus] Device {} acknowledged but didn't respond.",
+                address
+            );
+            continue;
+        }
+
+        let info = match split_i2c_response(&buffer) {
+            Some((I2cStatus::Success, data)) => {
+                core::str::from_utf8(data).ok().and_then(DeviceInfo::parse)
+            }
+            _ => None,
+        };
+
+        match info {
+            Some(info) => {
+                defmt::info!(
+                    "[scan_bus] Found {} with firmware {} at {}.",
+                    info.device_type,
+                    info.firmware,
+                    address
+                );
+
+                // Capacity matches the responder list, so this can't overflow
+                let _ = detected.push(DetectedDevice { address, info });
+            }
+            None => defmt::debug!("[scan_bus] Device at {} isn't an EZO device.", address),
+        }
+    }
+
+    detected
+}
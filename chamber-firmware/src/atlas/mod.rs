@@ -1,8 +1,44 @@
+mod address;
+mod calibration;
+mod co2;
+mod conductivity;
+mod dissolved_oxygen;
+mod fault;
+mod filter;
+mod humidity;
+mod i2c;
+mod identify;
+mod orp;
+mod power;
+mod pump;
 mod sensor;
+mod status;
+mod temperature;
+mod transport;
+mod uart;
 
+pub use address::*;
+pub use calibration::*;
+pub use co2::*;
+pub use conductivity::*;
+pub use dissolved_oxygen::*;
+pub use fault::*;
+pub use filter::*;
+pub use humidity::*;
+pub use i2c::*;
+pub use identify::*;
+pub use orp::*;
+pub use power::*;
+pub use pump::*;
 pub use sensor::*;
+pub use status::*;
+pub use temperature::*;
+pub use transport::*;
+pub use uart::*;
 
-#[derive(Clone, Copy)]
+use core::fmt::Write;
+
+#[derive(Clone, Copy, defmt::Format)]
 pub enum ResponseCode {
     Ok,
     UnknownCommand,
@@ -21,7 +57,7 @@ impl ResponseCode {
 
         let last_token = split.last()?;
 
-        Self::try_from(core::str::from_utf8(last_token).unwrap()).ok()
+        Self::try_from(core::str::from_utf8(last_token).ok()?).ok()
     }
 }
 
@@ -58,8 +94,96 @@ impl<'a> TryFrom<&'a str> for ResponseCode {
     }
 }
 
+/// Status byte that prefixes every response read from an EZO device over I2C.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum I2cStatus {
+    /// The command succeeded, and the response data follows.
+    Success,
+    /// The command wasn't understood.
+    SyntaxError,
+    /// The command is still being processed.
+    Pending,
+    /// There's no data to send.
+    NoData,
+}
+
+impl TryFrom<u8> for I2cStatus {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => I2cStatus::Success,
+            2 => I2cStatus::SyntaxError,
+            254 => I2cStatus::Pending,
+            255 => I2cStatus::NoData,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Splits an I2C read into its status and the response data before the NUL terminator.
+pub fn split_i2c_response(buffer: &[u8]) -> Option<(I2cStatus, &[u8])> {
+    let (status, data) = buffer.split_first()?;
+    let status = I2cStatus::try_from(*status).ok()?;
+
+    let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+
+    Some((status, &data[..end]))
+}
+
 #[derive(Debug)]
 pub struct AtlasCommand {
     pub address: usize,
     pub command: heapless::Vec<u8, 64>,
 }
+
+/// Buffer holding a single command string for an Atlas Scientific device.
+pub type CommandBuffer = heapless::Vec<u8, 64>;
+
+/// Formats a command with parameters, such as `K,1.0`, into a [`CommandBuffer`].
+///
+/// Returns `None` if the formatted command doesn't fit the buffer.
+pub fn format_command(arguments: core::fmt::Arguments) -> Option<CommandBuffer> {
+    let mut command = heapless::String::<64>::new();
+    command.write_fmt(arguments).ok()?;
+
+    Some(command.into_bytes())
+}
+
+/// Returns the command setting the solution temperature, in °C, used for compensation.
+pub fn temperature_compensation_command(celsius: f64) -> Option<CommandBuffer> {
+    format_command(format_args!("T,{celsius:.2}"))
+}
+
+/// Returns the command that sets the compensation temperature, in °C, and takes a reading.
+///
+/// This is used in place of [`AtlasSensor::sample_command`] for sensors that
+/// are [`AtlasSensor::temperature_compensated`].
+pub fn compensated_sample_command(celsius: f64) -> Option<CommandBuffer> {
+    format_command(format_args!("RT,{celsius:.2}"))
+}
+
+/// Returns the data line of a probe response, without the trailing response code.
+///
+/// Returns `None` if the data line is empty or isn't valid UTF-8.
+pub fn response_data(buffer: &[u8]) -> Option<&str> {
+    // Probe splits tokens by <CR>, and I2C reads are padded with NUL
+    let token = buffer.split(|c| *c == b'\r').next()?;
+    let token = core::str::from_utf8(token).ok()?;
+    let token = token.trim_end_matches('\0');
+
+    if token.is_empty() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+/// Strips the prefix of a query's response, such as `?CAL,`, whatever its case.
+///
+/// Devices report prefixes in upper case, while their datasheets mix cases.
+pub fn strip_response_prefix<'a>(data: &'a str, prefix: &str) -> Option<&'a str> {
+    let (head, rest) = data.split_at_checked(prefix.len())?;
+
+    head.eq_ignore_ascii_case(prefix).then_some(rest)
+}
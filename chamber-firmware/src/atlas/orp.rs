@@ -0,0 +1,94 @@
+use super::{
+    format_command, response_data, AtlasSensor, CommandBuffer, DeviceType, FilterConfig,
+    PendingAction,
+};
+
+/// Calibration commands supported by the EZO-ORP.
+#[derive(Clone, Copy)]
+pub enum OrpCalibration {
+    /// Single point calibration in a solution of known potential, in mV.
+    Point(f64),
+    /// Clears all calibration data.
+    Clear,
+}
+
+impl OrpCalibration {
+    /// Returns the command that performs this calibration step.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            OrpCalibration::Point(millivolts) => format_command(format_args!("Cal,{millivolts}")),
+            OrpCalibration::Clear => format_command(format_args!("Cal,clear")),
+        }
+    }
+}
+
+/// Atlas Scientific EZO-ORP oxidation-reduction potential circuit.
+pub struct OrpSensor {
+    /// I2C address of the device.
+    pub address: u32,
+    /// Latest oxidation-reduction potential in mV.
+    pub last_reading: Option<f64>,
+    pub action: PendingAction,
+}
+
+impl OrpSensor {
+    pub const fn new() -> Self {
+        Self {
+            address: DeviceType::Orp.default_address() as u32,
+            last_reading: None,
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+}
+
+impl Default for OrpSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasSensor for OrpSensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Orp
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 1.0,
+            stable_deviation: 2.0,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        if let Some(value) = response_data(response).and_then(|data| data.trim().parse().ok()) {
+            self.last_reading = Some(value);
+        }
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
@@ -0,0 +1,40 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::{AtlasSensor, PendingAction};
+
+/// Command that puts an EZO device to sleep.
+///
+/// The device responds with `*SL` over UART, and stops responding over I2C.
+pub const SLEEP_COMMAND: &[u8] = b"Sleep";
+
+/// Command used to wake a sleeping EZO device.
+///
+/// Any command wakes the device, `i` is used because it has no side effects.
+/// The device responds with `*WA` over UART.
+pub const WAKE_COMMAND: &[u8] = b"i";
+
+/// Shortest sample interval for which a device is put to sleep between samples.
+pub const MIN_SLEEP_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(30_000);
+
+/// Time before its next sample that a sleeping device is woken.
+pub const WAKE_LEAD: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(1000);
+
+/// Returns the action that follows a sample, sleeping the device if the next sample is far enough away.
+///
+/// When [`PendingAction::Sleep`] is returned the device must be sent
+/// [`SLEEP_COMMAND`] before the action is applied.
+pub fn action_after_sample(
+    sensor: &dyn AtlasSensor,
+    now: Instant<u32, 1, 1000>,
+    interval: Duration<u32, 1, 1000>,
+) -> PendingAction {
+    if sensor.sleeps_between_samples() && interval >= MIN_SLEEP_INTERVAL {
+        PendingAction::Sleep {
+            deadline: now + interval - WAKE_LEAD,
+        }
+    } else {
+        PendingAction::Sample {
+            deadline: now + interval,
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::{format_command, response_data, CommandBuffer, DeviceType, SensorFault};
+
+/// Interval at which a running dispense is polled for progress.
+pub const PUMP_POLL_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(1000);
+
+/// Ways in which a pump action can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PumpError {
+    /// The action's command couldn't be formatted, such as for an out of range volume.
+    InvalidCommand,
+    /// The command couldn't be issued, or the pump rejected it.
+    Fault(SensorFault),
+}
+
+/// Dispense modes supported by the EZO-PMP.
+///
+/// Volumes are in mL, negative volumes run the pump in reverse.
+#[derive(Clone, Copy)]
+pub enum Dispense {
+    /// Dispenses a volume at the pump's maximum flow rate.
+    Volume(f64),
+    /// Dispenses a volume evenly over a number of minutes.
+    VolumeOverTime { volume: f64, minutes: u32 },
+    /// Dispenses at a constant flow rate, in mL/min, for a number of minutes.
+    ///
+    /// The pump runs until stopped if no duration is given.
+    ConstantFlow { rate: f64, minutes: Option<u32> },
+    /// Dispenses at the pump's maximum flow rate until stopped.
+    Continuous,
+}
+
+impl Dispense {
+    /// Returns the command that starts this dispense.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            Dispense::Volume(volume) => format_command(format_args!("D,{volume:.2}")),
+            Dispense::VolumeOverTime { volume, minutes } => {
+                format_command(format_args!("D,{volume:.2},{minutes}"))
+            }
+            Dispense::ConstantFlow {
+                rate,
+                minutes: Some(minutes),
+            } => format_command(format_args!("DC,{rate:.2},{minutes}")),
+            Dispense::ConstantFlow {
+                rate,
+                minutes: None,
+            } => format_command(format_args!("DC,{rate:.2},*")),
+            Dispense::Continuous => format_command(format_args!("D,*")),
+        }
+    }
+}
+
+/// Status of a dispense, as reported by `D,?`.
+#[derive(Clone, Copy)]
+pub struct DispenseStatus {
+    /// Volume dispensed so far by the current or last dispense, in mL.
+    pub volume: f64,
+    /// Whether the pump is still dispensing.
+    pub dispensing: bool,
+}
+
+impl DispenseStatus {
+    /// Parses a `D,?` response in the form `?D,<volume>,<1|0>`.
+    pub fn parse(data: &str) -> Option<Self> {
+        let data = data.strip_prefix("?D,")?;
+        let (volume, dispensing) = data.split_once(',')?;
+
+        Some(Self {
+            volume: volume.trim().parse().ok()?,
+            dispensing: match dispensing.trim() {
+                "1" => true,
+                "0" => false,
+                _ => return None,
+            },
+        })
+    }
+}
+
+/// Next action the pump driver needs to perform.
+///
+/// Actions that issue a command are due immediately, except polling a running
+/// dispense, which is due at its deadline.
+#[derive(Clone, Copy, Default)]
+pub enum PumpAction {
+    /// Nothing is pending.
+    #[default]
+    Idle,
+    /// A dispense needs to be started.
+    Dispense(Dispense),
+    /// A dispense is running, and will be polled with `D,?` at the deadline.
+    Dispensing { deadline: Instant<u32, 1, 1000> },
+    /// A running dispense needs to be paused with `P`.
+    Pause,
+    /// A dispense is paused.
+    Paused,
+    /// A paused dispense needs to be resumed with `P`.
+    Resume,
+    /// The pump needs to be stopped with `X`.
+    Stop,
+    /// The total dispensed volume needs to be read with `TV,?`.
+    ReadTotalVolume,
+    /// The absolute total dispensed volume needs to be read with `ATV,?`.
+    ReadAbsoluteTotalVolume,
+    /// A volume calibration needs to be issued with the measured volume, in mL.
+    Calibrate(f64),
+    /// Calibration data needs to be cleared.
+    ClearCalibration,
+}
+
+/// Atlas Scientific EZO-PMP peristaltic pump.
+///
+/// Unlike an [`super::AtlasSensor`], the pump isn't sampled on an interval. A
+/// dispense is requested, then the pump is polled until it reports completion,
+/// after which the dispensed volume totals are read back.
+pub struct AtlasPump {
+    /// I2C address of the device.
+    pub address: u32,
+    pub action: PumpAction,
+    /// Status of the current or last dispense.
+    pub last_status: Option<DispenseStatus>,
+    /// Total volume dispensed since the totals were last cleared, in mL.
+    pub total_volume: Option<f64>,
+    /// Total absolute volume dispensed since the totals were last cleared, in mL.
+    ///
+    /// Volumes dispensed in reverse are added rather than subtracted.
+    pub absolute_total_volume: Option<f64>,
+}
+
+impl AtlasPump {
+    pub const fn new() -> Self {
+        Self {
+            address: DeviceType::Pump.default_address() as u32,
+            action: PumpAction::Idle,
+            last_status: None,
+            total_volume: None,
+            absolute_total_volume: None,
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Returns the pump's I2C address.
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// Records the pump being at a different I2C address.
+    pub fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    /// Returns whether the pump is dispensing, including while paused.
+    pub fn is_busy(&self) -> bool {
+        !matches!(
+            self.action,
+            PumpAction::Idle | PumpAction::ReadTotalVolume | PumpAction::ReadAbsoluteTotalVolume
+        )
+    }
+
+    /// Requests a dispense. Returns `false` if the pump is already busy.
+    pub fn dispense(&mut self, dispense: Dispense) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+
+        self.action = PumpAction::Dispense(dispense);
+        true
+    }
+
+    /// Requests a running dispense be paused.
+    pub fn pause(&mut self) {
+        if let PumpAction::Dispensing { .. } = self.action {
+            self.action = PumpAction::Pause;
+        }
+    }
+
+    /// Requests a paused dispense be resumed.
+    pub fn resume(&mut self) {
+        if let PumpAction::Paused = self.action {
+            self.action = PumpAction::Resume;
+        }
+    }
+
+    /// Requests the pump be stopped, whatever it's doing.
+    pub fn stop(&mut self) {
+        self.action = PumpAction::Stop;
+    }
+
+    /// Requests the total dispensed volumes be read back.
+    pub fn read_totals(&mut self) {
+        if !self.is_busy() {
+            self.action = PumpAction::ReadTotalVolume;
+        }
+    }
+
+    /// Requests a volume calibration with the volume actually measured, in mL.
+    ///
+    /// The measured volume is compared against the volume of the last dispense.
+    pub fn calibrate(&mut self, measured_volume: f64) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+
+        self.action = PumpAction::Calibrate(measured_volume);
+        true
+    }
+
+    /// Requests all calibration data be cleared.
+    pub fn clear_calibration(&mut self) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+
+        self.action = PumpAction::ClearCalibration;
+        true
+    }
+
+    /// Returns the command to issue if the pending action is due at `now`, if any.
+    ///
+    /// The response to the command must be passed to [`AtlasPump::handle_response`].
+    /// If the command can't be formatted, the action is dropped so the pump
+    /// doesn't stay busy.
+    pub fn due_command(
+        &mut self,
+        now: Instant<u32, 1, 1000>,
+    ) -> Result<Option<CommandBuffer>, PumpError> {
+        let command = match self.action {
+            PumpAction::Idle | PumpAction::Paused => return Ok(None),
+            PumpAction::Dispense(dispense) => dispense.command(),
+            PumpAction::Dispensing { deadline } if now >= deadline => {
+                format_command(format_args!("D,?"))
+            }
+            PumpAction::Dispensing { .. } => return Ok(None),
+            PumpAction::Pause | PumpAction::Resume => format_command(format_args!("P")),
+            PumpAction::Stop => format_command(format_args!("X")),
+            PumpAction::ReadTotalVolume => format_command(format_args!("TV,?")),
+            PumpAction::ReadAbsoluteTotalVolume => format_command(format_args!("ATV,?")),
+            PumpAction::Calibrate(volume) => format_command(format_args!("Cal,{volume:.2}")),
+            PumpAction::ClearCalibration => format_command(format_args!("Cal,clear")),
+        };
+
+        match command {
+            Some(command) => Ok(Some(command)),
+            None => {
+                self.action = PumpAction::Idle;
+                Err(PumpError::InvalidCommand)
+            }
+        }
+    }
+
+    /// Handles the response to the command returned by [`AtlasPump::due_command`].
+    ///
+    /// `response` is the response data, or the fault that stopped it being
+    /// read, such as [`SensorFault::UnknownCommand`] when the pump rejected the
+    /// command. A running dispense is still polled after a fault, anything
+    /// else is dropped.
+    pub fn handle_response(
+        &mut self,
+        response: Result<&[u8], SensorFault>,
+        now: Instant<u32, 1, 1000>,
+    ) -> Result<(), PumpError> {
+        let response = match response {
+            Ok(response) => response,
+            Err(fault) => {
+                self.action = match self.action {
+                    // Keep polling if a query failed, the dispense is still running
+                    PumpAction::Dispensing { .. } => PumpAction::Dispensing {
+                        deadline: now + PUMP_POLL_INTERVAL,
+                    },
+                    _ => PumpAction::Idle,
+                };
+
+                return Err(PumpError::Fault(fault));
+            }
+        };
+
+        let data = response_data(response);
+
+        self.action = match self.action {
+            PumpAction::Dispense(_) | PumpAction::Resume => PumpAction::Dispensing {
+                deadline: now + PUMP_POLL_INTERVAL,
+            },
+            PumpAction::Dispensing { .. } => {
+                match data.and_then(DispenseStatus::parse) {
+                    Some(status) => {
+                        self.last_status = Some(status);
+
+                        if status.dispensing {
+                            PumpAction::Dispensing {
+                                deadline: now + PUMP_POLL_INTERVAL,
+                            }
+                        } else {
+                            // Dispense completed, read back how much was dispensed in total
+                            PumpAction::ReadTotalVolume
+                        }
+                    }
+                    None => PumpAction::Dispensing {
+                        deadline: now + PUMP_POLL_INTERVAL,
+                    },
+                }
+            }
+            PumpAction::Pause => PumpAction::Paused,
+            PumpAction::Stop => PumpAction::ReadTotalVolume,
+            PumpAction::ReadTotalVolume => {
+                if let Some(volume) = data
+                    .and_then(|data| data.strip_prefix("?TV,"))
+                    .and_then(|volume| volume.trim().parse().ok())
+                {
+                    self.total_volume = Some(volume);
+                }
+
+                PumpAction::ReadAbsoluteTotalVolume
+            }
+            PumpAction::ReadAbsoluteTotalVolume => {
+                if let Some(volume) = data
+                    .and_then(|data| data.strip_prefix("?ATV,"))
+                    .and_then(|volume| volume.trim().parse().ok())
+                {
+                    self.absolute_total_volume = Some(volume);
+                }
+
+                PumpAction::Idle
+            }
+            PumpAction::Calibrate(_) | PumpAction::ClearCalibration => PumpAction::Idle,
+            action @ (PumpAction::Idle | PumpAction::Paused) => action,
+        };
+
+        Ok(())
+    }
+}
+
+impl Default for AtlasPump {
+    fn default() -> Self {
+        Self::new()
+    }
+}
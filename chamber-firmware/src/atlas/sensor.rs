@@ -1,15 +1,68 @@
-use rtic_monotonics::systick::fugit::Instant;
+use rtic_monotonics::systick::fugit::{Duration, Instant};
 
-pub struct PendingOperation {
-    pub sensor: usize,
-    pub operation: PendingAction,
+use super::{response_data, DeviceType, FilterConfig, SensorFault};
+
+/// Time between samples of a sensor, unless it's configured otherwise.
+pub const DEFAULT_SAMPLE_INTERVAL: Duration<u32, 1, 1000> =
+    Duration::<u32, 1, 1000>::from_ticks(5000);
+
+/// Returns the time most EZO devices need to process `command`.
+///
+/// Readings and calibration take the longest, up to 900 ms on the slowest
+/// devices. Everything else is processed within 300 ms.
+pub fn default_processing_delay(command: &[u8]) -> Duration<u32, 1, 1000> {
+    if command == b"R" || command.starts_with(b"RT,") || command.starts_with(b"Cal") {
+        Duration::<u32, 1, 1000>::from_ticks(900)
+    } else {
+        Duration::<u32, 1, 1000>::from_ticks(300)
+    }
 }
 
 #[derive(Clone, Copy)]
 pub enum PendingAction {
-    Startup { command_index: usize },
-    Sample { deadline: Instant<u32, 1, 1000> },
-    Receive { deadline: Instant<u32, 1, 1000> },
+    Startup {
+        command_index: usize,
+    },
+    Sample {
+        deadline: Instant<u32, 1, 1000>,
+    },
+    Receive {
+        deadline: Instant<u32, 1, 1000>,
+    },
+    /// The device is asleep, and is woken at the deadline for its next sample.
+    Sleep {
+        deadline: Instant<u32, 1, 1000>,
+    },
+    /// A command failed, the device is set up again at the deadline.
+    Retry {
+        fault: SensorFault,
+        deadline: Instant<u32, 1, 1000>,
+    },
+    /// Retrying didn't help, the device is reset at the deadline.
+    Reset {
+        fault: SensorFault,
+        deadline: Instant<u32, 1, 1000>,
+    },
+    /// The device couldn't be recovered, it's probed again at the deadline.
+    Offline {
+        fault: SensorFault,
+        deadline: Instant<u32, 1, 1000>,
+    },
+}
+
+impl PendingAction {
+    /// Returns when the action is due, or `None` if it's due immediately.
+    pub fn deadline(&self) -> Option<Instant<u32, 1, 1000>> {
+        match self {
+            PendingAction::Startup { .. } => None,
+            PendingAction::Sample { deadline }
+            | PendingAction::Receive { deadline }
+            | PendingAction::Sleep { deadline }
+            | PendingAction::Retry { deadline, .. }
+            | PendingAction::Reset { deadline, .. }
+            | PendingAction::Offline { deadline, .. } => Some(*deadline),
+        }
+    }
 }
 
 impl Default for PendingAction {
@@ -19,6 +72,8 @@ impl Default for PendingAction {
 }
 
 pub struct OxygenSensor {
+    /// I2C address of the device.
+    pub address: u32,
     pub last_reading: f64,
     pub action: PendingAction,
 }
@@ -26,69 +81,53 @@ pub struct OxygenSensor {
 impl OxygenSensor {
     pub const fn new() -> Self {
         Self {
+            address: DeviceType::Oxygen.default_address() as u32,
             last_reading: 0.0,
             action: PendingAction::Startup { command_index: 0 },
         }
     }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
 }
 
 impl AtlasSensor for OxygenSensor {
     fn address(&self) -> u32 {
-        0x6C
+        self.address
     }
 
-    fn sample_command(&self) -> &'static [u8] {
-        &[b'R']
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
     }
 
-    fn handle_response(&mut self, _response: &[u8]) {
-        todo!()
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Oxygen
     }
 
-    fn pending_action(&self) -> &PendingAction {
-        &self.action
+    fn sample_command(&self) -> &'static [u8] {
+        &[b'R']
     }
 
-    fn pending_action_mut(&mut self) -> &mut PendingAction {
-        &mut self.action
+    fn discard_after_wake(&self) -> bool {
+        // The sensing element needs a moment to settle after waking
+        true
     }
-}
 
-#[derive(Default)]
-pub struct HumiditySensor {
-    pub last_humidity: f64,
-    pub last_temperature: f64,
-    pub action: PendingAction,
-}
-
-impl HumiditySensor {
-    pub const fn new() -> Self {
-        Self {
-            last_humidity: 0.0,
-            last_temperature: 0.0,
-            action: PendingAction::Startup { command_index: 0 },
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 0.05,
+            stable_deviation: 0.1,
+            ..FilterConfig::default()
         }
     }
-}
 
-impl AtlasSensor for HumiditySensor {
-    fn address(&self) -> u32 {
-        0x6F
-    }
-
-    fn sample_command(&self) -> &'static [u8] {
-        &[b'R']
-    }
-
-    fn setup_commands(&self) -> &'static [&'static [u8]] {
-        &[
-            &[b'O', b',', b'T', b',', b'1'],
-            &[b'O', b',', b'H', b'U', b'M', b',', b'1'],
-        ]
-    }
-
-    fn handle_response(&mut self, _response: &[u8]) {
-        todo!()
+    fn handle_response(&mut self, response: &[u8]) {
+        if let Some(value) = response_data(response).and_then(|data| data.trim().parse().ok()) {
+            self.last_reading = value;
+        }
     }
 
     fn pending_action(&self) -> &PendingAction {
@@ -104,6 +143,12 @@ pub trait AtlasSensor {
     /// Returns a sensor's I2C address.
     fn address(&self) -> u32;
 
+    /// Records the sensor's device being at a different I2C address.
+    fn set_address(&mut self, address: u32);
+
+    /// Returns the type of EZO device the sensor drives.
+    fn device_type(&self) -> DeviceType;
+
     /// Returns the command string used to sample the device.
     ///
     /// This command is sent to sample the device. The response is
@@ -116,13 +161,98 @@ pub trait AtlasSensor {
 
     fn pending_action_mut(&mut self) -> &mut PendingAction;
 
-    /// Returns any command strings needed to set up the device.
+    /// Returns the command string at `index` of those needed to set up the device.
+    ///
+    /// They will be executed in order until `None` is returned, and the output
+    /// will be checked for errors. If a command fails the device will be
+    /// considered faulted, and recovered as described by [`super::FaultTracker`].
+    ///
+    /// The commands are built from the sensor's current settings, so setting
+    /// the device up again after a fault restores them.
+    fn setup_command(&self, _index: usize) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// Returns the command used to reset the device while recovering from a fault.
+    ///
+    /// `Factory` clears calibration data, so only devices that are calibrated
+    /// at the factory should return it. Devices without a reset command go
+    /// straight from retrying to offline.
+    fn reset_command(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// Returns whether the device's readings depend on the solution temperature.
+    ///
+    /// Compensated devices are sampled with [`super::compensated_sample_command`] when
+    /// a solution temperature is known, instead of [`AtlasSensor::sample_command`].
+    fn temperature_compensated(&self) -> bool {
+        false
+    }
+
+    /// Returns the latest solution temperature in °C, if the device measures it.
+    fn solution_temperature(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns the latest chamber air temperature in °C, if the device measures it.
+    fn air_temperature(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns the latest vapour pressure deficit in kPa, if the device's readings give it.
+    fn vapour_pressure_deficit(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns whether the device is put to sleep between samples.
+    ///
+    /// Devices are only put to sleep when samples are at least
+    /// [`super::MIN_SLEEP_INTERVAL`] apart.
+    fn sleeps_between_samples(&self) -> bool {
+        true
+    }
+
+    /// Returns whether the first reading after the device wakes up is discarded.
+    fn discard_after_wake(&self) -> bool {
+        false
+    }
+
+    /// Called after the device has been woken from sleep.
+    fn after_wake(&mut self) {}
+
+    /// Called after the device restarted, such as after its reset command or a power cycle.
+    fn after_restart(&mut self) {}
+
+    /// Called with the current time right before the device is sampled.
+    ///
+    /// Devices that track time-dependent state, such as a warm-up period,
+    /// update it here.
+    fn before_sample(&mut self, _now: Instant<u32, 1, 1000>) {}
+
+    /// Returns whether the device is warming up, so its readings are invalid.
+    ///
+    /// Readings taken during warm-up are passed to [`AtlasSensor::handle_response`],
+    /// so the sensor can flag them, but they aren't filtered or logged.
+    fn warming_up(&self) -> bool {
+        false
+    }
+
+    /// Returns the time between samples of the device, unless it's configured otherwise.
+    fn sample_interval(&self) -> Duration<u32, 1, 1000> {
+        DEFAULT_SAMPLE_INTERVAL
+    }
+
+    /// Returns the time the device needs to process `command` before its response can be read.
+    fn processing_delay(&self, command: &[u8]) -> Duration<u32, 1, 1000> {
+        default_processing_delay(command)
+    }
+
+    /// Returns the configuration of the filter applied to the device's readings.
     ///
-    /// They will be executed, and the output will be checked for
-    /// [`SensorResponse::Ok`]. If a command fails the device will
-    /// be considered faulted.
-    fn setup_commands(&self) -> &'static [&'static [u8]] {
-        &[]
+    /// The filter works in the units of the first value in a reading.
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig::default()
     }
 
     /// Handles a response to a sample command for the device.
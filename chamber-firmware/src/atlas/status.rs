@@ -0,0 +1,148 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::{strip_response_prefix, AtlasEvent};
+
+/// Command that reads a device's restart reason and supply voltage.
+pub const STATUS_COMMAND: &[u8] = b"Status";
+
+/// Interval at which each device's status is polled.
+pub const STATUS_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(300_000);
+
+/// Lowest healthy supply voltage of an EZO device, in V.
+pub const MIN_SUPPLY_VOLTAGE: f64 = 3.2;
+
+/// Highest healthy supply voltage of an EZO device, in V.
+pub const MAX_SUPPLY_VOLTAGE: f64 = 5.5;
+
+/// Reason an EZO device last restarted, as reported by `Status`.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RestartReason {
+    PoweredOff,
+    SoftwareReset,
+    BrownOut,
+    Watchdog,
+    Unknown,
+}
+
+impl core::str::FromStr for RestartReason {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "P" => RestartReason::PoweredOff,
+            "S" => RestartReason::SoftwareReset,
+            "B" => RestartReason::BrownOut,
+            "W" => RestartReason::Watchdog,
+            "U" => RestartReason::Unknown,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Status of an EZO device.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct DeviceStatus {
+    pub restart_reason: RestartReason,
+    /// Supply voltage in V.
+    pub vcc: f64,
+}
+
+impl DeviceStatus {
+    /// Parses a `Status` response in the form `?STATUS,<reason>,<vcc>`.
+    ///
+    /// The prefix is matched regardless of case, as it differs between devices.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut fields = strip_response_prefix(data, "?Status,")?.split(',');
+
+        Some(Self {
+            restart_reason: fields.next()?.trim().parse().ok()?,
+            vcc: fields.next()?.trim().parse().ok()?,
+        })
+    }
+
+    /// Returns whether the supply voltage is within the healthy range.
+    pub fn supply_healthy(&self) -> bool {
+        (MIN_SUPPLY_VOLTAGE..=MAX_SUPPLY_VOLTAGE).contains(&self.vcc)
+    }
+}
+
+/// Tracks a device's status, and raises health warnings from it.
+///
+/// The restart reason only says why the device last restarted, so a restart
+/// is noticed when the reason changes. Brown-outs and watchdog resets are
+/// always unexpected, and usually point to a wiring problem. Software resets
+/// are expected after the firmware resets the device itself.
+#[derive(Clone, Copy, Default)]
+pub struct DeviceHealth {
+    /// The most recent status.
+    pub last_status: Option<DeviceStatus>,
+    next_poll: Option<Instant<u32, 1, 1000>>,
+    restart_expected: bool,
+}
+
+impl DeviceHealth {
+    pub const fn new() -> Self {
+        Self {
+            last_status: None,
+            next_poll: None,
+            restart_expected: false,
+        }
+    }
+
+    /// Returns whether the device's status should be polled.
+    pub fn is_due(&self, now: Instant<u32, 1, 1000>) -> bool {
+        self.next_poll.is_none_or(|deadline| now >= deadline)
+    }
+
+    /// Records that the status couldn't be polled, so it's polled again after the usual interval.
+    ///
+    /// The sample that follows the poll finds out whether the device is faulted.
+    pub fn poll_failed(&mut self, now: Instant<u32, 1, 1000>) {
+        self.next_poll = Some(now + STATUS_INTERVAL);
+    }
+
+    /// Records that the firmware is about to reset the device.
+    pub fn expect_restart(&mut self) {
+        self.restart_expected = true;
+    }
+
+    /// Records a polled status, returning any health warnings it raises.
+    pub fn record(
+        &mut self,
+        address: u32,
+        status: DeviceStatus,
+        now: Instant<u32, 1, 1000>,
+    ) -> heapless::Vec<AtlasEvent, 2> {
+        let previous = self.last_status.replace(status);
+        let restart_expected = core::mem::take(&mut self.restart_expected);
+        self.next_poll = Some(now + STATUS_INTERVAL);
+
+        let mut warnings = heapless::Vec::new();
+
+        let restarted =
+            previous.is_none_or(|previous| previous.restart_reason != status.restart_reason);
+        let unexpected = match status.restart_reason {
+            RestartReason::BrownOut | RestartReason::Watchdog => true,
+            // Devices restart when the chamber starts up, so earlier restarts aren't a concern
+            RestartReason::SoftwareReset => previous.is_some() && !restart_expected,
+            RestartReason::PoweredOff | RestartReason::Unknown => previous.is_some(),
+        };
+
+        if restarted && unexpected {
+            let _ = warnings.push(AtlasEvent::UnexpectedRestart {
+                address,
+                reason: status.restart_reason,
+            });
+        }
+
+        // Only warn when the supply voltage leaves the healthy range
+        if !status.supply_healthy() && previous.is_none_or(|previous| previous.supply_healthy()) {
+            let _ = warnings.push(AtlasEvent::SupplyVoltage {
+                address,
+                vcc: status.vcc,
+            });
+        }
+
+        warnings
+    }
+}
@@ -0,0 +1,193 @@
+use super::{
+    format_command, response_data, AtlasSensor, CommandBuffer, DeviceType, FilterConfig,
+    PendingAction,
+};
+
+/// Value reported by the EZO-RTD when no probe is connected.
+const NO_PROBE_READING: f64 = -1023.0;
+
+/// Temperature scale used by the EZO-RTD for readings.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureScale {
+    Celsius,
+    Kelvin,
+    Fahrenheit,
+}
+
+impl TemperatureScale {
+    /// Returns the command that selects this scale.
+    pub fn command(&self) -> &'static [u8] {
+        match self {
+            TemperatureScale::Celsius => b"S,c",
+            TemperatureScale::Kelvin => b"S,k",
+            TemperatureScale::Fahrenheit => b"S,f",
+        }
+    }
+
+    /// Converts a value in this scale to °C.
+    pub fn to_celsius(&self, value: f64) -> f64 {
+        match self {
+            TemperatureScale::Celsius => value,
+            TemperatureScale::Kelvin => value - 273.15,
+            TemperatureScale::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+        }
+    }
+}
+
+/// Calibration commands supported by the EZO-RTD.
+#[derive(Clone, Copy)]
+pub enum TemperatureCalibration {
+    /// Single point calibration against a known temperature, in the current scale.
+    Point(f64),
+    /// Clears all calibration data.
+    Clear,
+}
+
+impl TemperatureCalibration {
+    /// Returns the command that performs this calibration step.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            TemperatureCalibration::Point(value) => format_command(format_args!("Cal,{value}")),
+            TemperatureCalibration::Clear => format_command(format_args!("Cal,clear")),
+        }
+    }
+}
+
+/// Commands for the EZO-RTD's onboard data logger.
+///
+/// The logger stores up to 50 readings in a circular buffer, independently of
+/// the readings taken by the firmware.
+#[derive(Clone, Copy)]
+pub enum DataLoggerCommand {
+    /// Logs a reading every `n * 10` seconds, with `n` between 1 and 32,000.
+    Enable(u16),
+    /// Stops the data logger.
+    Disable,
+    /// Queries the logger interval.
+    Query,
+    /// Recalls the next stored reading.
+    Recall,
+    /// Queries the last memory location and its reading.
+    RecallLast,
+    /// Clears all stored readings.
+    Clear,
+}
+
+impl DataLoggerCommand {
+    /// Returns the command for this data logger operation.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        match self {
+            DataLoggerCommand::Enable(interval) if (1..=32_000).contains(interval) => {
+                format_command(format_args!("D,{interval}"))
+            }
+            DataLoggerCommand::Enable(_) => None,
+            DataLoggerCommand::Disable => format_command(format_args!("D,0")),
+            DataLoggerCommand::Query => format_command(format_args!("D,?")),
+            DataLoggerCommand::Recall => format_command(format_args!("M")),
+            DataLoggerCommand::RecallLast => format_command(format_args!("M,?")),
+            DataLoggerCommand::Clear => format_command(format_args!("M,clear")),
+        }
+    }
+}
+
+/// A reading recalled from the EZO-RTD's data logger.
+#[derive(Clone, Copy)]
+pub struct LoggedTemperature {
+    /// Memory location the reading was stored in.
+    pub location: u16,
+    /// Stored temperature, in the scale active when it was logged.
+    pub value: f64,
+}
+
+impl LoggedTemperature {
+    /// Parses a `M` or `M,?` response in the form `[?M,]<location>,<value>`.
+    pub fn parse(data: &str) -> Option<Self> {
+        let data = data.strip_prefix("?M,").unwrap_or(data);
+        let (location, value) = data.split_once(',')?;
+
+        Some(Self {
+            location: location.trim().parse().ok()?,
+            value: value.trim().parse().ok()?,
+        })
+    }
+}
+
+/// Atlas Scientific EZO-RTD temperature circuit, used for solution temperature.
+pub struct TemperatureSensor {
+    /// I2C address of the device.
+    pub address: u32,
+    pub scale: TemperatureScale,
+    pub last_reading: Option<f64>,
+    pub action: PendingAction,
+}
+
+impl TemperatureSensor {
+    pub const fn new(scale: TemperatureScale) -> Self {
+        Self {
+            address: DeviceType::Temperature.default_address() as u32,
+            scale,
+            last_reading: None,
+            action: PendingAction::Startup { command_index: 0 },
+        }
+    }
+
+    /// Sets the device's I2C address, for devices moved from the default address.
+    pub const fn with_address(mut self, address: u32) -> Self {
+        self.address = address;
+        self
+    }
+}
+
+impl AtlasSensor for TemperatureSensor {
+    fn address(&self) -> u32 {
+        self.address
+    }
+
+    fn set_address(&mut self, address: u32) {
+        self.address = address;
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Temperature
+    }
+
+    fn sample_command(&self) -> &'static [u8] {
+        b"R"
+    }
+
+    fn setup_command(&self, index: usize) -> Option<&'static [u8]> {
+        (index == 0).then_some(self.scale.command())
+    }
+
+    fn filter_config(&self) -> FilterConfig {
+        FilterConfig {
+            min_deviation: 0.05,
+            stable_deviation: 0.1,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn handle_response(&mut self, response: &[u8]) {
+        let Some(value) = response_data(response).and_then(|data| data.parse::<f64>().ok()) else {
+            return;
+        };
+
+        self.last_reading = if value == NO_PROBE_READING {
+            None
+        } else {
+            Some(value)
+        };
+    }
+
+    fn solution_temperature(&self) -> Option<f64> {
+        self.last_reading.map(|value| self.scale.to_celsius(value))
+    }
+
+    fn pending_action(&self) -> &PendingAction {
+        &self.action
+    }
+
+    fn pending_action_mut(&mut self) -> &mut PendingAction {
+        &mut self.action
+    }
+}
@@ -0,0 +1,17 @@
+use super::{ResponseBuffer, SensorFault};
+
+/// A link to a single EZO device, over which commands are sent and responses read.
+///
+/// EZO devices accept the same commands over I2C and UART, so the
+/// [`super::AtlasSensor`] types work over either link.
+pub trait AtlasTransport {
+    /// Sends a command to the device.
+    fn send_command(&mut self, command: &[u8]) -> Result<(), SensorFault>;
+
+    /// Reads the response to the last command.
+    ///
+    /// Only the response data is returned, response codes are turned into
+    /// faults. [`SensorFault::Busy`] is returned while the response isn't
+    /// available yet, in which case the read can be repeated.
+    fn read_response(&mut self) -> Result<ResponseBuffer, SensorFault>;
+}
@@ -0,0 +1,255 @@
+use stm32h7xx_hal::hal::serial::{Read, Write};
+use stm32h7xx_hal::nb;
+
+use super::{
+    format_command, AtlasTransport, CommandBuffer, DeviceInfo, ResponseBuffer, ResponseCode,
+    SensorFault, IDENTIFY_DELAY_MS,
+};
+
+/// Baud rates supported by EZO devices in UART mode.
+#[derive(Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum BaudRate {
+    B300,
+    B1200,
+    B2400,
+    /// The factory default.
+    #[default]
+    B9600,
+    B19200,
+    B38400,
+    B57600,
+    B115200,
+}
+
+impl BaudRate {
+    pub fn bits_per_second(&self) -> u32 {
+        match self {
+            BaudRate::B300 => 300,
+            BaudRate::B1200 => 1200,
+            BaudRate::B2400 => 2400,
+            BaudRate::B9600 => 9600,
+            BaudRate::B19200 => 19200,
+            BaudRate::B38400 => 38400,
+            BaudRate::B57600 => 57600,
+            BaudRate::B115200 => 115200,
+        }
+    }
+
+    /// Returns the command that switches a device to UART mode at this baud rate.
+    ///
+    /// This works over either link, and the device restarts once it's received.
+    pub fn command(&self) -> Option<CommandBuffer> {
+        format_command(format_args!("Baud,{}", self.bits_per_second()))
+    }
+}
+
+/// Returns the command that switches a device to I2C mode at `address`.
+///
+/// This works over either link, and the device restarts once it's received.
+/// Returns `None` if the address is out of range.
+pub fn i2c_mode_command(address: u8) -> Option<CommandBuffer> {
+    if (1..=127).contains(&address) {
+        format_command(format_args!("I2C,{address}"))
+    } else {
+        None
+    }
+}
+
+/// A single `<CR>` terminated line received from an EZO device in UART mode.
+pub enum UartLine {
+    /// Response data, such as a reading.
+    Data(ResponseBuffer),
+    /// A response code, such as `*OK`.
+    Code(ResponseCode),
+}
+
+/// An EZO device in UART mode, on its own serial port.
+///
+/// Devices leave the factory in UART mode, with continuous readings and
+/// response codes enabled. Continuous readings arrive unprompted, so they
+/// should be disabled with [`UartTransport::set_continuous`] before commands
+/// are sent, or collected with [`UartTransport::read_continuous`] instead.
+pub struct UartTransport<S> {
+    pub serial: S,
+    /// Baud rate the device is configured for.
+    pub baud: BaudRate,
+    response_codes: bool,
+    continuous: bool,
+    /// The line currently being received.
+    line: ResponseBuffer,
+    /// Data received in response to the last command, waiting on its response code.
+    data: Option<ResponseBuffer>,
+}
+
+impl<S: Read<u8> + Write<u8>> UartTransport<S> {
+    /// Creates a transport for a device with its factory default settings.
+    pub fn new(serial: S, baud: BaudRate) -> Self {
+        Self {
+            serial,
+            baud,
+            response_codes: true,
+            continuous: true,
+            line: ResponseBuffer::new(),
+            data: None,
+        }
+    }
+
+    /// Returns whether the device sends continuous readings.
+    pub fn is_continuous(&self) -> bool {
+        self.continuous
+    }
+
+    /// Enables or disables continuous readings with `C,1` or `C,0`.
+    ///
+    /// Like any other command, its response is read with [`AtlasTransport::read_response`].
+    pub fn set_continuous(&mut self, enabled: bool) -> Result<(), SensorFault> {
+        self.send_command(if enabled { b"C,1" } else { b"C,0" })?;
+        self.continuous = enabled;
+
+        Ok(())
+    }
+
+    /// Enables or disables the `*OK` response code with `*OK,1` or `*OK,0`.
+    ///
+    /// Without response codes, commands that don't return data have no
+    /// response, so their response mustn't be read.
+    pub fn set_response_codes(&mut self, enabled: bool) -> Result<(), SensorFault> {
+        self.send_command(if enabled { b"*OK,1" } else { b"*OK,0" })?;
+        self.response_codes = enabled;
+
+        Ok(())
+    }
+
+    /// Identifies the device, disabling continuous readings first so they don't get in the way.
+    ///
+    /// `wait_ms` waits [`IDENTIFY_DELAY_MS`] for each command to be processed.
+    /// Returns `None` if nothing responds, or the response isn't an EZO identity.
+    ///
+    /// This blocks while the device processes the commands, and is intended to run at startup.
+    pub fn identify(&mut self, mut wait_ms: impl FnMut(u32)) -> Option<DeviceInfo> {
+        self.set_continuous(false).ok()?;
+        wait_ms(IDENTIFY_DELAY_MS);
+        self.read_response().ok()?;
+
+        self.send_command(b"i").ok()?;
+        wait_ms(IDENTIFY_DELAY_MS);
+        let response = self.read_response().ok()?;
+
+        core::str::from_utf8(&response)
+            .ok()
+            .and_then(DeviceInfo::parse)
+    }
+
+    /// Moves the device to a new baud rate.
+    ///
+    /// The device restarts at the new baud rate, so the serial port must be
+    /// reconfigured to match before anything else is sent.
+    pub fn set_baud(&mut self, baud: BaudRate) -> Result<(), SensorFault> {
+        let command = baud.command().ok_or(SensorFault::InvalidResponse)?;
+
+        self.send_command(&command)?;
+        self.baud = baud;
+
+        Ok(())
+    }
+
+    /// Switches the device to I2C mode at `address`.
+    ///
+    /// The device restarts and stops responding over UART, so the transport
+    /// shouldn't be used afterwards.
+    pub fn switch_to_i2c(&mut self, address: u8) -> Result<(), SensorFault> {
+        let command = i2c_mode_command(address).ok_or(SensorFault::InvalidResponse)?;
+
+        self.send_command(&command)
+    }
+
+    /// Returns the next complete line received from the device, if there is one.
+    pub fn poll_line(&mut self) -> Result<Option<UartLine>, SensorFault> {
+        loop {
+            let byte = match self.serial.read() {
+                Ok(byte) => byte,
+                Err(nb::Error::WouldBlock) => return Ok(None),
+                Err(nb::Error::Other(_)) => {
+                    // The partial line is corrupt after an overrun or framing error
+                    self.line.clear();
+                    return Err(SensorFault::InvalidResponse);
+                }
+            };
+
+            match byte {
+                b'\r' => {
+                    let line = core::mem::take(&mut self.line);
+
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    let code = core::str::from_utf8(&line)
+                        .ok()
+                        .and_then(|line| ResponseCode::try_from(line).ok());
+
+                    return Ok(Some(match code {
+                        Some(code) => UartLine::Code(code),
+                        None => UartLine::Data(line),
+                    }));
+                }
+                b'\n' => {}
+                byte => {
+                    if self.line.push(byte).is_err() {
+                        self.line.clear();
+                        return Err(SensorFault::InvalidResponse);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the next continuous reading, if one has been received.
+    ///
+    /// Response codes received in the meantime are discarded.
+    pub fn read_continuous(&mut self) -> Result<Option<ResponseBuffer>, SensorFault> {
+        while let Some(line) = self.poll_line()? {
+            if let UartLine::Data(data) = line {
+                return Ok(Some(data));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<S: Read<u8> + Write<u8>> AtlasTransport for UartTransport<S> {
+    fn send_command(&mut self, command: &[u8]) -> Result<(), SensorFault> {
+        // Discard anything left over, so it isn't mistaken for the response to this command
+        while let Ok(Some(_)) = self.poll_line() {}
+        self.data = None;
+
+        for byte in command.iter().chain(b"\r") {
+            nb::block!(self.serial.write(*byte)).map_err(|_| SensorFault::Nack)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_response(&mut self) -> Result<ResponseBuffer, SensorFault> {
+        while let Some(line) = self.poll_line()? {
+            match line {
+                // With response codes, the data is followed by `*OK`
+                UartLine::Data(data) if self.response_codes => self.data = Some(data),
+                UartLine::Data(data) => return Ok(data),
+                UartLine::Code(ResponseCode::Ok) => {
+                    return Ok(self.data.take().unwrap_or_default())
+                }
+                UartLine::Code(ResponseCode::UnknownCommand) => {
+                    return Err(SensorFault::UnknownCommand)
+                }
+                UartLine::Code(ResponseCode::OverVolt) => return Err(SensorFault::OverVolt),
+                UartLine::Code(ResponseCode::UnderVolt) => return Err(SensorFault::UnderVolt),
+                // Reset, ready, sleep and wake notifications aren't responses to a command
+                UartLine::Code(_) => {}
+            }
+        }
+
+        Err(SensorFault::Busy)
+    }
+}
@@ -17,17 +17,156 @@ mod app {
     use heapless::Vec;
     use rtic_monotonics::systick::fugit::Duration;
     use rtic_monotonics::systick::Systick;
-    use stm32h7xx_hal::device::I2C1;
+    use stm32h7xx_hal::device::{I2C1, TIM3, USART1, USART2};
+    use stm32h7xx_hal::gpio::{Output, PushPull, PB0};
     use stm32h7xx_hal::i2c::I2c;
     use stm32h7xx_hal::pac::Peripherals;
+    use stm32h7xx_hal::pwm::{ComplementaryImpossible, Pwm};
+    use stm32h7xx_hal::serial::{self, Rx, Serial, Tx};
+    #[cfg(feature = "sd-log")]
+    use stm32h7xx_hal::{
+        device::SDMMC1,
+        gpio::Speed,
+        sdmmc::{SdCard, Sdmmc},
+    };
 
+    use rtic::Mutex;
     use rtic_monotonics::Monotonic;
+    use rtic_sync::channel::{Receiver, Sender};
+    use stm32h7xx_hal::nb;
     use stm32h7xx_hal::prelude::*;
 
     use chamber_firmware::atlas::{
-        AtlasCommand, HumiditySensor, OxygenSensor, PendingAction, PendingOperation, ResponseCode,
+        action_after_sample, compensated_sample_command, default_processing_delay,
+        load_calibration_data, provision_address, response_data, save_calibration_data, scan_bus,
+        AtlasEvent, AtlasPump, AtlasSensor, AtlasTransport, BaudRate, CalibrationExport,
+        CalibrationImport, CalibrationJob, CalibrationLog, CalibrationProcedure, CalibrationState,
+        Co2Sensor, ConductivitySensor, DetectedDevice, DeviceStatus, DeviceType, Dispense,
+        DissolvedOxygenSensor, FilteredReading, HumiditySensor, OrpSensor, OxygenSensor,
+        PendingAction, ProbeK, ResponseBuffer, SensorFault, SharedI2cTransport, TemperatureScale,
+        TemperatureSensor, UartTransport, MAX_BUSY_READS, PUMP_POLL_INTERVAL, REPROBE_INTERVAL,
+        RESET_DELAY, SLEEP_COMMAND, STATUS_COMMAND, WAKE_COMMAND, WAKE_LEAD,
+    };
+    use chamber_firmware::clock::{self, Timestamp};
+    use chamber_firmware::config::{Config, RadioConfig};
+    use chamber_firmware::light::{
+        register_force_off, DimmingCalibration, DliProgress, DliTarget, DliTracker, GrowLight,
+        HeatDerating, Photoperiod, TimeOfDay, SF2000_PPFD,
+    };
+    #[cfg(feature = "sd-log")]
+    use chamber_firmware::sd_log::{CsvLog, FatVolume};
+    use chamber_firmware::sensors::{AtlasScientificSensors, AtlasSensorSlot, SensorLink};
+    use chamber_firmware::state::{DeviceEvent, DeviceStateMachine, StateReport};
+    use chamber_firmware::storage::{
+        DataLog, InternalFlash, LogRecord, Partition, RecordStore, SharedFlash, StoreError,
+        INTERNAL_SECTOR_SIZE,
+    };
+    use chamber_firmware::telemetry::TelemetryReport;
+    use chamber_firmware::xbee::command::Command;
+    use chamber_firmware::xbee::frame::{
+        Frame, FrameReader, LocalATCommandRequest, LocalATCommandResponseStatus, ModemStatusType,
+        ReceivedFrame, TransmitRequest, ASSOCIATION_INDICATION, MAX_FRAME_LEN, PAN_ID,
     };
-    use chamber_firmware::sensors::AtlasScientificSensors;
+    use chamber_firmware::xbee::log_export::{LogExportChunk, LogExportRequest};
+    use chamber_firmware::xbee::message::MessageType;
+    use chamber_firmware::xbee::time_sync::{TimeSync, TimeSyncResponse, TimeSyncSample};
+    use chamber_firmware::xbee::Payload;
+
+    /// PWM channel driving the grow light's dimming input on the RJ11 port.
+    type GrowLightPwm = Pwm<TIM3, 0, ComplementaryImpossible>;
+
+    /// CSV log on the SD card in the SDMMC1 slot.
+    #[cfg(feature = "sd-log")]
+    type SdCardLog = CsvLog<Sdmmc<SDMMC1, SdCard>>;
+
+    /// EZO device on the USART2 port, in UART mode.
+    type AtlasUart = UartTransport<Serial<USART2>>;
+
+    /// Pin driving the status LED.
+    type StatusLed = PB0<Output<PushPull>>;
+
+    /// Partitions of internal flash that storage is kept in.
+    type StorageFlash = Partition<InternalFlash>;
+
+    /// Offset of the storage sectors from the start of the internal flash.
+    ///
+    /// These are the last 64 KB of the flash, which `memory.x` leaves out of
+    /// the firmware's FLASH region, so the link fails before the firmware
+    /// grows into them.
+    const STORAGE_FLASH_OFFSET: usize = 0x1_0000;
+
+    /// Size of the storage sectors.
+    const STORAGE_FLASH_LEN: usize = 8 * INTERNAL_SECTOR_SIZE;
+
+    /// Size of the data log's partition, at the start of the storage sectors.
+    const DATA_LOG_LEN: usize = 6 * INTERNAL_SECTOR_SIZE;
+
+    /// Size of the configuration's partition, two sectors so it's always in one of them.
+    const CONFIG_LEN: usize = 2 * INTERNAL_SECTOR_SIZE;
+
+    /// Interval at which each sensor's smoothed reading is written to the data log, in ms.
+    ///
+    /// The data log holds about 3000 records, so a day and a half of the
+    /// seven sensors at this interval.
+    const DATA_LOG_INTERVAL: u64 = 300_000;
+
+    /// Interval at which new records in the data log are copied to the SD card.
+    #[cfg(feature = "sd-log")]
+    const SD_LOG_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(60_000);
+
+    /// Frequency of the SD card's bus, in MHz.
+    #[cfg(feature = "sd-log")]
+    const SD_CARD_FREQUENCY: u32 = 12;
+
+    /// Time left for other messages between the chunks of a log export.
+    const LOG_EXPORT_DELAY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(100);
+
+    /// Number of Atlas Scientific sensor drivers available to the firmware.
+    const ATLAS_SENSOR_CAPACITY: usize = 7;
+
+    /// Atlas Scientific sensors the chamber should always have connected.
+    const EXPECTED_ATLAS_SENSORS: &[DeviceType] = &[DeviceType::Humidity, DeviceType::Oxygen];
+
+    /// Time waited before reading again from an Atlas Scientific device that's still busy.
+    const ATLAS_BUSY_DELAY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(300);
+
+    /// Delay for responses that have already been waited for.
+    const NO_DELAY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(0);
+
+    /// Frequency of the PWM filtered into the grow light's 0-10 V dimming input, in Hz.
+    const GROW_LIGHT_PWM_FREQUENCY: u32 = 10_000;
+
+    /// Interval at which the grow light follows the photoperiod.
+    const PHOTOPERIOD_UPDATE_INTERVAL: Duration<u32, 1, 1000> =
+        Duration::<u32, 1, 1000>::from_ticks(10_000);
+
+    /// Interval at which the day's light integral is stored, besides when a day ends.
+    ///
+    /// A reboot loses at most this much of the day's light, without wearing the flash.
+    const DLI_SAVE_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(900_000);
+
+    /// Number of calibrations kept in the calibration log.
+    const CALIBRATION_LOG_CAPACITY: usize = 16;
+
+    /// Baud rate of the EZO device on the UART port, which is the factory default.
+    const ATLAS_UART_BAUD: BaudRate = BaudRate::B9600;
+
+    /// Number of Atlas Scientific sensor events that can be queued.
+    const ATLAS_EVENT_CAPACITY: usize = 8;
+
+    /// Interval at which the device state is reported to the coordinator, besides when it changes.
+    const STATE_REPORT_INTERVAL: Duration<u32, 1, 1000> =
+        Duration::<u32, 1, 1000>::from_ticks(60_000);
+
+    /// Interval at which the values derived from the sensors are reported to the coordinator.
+    const TELEMETRY_REPORT_INTERVAL: Duration<u32, 1, 1000> =
+        Duration::<u32, 1, 1000>::from_ticks(300_000);
+
+    /// Baud rate of the XBee on USART1, the radio's default.
+    const XBEE_BAUD_RATE: u32 = 9600;
+
+    /// Time waited for room in USART1's transmit register, about a byte at [`XBEE_BAUD_RATE`].
+    const XBEE_TX_POLL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(1);
 
     // =================================================================================
     //                             Shared Resources
@@ -35,7 +174,23 @@ mod app {
     #[shared]
     struct Shared {
         i2c_atlas: I2c<I2C1>,
-        atlas_response: Option<Vec<u8, 64>>,
+        pump: AtlasPump,
+        grow_light: GrowLight<GrowLightPwm>,
+        photoperiod: Photoperiod,
+        dli: DliTracker,
+        derating: HeatDerating,
+        /// Latest chamber air temperature in °C, from the Atlas Scientific sensors.
+        air_temperature: Option<f64>,
+        /// Latest values derived from the sensors, reported to the coordinator.
+        telemetry: TelemetryReport,
+        time_sync: TimeSync,
+        device_state: DeviceStateMachine,
+        config: Config,
+        /// Records kept in flash, such as the configuration and the calibration log.
+        record_store: RecordStore<StorageFlash>,
+        data_log: DataLog<StorageFlash>,
+        /// Calibration work on a sensor, run before its samples.
+        calibration: Option<CalibrationJob>,
     }
 
     // =================================================================================
@@ -43,7 +198,19 @@ mod app {
     // =================================================================================
     #[local]
     struct Local {
-        atlas_sensors: AtlasScientificSensors<2>,
+        atlas_sensors: AtlasScientificSensors<ATLAS_SENSOR_CAPACITY>,
+        atlas_uart: AtlasUart,
+        atlas_events: Sender<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+        status_led: StatusLed,
+        calibration_log: CalibrationLog<CALIBRATION_LOG_CAPACITY>,
+        radio: RadioConfig,
+        xbee_tx: Tx<USART1>,
+        xbee_rx: Rx<USART1>,
+        #[cfg(feature = "sd-log")]
+        csv_log: Option<SdCardLog>,
+        /// Type of the sensor at each address, which names its CSV files.
+        #[cfg(feature = "sd-log")]
+        sensor_types: heapless::LinearMap<u8, DeviceType, ATLAS_SENSOR_CAPACITY>,
     }
 
     // =================================================================================
@@ -52,294 +219,1905 @@ mod app {
     #[init(local = [
         // Atlas Scientific Sensors
         humidity_sensor: HumiditySensor = HumiditySensor::new(),
-        oxygen_sensor: OxygenSensor = OxygenSensor::new()
+        oxygen_sensor: OxygenSensor = OxygenSensor::new(),
+        conductivity_sensor: ConductivitySensor = ConductivitySensor::new(ProbeK::One),
+        temperature_sensor: TemperatureSensor = TemperatureSensor::new(TemperatureScale::Celsius),
+        dissolved_oxygen_sensor: DissolvedOxygenSensor = DissolvedOxygenSensor::new(),
+        orp_sensor: OrpSensor = OrpSensor::new(),
+        co2_sensor: Co2Sensor = Co2Sensor::new(),
+        // Internal flash, shared by the storage partitions
+        storage_flash: Option<SharedFlash<InternalFlash>> = None
     ])]
     fn init(cx: init::Context) -> (Shared, Local) {
         defmt::info!("init");
         let dp = Peripherals::take().unwrap();
 
         let power = dp.PWR.constrain();
-        let power_config = power.freeze();
+        let mut power_config = power.freeze();
+        let backup = power_config.backup().unwrap();
 
         let rcc = dp.RCC.constrain();
         let ccdr = rcc
             .sys_ck(96.MHz())
             .pll1_q_ck(48.MHz())
             .freeze(power_config, &dp.SYSCFG);
+
+        // The RTC keeps running from the coin cell while the chamber is unpowered
+        if !clock::start(dp.RTC, backup.RTC, &ccdr.clocks) {
+            defmt::warn!("[init] Real-time clock lost its time, waiting for it to be set.");
+        }
+
+        let (flash_bank, _) = dp.FLASH.split();
+        let storage_flash: &'static SharedFlash<InternalFlash> =
+            cx.local
+                .storage_flash
+                .insert(SharedFlash::new(core::cell::RefCell::new(
+                    InternalFlash::new(flash_bank, STORAGE_FLASH_OFFSET, STORAGE_FLASH_LEN)
+                        .unwrap(),
+                )));
+
+        let data_log_flash = Partition::new(storage_flash, 0, DATA_LOG_LEN).unwrap();
+        let data_log = defmt::unwrap!(DataLog::new(data_log_flash));
+
+        // Records torn by a power loss are skipped when the log is read
+        if data_log.torn() > 0 {
+            defmt::warn!(
+                "[init] Data log has {} torn records, skipping them.",
+                data_log.torn()
+            );
+        }
+
+        let config_flash = Partition::new(storage_flash, DATA_LOG_LEN, CONFIG_LEN).unwrap();
+        let mut record_store = defmt::unwrap!(RecordStore::new(config_flash));
+        let config = Config::load(&mut record_store);
+        let calibration_log = CalibrationLog::load(&mut record_store);
+        let dli_progress = DliProgress::load(&mut record_store);
+
+        let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
         let gpiob = dp.GPIOB.split(ccdr.peripheral.GPIOB);
 
+        // Configure the grow light, which starts off
+        register_force_off(grow_light_off);
+        let grow_light_pwm = dp.TIM3.pwm(
+            gpioa.pa6.into_alternate(),
+            GROW_LIGHT_PWM_FREQUENCY.Hz(),
+            ccdr.peripheral.TIM3,
+            &ccdr.clocks,
+        );
+        let grow_light = GrowLight::new(grow_light_pwm, DimmingCalibration::default());
+
+        let status_led = gpiob.pb0.into_push_pull_output();
+
+        // Configure the SD card, which is optional
+        #[cfg(feature = "sd-log")]
+        let csv_log = {
+            let gpioc = dp.GPIOC.split(ccdr.peripheral.GPIOC);
+            let gpiod = dp.GPIOD.split(ccdr.peripheral.GPIOD);
+
+            let clk = gpioc.pc12.into_alternate::<12>().speed(Speed::VeryHigh);
+            let cmd = gpiod.pd2.into_alternate::<12>().internal_pull_up(true);
+            let d0 = gpioc.pc8.into_alternate::<12>().internal_pull_up(true);
+            let d1 = gpioc.pc9.into_alternate::<12>().internal_pull_up(true);
+            let d2 = gpioc.pc10.into_alternate::<12>().internal_pull_up(true);
+            let d3 = gpioc.pc11.into_alternate::<12>().internal_pull_up(true);
+
+            let sdmmc: Sdmmc<SDMMC1, SdCard> = dp.SDMMC1.sdmmc(
+                (clk, cmd, d0, d1, d2, d3),
+                ccdr.peripheral.SDMMC1,
+                &ccdr.clocks,
+            );
+
+            mount_sd_card(sdmmc)
+        };
+
         // Configure I2C
         let scl = gpiob.pb8.into_alternate_open_drain();
         let sda = gpiob.pb9.into_alternate_open_drain();
 
-        let i2c = dp
+        let mut i2c = dp
             .I2C1
             .i2c((scl, sda), 100.kHz(), ccdr.peripheral.I2C1, &ccdr.clocks);
 
-        // Create atlas scientific sensors processor.
-        let atlas_sensors = AtlasScientificSensors {
-            sensors: [cx.local.humidity_sensor as _, cx.local.oxygen_sensor as _],
-            current_operation: None,
+        // Find out which Atlas Scientific devices are actually connected.
+        let cycles_per_ms = ccdr.clocks.sys_ck().raw() / 1000;
+        let mut detected = scan_bus(&mut i2c, |ms| cortex_m::asm::delay(cycles_per_ms * ms));
+
+        // Configure the UART port, which has room for one more EZO device
+        let atlas_serial = dp
+            .USART2
+            .serial(
+                (gpioa.pa2.into_alternate(), gpioa.pa3.into_alternate()),
+                ATLAS_UART_BAUD.bits_per_second().bps(),
+                ccdr.peripheral.USART2,
+                &ccdr.clocks,
+            )
+            .unwrap();
+        let mut atlas_uart = UartTransport::new(atlas_serial, ATLAS_UART_BAUD);
+        let uart_device = atlas_uart.identify(|ms| cortex_m::asm::delay(cycles_per_ms * ms));
+
+        // The pump isn't sampled like the sensors, so it's driven by its own task
+        let mut pump = AtlasPump::new();
+        let pump_detected = match detected
+            .iter()
+            .position(|device| device.info.device_type == DeviceType::Pump)
+        {
+            Some(index) => {
+                pump.set_address(u32::from(detected.swap_remove(index).address));
+                true
+            }
+            None => false,
+        };
+
+        // Create atlas scientific sensors processor from the detected devices.
+        let mut candidates: [&'static mut dyn AtlasSensor; ATLAS_SENSOR_CAPACITY] = [
+            cx.local.humidity_sensor,
+            cx.local.oxygen_sensor,
+            cx.local.conductivity_sensor,
+            cx.local.temperature_sensor,
+            cx.local.dissolved_oxygen_sensor,
+            cx.local.orp_sensor,
+            cx.local.co2_sensor,
+        ];
+        for candidate in candidates.iter_mut() {
+            if let Some(address) = config.sensor_address(candidate.device_type()) {
+                candidate.set_address(address);
+            }
+        }
+
+        // The device on the UART port is driven by the candidate of its type
+        let mut uart_sensor = None;
+        let mut i2c_candidates: Vec<&'static mut dyn AtlasSensor, ATLAS_SENSOR_CAPACITY> =
+            Vec::new();
+        for candidate in candidates {
+            let on_uart =
+                uart_device.is_some_and(|info| info.device_type == candidate.device_type());
+
+            if on_uart && uart_sensor.is_none() {
+                uart_sensor = Some(candidate);
+            } else {
+                // Capacity matches the candidate list, so this can't overflow
+                let _ = i2c_candidates.push(candidate);
+            }
+        }
+
+        // Boards leave the factory at their type's default address, so they're moved on first use
+        for candidate in i2c_candidates.iter_mut() {
+            if let Some(address) = config.sensor_address(candidate.device_type()) {
+                provision_configured_address(
+                    &mut i2c,
+                    &mut detected,
+                    &mut **candidate,
+                    address,
+                    |ms| cortex_m::asm::delay(cycles_per_ms * ms),
+                );
+            }
+        }
+
+        let mut atlas_sensors: AtlasScientificSensors<ATLAS_SENSOR_CAPACITY> =
+            AtlasScientificSensors::from_detected(
+                i2c_candidates,
+                &detected,
+                EXPECTED_ATLAS_SENSORS,
+            );
+
+        match (uart_device, uart_sensor) {
+            (Some(info), Some(sensor)) => {
+                defmt::info!(
+                    "[init] Found {} with firmware {} on the UART port.",
+                    info.device_type,
+                    info.firmware
+                );
+                atlas_sensors.add_uart_sensor(sensor);
+            }
+            (Some(info), None) => defmt::warn!(
+                "[init] No driver for {} device on the UART port.",
+                info.device_type
+            ),
+            (None, _) => defmt::debug!("[init] No EZO device on the UART port."),
+        }
+
+        for setting in &config.sample_intervals {
+            if !atlas_sensors.set_sample_interval(setting.address, setting.interval) {
+                defmt::warn!(
+                    "[init] No sensor at address {} for its configured sample interval.",
+                    setting.address
+                );
+            }
+        }
+
+        let radio = config.radio;
+        defmt::info!(
+            "[init] Radio joining PAN {:#x}, reporting to {:#x}.",
+            radio.pan_id,
+            radio.coordinator
+        );
+
+        // Configure the XBee, which interrupts as each byte arrives
+        let mut xbee_serial = dp
+            .USART1
+            .serial(
+                (gpioa.pa9.into_alternate(), gpioa.pa10.into_alternate()),
+                XBEE_BAUD_RATE.bps(),
+                ccdr.peripheral.USART1,
+                &ccdr.clocks,
+            )
+            .unwrap();
+        xbee_serial.listen(serial::Event::Rxne);
+        let (mut xbee_tx, xbee_rx) = xbee_serial.split();
+
+        let mut send_at_command = |id, command, value: &[u8]| {
+            let frame = Frame {
+                id: Some(id),
+                data: LocalATCommandRequest::new(command, value),
+            };
+            let mut buffer = [0; MAX_FRAME_LEN];
+            let length = defmt::unwrap!(frame.write(&mut buffer));
+            for byte in &buffer[..length] {
+                defmt::unwrap!(nb::block!(xbee_tx.write(*byte)));
+            }
         };
 
+        // The PAN ID is applied straight away, but not written to the radio's flash
+        send_at_command(1, PAN_ID, &radio.pan_id.to_be_bytes());
+
+        // The radio only reports joining the network as it happens, which may have been before a reset
+        send_at_command(2, ASSOCIATION_INDICATION, &[]);
+
+        #[cfg(feature = "sd-log")]
+        let sensor_types = atlas_sensors
+            .sensors
+            .iter()
+            .map(|slot| (slot.sensor.address() as u8, slot.sensor.device_type()))
+            .collect();
+
+        let mut dli = DliTracker::new(SF2000_PPFD);
+        dli.target = config.dli_target;
+
         // TODO setup monotonic if used
         let systick_token = rtic_monotonics::create_systick_token!();
         Systick::start(cx.core.SYST, 12_000_000, systick_token);
 
-        atlas_sensors::spawn().unwrap();
+        let mut device_state = DeviceStateMachine::new(Systick::now());
+        device_state.handle(DeviceEvent::Booted, Systick::now());
+
+        let (atlas_events, atlas_event_receiver) =
+            rtic_sync::make_channel!(AtlasEvent, ATLAS_EVENT_CAPACITY);
+
+        // The sensors are set up once the self-test passes
+        self_test::spawn(atlas_sensors.sensors.len()).unwrap();
+        atlas_events::spawn(atlas_event_receiver).unwrap();
+        defmt::unwrap!(photoperiod::spawn(dli_progress));
+        time_sync::spawn().unwrap();
+        status_led::spawn().unwrap();
+        report_state::spawn().unwrap();
+        report_telemetry::spawn().unwrap();
+        if pump_detected {
+            atlas_pump::spawn().unwrap();
+        }
+        #[cfg(feature = "sd-log")]
+        sd_log::spawn().unwrap();
 
         (
             Shared {
                 // Initialization of shared resources go here
                 i2c_atlas: i2c,
-                atlas_response: None,
+                pump,
+                grow_light,
+                photoperiod: config.photoperiod.clone(),
+                dli,
+                derating: HeatDerating::from_settings(config.derating.clone()),
+                air_temperature: None,
+                telemetry: TelemetryReport::default(),
+                time_sync: TimeSync::new(),
+                device_state,
+                config,
+                record_store,
+                data_log,
+                calibration: None,
+            },
+            Local {
+                atlas_sensors,
+                atlas_uart,
+                atlas_events,
+                status_led,
+                calibration_log,
+                radio,
+                xbee_tx,
+                xbee_rx,
+                #[cfg(feature = "sd-log")]
+                csv_log,
+                #[cfg(feature = "sd-log")]
+                sensor_types,
             },
-            Local { atlas_sensors },
         )
     }
 
+    /// Starts the SD card and mounts its FAT32 volume, or returns `None` if it can't.
+    #[cfg(feature = "sd-log")]
+    fn mount_sd_card(mut sdmmc: Sdmmc<SDMMC1, SdCard>) -> Option<SdCardLog> {
+        if let Err(error) = sdmmc.init(SD_CARD_FREQUENCY.MHz()) {
+            defmt::warn!(
+                "[init] No SD card, readings won't be logged to it: {}",
+                defmt::Debug2Format(&error)
+            );
+            return None;
+        }
+
+        match FatVolume::mount(sdmmc) {
+            Ok(volume) => Some(CsvLog::new(volume)),
+            Err(error) => {
+                defmt::warn!(
+                    "[init] SD card isn't FAT32, readings won't be logged to it: {}",
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    /// Turns the grow light off by driving its PWM pin low, without the timer.
+    ///
+    /// This is called from the panic handler, where the driver can't be used.
+    fn grow_light_off() {
+        // SAFETY: Only PA6 is changed, with atomic writes, and nothing runs after a panic
+        let gpioa = unsafe { &*stm32h7xx_hal::pac::GPIOA::ptr() };
+
+        gpioa.bsrr.write(|w| w.br6().set_bit());
+        gpioa.moder.modify(|_, w| w.moder6().output());
+    }
+
     // =================================================================================
     //                        Atlas Scientific Sensor Operations
     // =================================================================================
 
-    /// Processes current Atlas Scientific sensor operations.
-    #[task(local = [atlas_sensors], shared = [atlas_response])]
+    /// Runs Atlas Scientific sensor operations.
+    ///
+    /// Each sensor's pending action is run once its deadline passes, one bus
+    /// transaction at a time, sleeping until the next action is due. Sensors
+    /// are sampled at their own interval, and each command's response is read
+    /// once the device has had time to process it.
+    ///
+    /// Bus failures don't panic. They're recorded against the sensor, which is
+    /// recovered as described by [`chamber_firmware::atlas::FaultTracker`], and
+    /// surfaced as [`AtlasEvent`]s.
+    #[task(
+        local = [atlas_sensors, atlas_uart, atlas_events],
+        shared = [
+            i2c_atlas,
+            air_temperature,
+            telemetry,
+            device_state,
+            data_log,
+            calibration
+        ]
+    )]
     async fn atlas_sensors(mut cx: atlas_sensors::Context) {
         let sensors = cx.local.atlas_sensors;
+        let events = cx.local.atlas_events;
+        let mut initialised = false;
+        let mut faulted = 0;
 
-        if sensors.current_operation.is_none() {
-            // Determine the current operation
-            let mut soonest_deadline = None;
-            let mut soonest = None;
-            let mut soonest_index = None;
-
-            for (index, sensor) in sensors.sensors.iter().enumerate() {
-                match sensor.pending_action() {
-                    operation @ PendingAction::Startup { .. } => {
-                        // We'll deal with startup commands immediately
-                        sensors.current_operation = Some(PendingOperation {
-                            sensor: index,
-                            operation: *operation,
-                        });
+        loop {
+            let Some(index) = sensors.next_due() else {
+                defmt::warn!("[atlas_sensors] No sensors detected, stopping.");
+                return;
+            };
 
-                        break;
-                    }
-                    PendingAction::Sample { deadline } => {
-                        if soonest_deadline.is_none() {
-                            soonest_deadline = Some(*deadline);
-                            soonest = Some(sensor);
-                            soonest_index = Some(index);
-                        } else if soonest_deadline.unwrap() >= *deadline {
-                            soonest_deadline = Some(*deadline);
-                            soonest = Some(sensor);
-                            soonest_index = Some(index);
-                        }
-                    }
-                    PendingAction::Receive { deadline } => {
-                        if soonest_deadline.is_none() {
-                            soonest_deadline = Some(*deadline);
-                            soonest = Some(sensor);
-                            soonest_index = Some(index);
-                        } else if soonest_deadline.unwrap() >= *deadline {
-                            soonest_deadline = Some(*deadline);
-                            soonest = Some(sensor);
-                            soonest_index = Some(index);
-                        }
-                    }
-                }
+            // Only this task changes pending actions, so nothing becomes due sooner while waiting
+            if let Some(deadline) = sensors.sensors[index].sensor.pending_action().deadline() {
+                Systick::delay_until(deadline).await;
             }
 
-            // If soonest_deadline filtering is needed to set the current operation, we set it now.
-            if sensors.current_operation.is_none() {
-                if let (Some(soonest), Some(soonest_index)) = (soonest, soonest_index) {
-                    sensors.current_operation = Some(PendingOperation {
-                        sensor: soonest_index,
-                        operation: *soonest.pending_action(),
-                    })
+            let solution_temperature = sensors.solution_temperature(Systick::now());
+            let slot = &mut sensors.sensors[index];
+
+            let address = slot.sensor.address();
+            let Ok(bus_address) = u8::try_from(address) else {
+                defmt::error!(
+                    "[atlas_sensors] Sensor address {} is out of range.",
+                    address
+                );
+                *slot.sensor.pending_action_mut() = PendingAction::Offline {
+                    fault: SensorFault::Nack,
+                    deadline: Systick::now() + REPROBE_INTERVAL,
+                };
+                continue;
+            };
+
+            let result = match slot.link {
+                SensorLink::I2c => {
+                    let mut transport =
+                        SharedI2cTransport::new(&mut cx.shared.i2c_atlas, bus_address);
+
+                    run_pending_action(
+                        &mut transport,
+                        slot,
+                        bus_address,
+                        solution_temperature,
+                        &mut cx.shared.data_log,
+                        &mut cx.shared.calibration,
+                        events,
+                    )
+                    .await
                 }
+                SensorLink::Uart => {
+                    run_pending_action(
+                        cx.local.atlas_uart,
+                        slot,
+                        bus_address,
+                        solution_temperature,
+                        &mut cx.shared.data_log,
+                        &mut cx.shared.calibration,
+                        events,
+                    )
+                    .await
+                }
+            };
+
+            if let Err(fault) = result {
+                let (action, event) = slot.faults.fault(
+                    address,
+                    fault,
+                    slot.sensor.reset_command().is_some(),
+                    Systick::now(),
+                );
+
+                *slot.sensor.pending_action_mut() = action;
+                slot.filter.reset();
+                publish_event(events, event);
             }
-        }
 
-        let next_operation;
+            // The grow light is derated from the chamber air temperature, while it's current
+            let air_temperature = sensors.air_temperature(Systick::now());
+            cx.shared
+                .air_temperature
+                .lock(|temperature| *temperature = air_temperature);
+
+            let vapour_pressure_deficit = sensors.vapour_pressure_deficit(Systick::now());
+            cx.shared.telemetry.lock(|telemetry| {
+                telemetry.air_temperature = air_temperature;
+                telemetry.vapour_pressure_deficit = vapour_pressure_deficit;
+                telemetry.synced = clock::is_synced();
+            });
+
+            // The device state follows the sensors' setup and faults
+            if !initialised && sensors.initialised() {
+                initialised = true;
+                change_state(&mut cx.shared.device_state, DeviceEvent::SensorsInitialised);
+            }
+
+            if sensors.faulted() != faulted {
+                faulted = sensors.faulted();
+                change_state(
+                    &mut cx.shared.device_state,
+                    DeviceEvent::SensorsFaulted {
+                        count: u8::try_from(faulted).unwrap_or(u8::MAX),
+                    },
+                );
+            }
+        }
+    }
 
-        if let Some(mut current_operation) = sensors.current_operation.take() {
-            let sensor = sensors.sensors.get_mut(current_operation.sensor).unwrap();
+    /// Runs a sensor's pending action over the link to its device.
+    ///
+    /// Faults are returned for the caller to record against the sensor.
+    async fn run_pending_action(
+        transport: &mut impl AtlasTransport,
+        slot: &mut AtlasSensorSlot,
+        address: u8,
+        solution_temperature: Option<f64>,
+        data_log: &mut impl Mutex<T = DataLog<StorageFlash>>,
+        calibration: &mut impl Mutex<T = Option<CalibrationJob>>,
+        events: &mut Sender<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+    ) -> Result<(), SensorFault> {
+        match *slot.sensor.pending_action() {
+            PendingAction::Startup { command_index } => {
+                run_setup_command(transport, &mut *slot.sensor, address, command_index).await
+            }
+            PendingAction::Sample { .. } => {
+                if slot.health.is_due(Systick::now()) {
+                    if let Err(fault) = poll_status(transport, slot, address, events).await {
+                        slot.health.poll_failed(Systick::now());
 
-            let address = sensor.address();
+                        defmt::warn!(
+                            "[atlas_sensors] Couldn't poll status of sensor {}: {}",
+                            address,
+                            fault
+                        );
+                    }
+                }
 
-            let next_action;
+                run_calibration(transport, calibration, slot).await;
 
-            match current_operation.operation {
-                PendingAction::Startup { command_index } => {
-                    if sensor.setup_commands().len() > command_index {
-                        let send_command = sensor.setup_commands()[command_index];
+                slot.sensor.before_sample(Systick::now());
 
-                        let mut command_buff = Vec::new();
-                        command_buff[..send_command.len()].copy_from_slice(send_command);
+                // Compensated sensors are sampled with the latest solution temperature
+                let command = match solution_temperature {
+                    Some(temperature) if slot.sensor.temperature_compensated() => {
+                        compensated_sample_command(temperature)
+                    }
+                    _ => Vec::from_slice(slot.sensor.sample_command()).ok(),
+                };
 
+                match command {
+                    Some(command) => {
                         defmt::trace!(
-                            "[atlas_sensors] Startup command {} for sensor {} issuing.",
-                            send_command,
+                            "[atlas_sensors] Sample command {} for sensor {} issuing.",
+                            &command[..],
                             address
                         );
-                        send_atlas_command::spawn(AtlasCommand {
-                            address: address as usize,
-                            command: command_buff,
-                        })
-                        .unwrap();
-
-                        if sensor.setup_commands().len() > command_index + 1 {
-                            next_action = Some(PendingAction::Startup {
-                                command_index: command_index + 1,
-                            })
-                        } else {
-                            // We're done running startup commands, queue a sample
-                            *sensor.pending_action_mut() = PendingAction::Sample {
-                                deadline: Systick::now()
-                                    + Duration::<u32, 1, 1000>::from_ticks(5000),
-                            };
 
-                            next_action = None
-                        }
-                    } else {
-                        next_action = None
+                        transport.send_command(&command).map(|_| {
+                            *slot.sensor.pending_action_mut() = PendingAction::Receive {
+                                deadline: Systick::now() + slot.sensor.processing_delay(&command),
+                            };
+                        })
                     }
+                    None => Err(SensorFault::InvalidResponse),
                 }
-                PendingAction::Sample { deadline } => {
-                    let send_command = sensor.sample_command();
+            }
+            PendingAction::Receive { .. } => {
+                defmt::trace!("[atlas_sensors] Handling response for sensor {}.", address);
 
-                    let mut command_buff = Vec::new();
-                    command_buff[..send_command.len()].copy_from_slice(send_command);
+                // The deadline already allowed for the processing delay
+                let response = read_atlas_response(transport, NO_DELAY).await?;
 
-                    defmt::trace!(
-                        "[atlas_sensors] Sample command {} for sensor {} issuing.",
-                        send_command,
-                        address
-                    );
-                    send_atlas_command::spawn(AtlasCommand {
-                        address: address as usize,
-                        command: command_buff,
-                    })
-                    .unwrap();
+                handle_reading(
+                    transport,
+                    data_log,
+                    calibration,
+                    slot,
+                    address,
+                    &response,
+                    events,
+                )
+            }
+            PendingAction::Sleep { .. } => {
+                defmt::trace!("[atlas_sensors] Waking sensor {}.", address);
 
-                    next_action = Some(PendingAction::Receive {
-                        deadline: deadline + Duration::<u32, 1, 1000>::from_ticks(1000u32),
+                // The device may not acknowledge the command that wakes it
+                let _ = transport.send_command(WAKE_COMMAND);
+
+                read_atlas_response(transport, slot.sensor.processing_delay(WAKE_COMMAND))
+                    .await
+                    .map(|_| {
+                        slot.sensor.after_wake();
+                        slot.discard_next = slot.sensor.discard_after_wake();
+                        *slot.sensor.pending_action_mut() = PendingAction::Sample {
+                            deadline: Systick::now() + WAKE_LEAD,
+                        };
                     })
-                }
-                PendingAction::Receive { deadline: _ } => {
-                    defmt::trace!("[atlas_sensors] Handling response for sensor {}.", address);
-                    handle_atlas_response::spawn(address as _).unwrap();
-
-                    let response = loop {
-                        // Wait for response
-                        if let Some(response) =
-                            cx.shared.atlas_response.lock(|response| response.take())
-                        {
-                            break response;
-                        }
-                    };
+            }
+            PendingAction::Retry { .. } => {
+                // Set the device up again, in case it restarted
+                *slot.sensor.pending_action_mut() = PendingAction::Startup { command_index: 0 };
+                Ok(())
+            }
+            PendingAction::Reset { .. } => {
+                if let Some(command) = slot.sensor.reset_command() {
+                    slot.health.expect_restart();
+
+                    // The device may not acknowledge while it restarts, which is fine
+                    let _ = transport.send_command(command);
 
-                    let response_code = ResponseCode::try_from_probe_response(&response).unwrap();
+                    Systick::delay(RESET_DELAY).await;
+                    slot.sensor.after_restart();
+                }
 
-                    let split = response.split(|c| *c == '\r' as u8);
+                *slot.sensor.pending_action_mut() = PendingAction::Startup { command_index: 0 };
+                Ok(())
+            }
+            PendingAction::Offline { fault, .. } => {
+                let probe = run_command(transport, b"i", slot.sensor.processing_delay(b"i")).await;
 
-                    for token in split {
-                        defmt::trace!("Read token {:?}", core::str::from_utf8(&token));
+                match probe {
+                    Ok(_) => {
+                        defmt::info!("[atlas_sensors] Sensor {} is back online.", address);
+                        *slot.sensor.pending_action_mut() =
+                            PendingAction::Startup { command_index: 0 };
                     }
+                    Err(_) => {
+                        *slot.sensor.pending_action_mut() = PendingAction::Offline {
+                            fault,
+                            deadline: Systick::now() + REPROBE_INTERVAL,
+                        };
+                    }
+                }
 
-                    let mut split = response.split(|c| *c == '\r' as u8);
-                    let token = split.next().unwrap();
-                    let token = core::str::from_utf8(&token).unwrap();
+                Ok(())
+            }
+        }
+    }
 
-                    let value: f64 = token.parse().unwrap();
+    /// Issues a sensor's setup command and checks its response.
+    async fn run_setup_command(
+        transport: &mut impl AtlasTransport,
+        sensor: &mut dyn AtlasSensor,
+        address: u8,
+        command_index: usize,
+    ) -> Result<(), SensorFault> {
+        if let Some(command) = sensor.setup_command(command_index) {
+            defmt::trace!(
+                "[atlas_sensors] Startup command {} for sensor {} issuing.",
+                command,
+                address
+            );
 
-                    debug!(
-                        "Read value {} with status {} from sensor {}",
-                        value, response_code, address
-                    );
+            run_command(transport, command, sensor.processing_delay(command)).await?;
+        }
 
-                    // Do thing with response
+        *sensor.pending_action_mut() = if sensor.setup_command(command_index + 1).is_some() {
+            PendingAction::Startup {
+                command_index: command_index + 1,
+            }
+        } else {
+            // We're done running startup commands, sample straight away
+            PendingAction::Sample {
+                deadline: Systick::now(),
+            }
+        };
 
-                    *sensor.pending_action_mut() = PendingAction::Sample {
-                        deadline: Systick::now() + Duration::<u32, 1, 1000>::from_ticks(5000),
-                    };
+        Ok(())
+    }
 
-                    next_action = None
+    /// Reads a sensor's status, and publishes any health warnings it raises.
+    async fn poll_status(
+        transport: &mut impl AtlasTransport,
+        slot: &mut AtlasSensorSlot,
+        address: u8,
+        events: &mut Sender<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+    ) -> Result<(), SensorFault> {
+        let delay = slot.sensor.processing_delay(STATUS_COMMAND);
+        let response = run_command(transport, STATUS_COMMAND, delay).await?;
+        let status = response_data(&response)
+            .and_then(DeviceStatus::parse)
+            .ok_or(SensorFault::InvalidResponse)?;
+
+        debug!("Sensor {} status {}", address, status);
+
+        // A new restart reason means the device restarted since the last poll, such as on a power cycle
+        if slot
+            .health
+            .last_status
+            .is_some_and(|last| last.restart_reason != status.restart_reason)
+        {
+            slot.sensor.after_restart();
+        }
+
+        for warning in slot
+            .health
+            .record(slot.sensor.address(), status, Systick::now())
+        {
+            publish_event(events, warning);
+        }
+
+        Ok(())
+    }
+
+    /// Issues the calibration commands due for a sensor, if it's being calibrated.
+    ///
+    /// A finished job is handed to [`calibration_finished`], which stores its result.
+    async fn run_calibration(
+        transport: &mut impl AtlasTransport,
+        calibration: &mut impl Mutex<T = Option<CalibrationJob>>,
+        slot: &mut AtlasSensorSlot,
+    ) {
+        let sensor_address = slot.sensor.address();
+        let is_sensor = |job: &CalibrationJob| job.address() == sensor_address;
+
+        loop {
+            let Some(command) = calibration.lock(|job| {
+                job.as_ref()
+                    .filter(|job| is_sensor(job))
+                    .and_then(|job| job.due_command())
+            }) else {
+                break;
+            };
+
+            let delay = slot.sensor.processing_delay(&command);
+            let response = run_command(transport, &command, delay).await;
+
+            calibration.lock(|job| {
+                if let Some(job) = job.as_mut().filter(|job| is_sensor(job)) {
+                    job.handle_response(response.as_deref().map_err(|fault| *fault));
                 }
-            }
+            });
+        }
 
-            if let Some(action) = next_action {
-                current_operation.operation = action;
-                next_operation = Some(current_operation);
-                *sensor.pending_action_mut() = action;
-            } else {
-                next_operation = None;
+        let finished =
+            calibration.lock(|job| job.take_if(|job| is_sensor(job) && job.is_finished()));
+
+        if let Some(job) = finished {
+            if calibration_finished::spawn(job).is_err() {
+                defmt::error!(
+                    "[atlas_sensors] Calibration of sensor {} finished twice.",
+                    sensor_address
+                );
             }
-        } else {
-            next_operation = None;
         }
+    }
+
+    /// Handles a sensor's reading, then schedules its next sample.
+    ///
+    /// The sensor is put to sleep if its next sample is far enough away.
+    fn handle_reading(
+        transport: &mut impl AtlasTransport,
+        data_log: &mut impl Mutex<T = DataLog<StorageFlash>>,
+        calibration: &mut impl Mutex<T = Option<CalibrationJob>>,
+        slot: &mut AtlasSensorSlot,
+        address: u8,
+        response: &[u8],
+        events: &mut Sender<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+    ) -> Result<(), SensorFault> {
+        // The first value of a reading is filtered, bubbles and splashes are rejected
+        let value: f64 = response_data(response)
+            .and_then(|data| data.split(',').next())
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or(SensorFault::InvalidResponse)?;
 
-        if let Some(operation) = next_operation {
-            sensors.current_operation = Some(operation);
+        if let Some(event) = slot.faults.recovered(slot.sensor.address()) {
+            publish_event(events, event);
         }
-    }
 
-    /// Spawn to send command to Atlas Scientific sensor.
-    #[task(shared = [i2c_atlas], priority = 1)]
-    async fn send_atlas_command(mut cx: send_atlas_command::Context, command: AtlasCommand) {
-        cx.shared.i2c_atlas.lock(|i2c| {
+        if slot.discard_next {
             debug!(
-                "[send_atlas_command] Writing i2c command to {}: {:?}",
-                command.address,
-                &command.command[..command.command.len()]
+                "Discarded value {} from sensor {} after wake",
+                value, address
             );
 
-            i2c.write(
-                u8::try_from(command.address).unwrap(),
-                &command.command[..command.command.len()],
-            )
-            .unwrap();
-        })
-    }
+            slot.discard_next = false;
+            *slot.sensor.pending_action_mut() = PendingAction::Sample {
+                deadline: Systick::now(),
+            };
+
+            return Ok(());
+        }
 
-    /// Spawn to handle response from target Atlas Scientific sensor. The result is stored
-    /// in the shared `atlas_response` value.
-    #[task(shared = [atlas_response, i2c_atlas], priority = 1)]
-    async fn handle_atlas_response(mut cx: handle_atlas_response::Context, address: u8) {
-        let mut read_buffer: [u8; 64] = [0; 64];
-        let mut offset = 0;
+        // Readings taken during warm-up are invalid, so they're only flagged by the sensor
+        if slot.sensor.warming_up() {
+            slot.sensor.handle_response(response);
 
-        cx.shared.i2c_atlas.lock(|i2c| {
-            defmt::trace!("[read_atlas_response] Reading i2c from {}.", address);
+            debug!(
+                "Ignored value {} from sensor {} during warm-up",
+                value, address
+            );
+        } else {
+            // Calibration waits for the raw readings to stabilise
+            calibration.lock(|job| {
+                if let Some(job) = job
+                    .as_mut()
+                    .filter(|job| job.address() == slot.sensor.address())
+                {
+                    job.push_reading(value);
+                }
+            });
 
-            while i2c.read(address, &mut read_buffer[offset..]).is_ok() {
-                offset += 1;
+            let reading = slot.filter.push(value, clock::now(), clock::is_synced());
+
+            debug!("Read value {} from sensor {}", reading, address);
+
+            // Outliers such as bubbles and splashes mustn't become the sensor's last reading
+            if !reading.rejected {
+                slot.sensor.handle_response(response);
+                slot.read_at = Some(Systick::now());
             }
+
+            log_reading(data_log, slot, address, &reading);
+        }
+
+        let action = action_after_sample(&*slot.sensor, Systick::now(), slot.sample_interval);
+
+        if let PendingAction::Sleep { .. } = action {
+            defmt::trace!("[atlas_sensors] Putting sensor {} to sleep.", address);
+
+            transport.send_command(SLEEP_COMMAND)?;
+        }
+
+        *slot.sensor.pending_action_mut() = action;
+        Ok(())
+    }
+
+    /// Writes a sensor's smoothed reading to the data log, at most every [`DATA_LOG_INTERVAL`].
+    ///
+    /// Readings taken before the time is known, and rejected readings, aren't logged.
+    fn log_reading(
+        data_log: &mut impl Mutex<T = DataLog<StorageFlash>>,
+        slot: &mut AtlasSensorSlot,
+        address: u8,
+        reading: &FilteredReading,
+    ) {
+        let Some(at) = reading.at else {
+            return;
+        };
+
+        // The clock may have been set back since the last record
+        let due = slot.logged_at.is_none_or(|logged_at| {
+            at < logged_at || at.millis_since(logged_at) >= DATA_LOG_INTERVAL
         });
 
-        debug!(
-            "[read_atlas_response] Read {} bytes from i2c for {}.",
-            offset, address
-        );
+        if reading.rejected || !due {
+            return;
+        }
+
+        let record = LogRecord {
+            at,
+            address,
+            value: reading.smoothed as f32,
+            stable: reading.stable,
+            synced: reading.synced,
+        };
+
+        match data_log.lock(|data_log| data_log.append(&record)) {
+            Ok(()) => slot.logged_at = Some(at),
+            Err(error) => defmt::error!(
+                "[atlas_sensors] Couldn't log reading of sensor {}: {}",
+                address,
+                error
+            ),
+        }
     }
 
-    // =================================================================================
-    //                         XBEE Operation and Communication
-    // =================================================================================
-    #[task]
-    async fn xbee_handler(_cx: xbee_handler::Context) {}
+    /// Issues a command, then reads its response once `delay` has passed.
+    async fn run_command(
+        transport: &mut impl AtlasTransport,
+        command: &[u8],
+        delay: Duration<u32, 1, 1000>,
+    ) -> Result<ResponseBuffer, SensorFault> {
+        transport.send_command(command)?;
 
-    #[task]
-    async fn xbee_send(_cx: xbee_send::Context) {}
+        read_atlas_response(transport, delay).await
+    }
 
-    #[task]
-    async fn xbee_recv(_cx: xbee_recv::Context) {}
+    /// Waits `delay` for a command to be processed, then reads its response.
+    ///
+    /// The response is read again after [`ATLAS_BUSY_DELAY`] while the device
+    /// reports it's still busy, up to [`MAX_BUSY_READS`] times.
+    async fn read_atlas_response(
+        transport: &mut impl AtlasTransport,
+        delay: Duration<u32, 1, 1000>,
+    ) -> Result<ResponseBuffer, SensorFault> {
+        Systick::delay(delay).await;
+
+        for _ in 0..MAX_BUSY_READS {
+            match transport.read_response() {
+                Err(SensorFault::Busy) => Systick::delay(ATLAS_BUSY_DELAY).await,
+                result => return result,
+            }
+        }
+
+        Err(SensorFault::Busy)
+    }
+
+    /// Surfaces a sensor fault transition or health warning to the rest of the firmware.
+    fn publish_event(
+        events: &mut Sender<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+        event: AtlasEvent,
+    ) {
+        if events.try_send(event).is_err() {
+            defmt::warn!("[atlas_sensors] Event queue full, dropping {}.", event);
+        }
+    }
+
+    /// Handles Atlas Scientific sensor fault transitions and health warnings.
+    #[task(priority = 1)]
+    async fn atlas_events(
+        _cx: atlas_events::Context,
+        mut events: Receiver<'static, AtlasEvent, ATLAS_EVENT_CAPACITY>,
+    ) {
+        while let Ok(event) = events.recv().await {
+            match event {
+                AtlasEvent::Recovered { .. } => defmt::info!("[atlas_events] {}", event),
+                AtlasEvent::Retrying { .. } => defmt::warn!("[atlas_events] {}", event),
+                // Brown-outs and unexpected restarts usually point to a wiring problem
+                AtlasEvent::UnexpectedRestart { .. } | AtlasEvent::SupplyVoltage { .. } => {
+                    defmt::warn!("[atlas_events] Health warning: {}", event)
+                }
+                AtlasEvent::Resetting { .. } | AtlasEvent::Offline { .. } => {
+                    defmt::error!("[atlas_events] {}", event)
+                }
+            }
+        }
+    }
+
+    /// Runs the pump's pending action, polling a running dispense until it completes.
+    ///
+    /// Commands share the bus with the sensors, one transaction at a time.
+    #[task(shared = [i2c_atlas, pump])]
+    async fn atlas_pump(mut cx: atlas_pump::Context) {
+        loop {
+            let now = Systick::now();
+            let (address, command) = cx
+                .shared
+                .pump
+                .lock(|pump| (pump.address(), pump.due_command(now)));
+
+            let Ok(bus_address) = u8::try_from(address) else {
+                defmt::error!("[atlas_pump] Pump address {} is out of range.", address);
+                return;
+            };
+
+            match command {
+                Ok(Some(command)) => {
+                    let mut transport =
+                        SharedI2cTransport::new(&mut cx.shared.i2c_atlas, bus_address);
+                    let response =
+                        run_command(&mut transport, &command, default_processing_delay(&command))
+                            .await;
+
+                    let now = Systick::now();
+                    let result = cx.shared.pump.lock(|pump| {
+                        pump.handle_response(response.as_deref().map_err(|fault| *fault), now)
+                    });
+
+                    if let Err(error) = result {
+                        defmt::error!(
+                            "[atlas_pump] Command {} to pump failed: {}",
+                            &command[..],
+                            error
+                        );
+                    }
+
+                    // The next action may be due straight away
+                    continue;
+                }
+                Ok(None) => {}
+                Err(error) => defmt::error!("[atlas_pump] Dropped pump action: {}", error),
+            }
+
+            Systick::delay(PUMP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Starts a dispense, unless the pump is already busy.
+    #[task(shared = [pump])]
+    async fn dispense(mut cx: dispense::Context, dispense: Dispense) {
+        if !cx.shared.pump.lock(|pump| pump.dispense(dispense)) {
+            defmt::warn!("[dispense] Pump is busy, dispense dropped.");
+        }
+    }
+
+    /// Stops the pump, whatever it's doing.
+    #[task(shared = [pump])]
+    async fn stop_pump(mut cx: stop_pump::Context) {
+        defmt::info!("[stop_pump] Stopping pump.");
+
+        cx.shared.pump.lock(|pump| pump.stop());
+    }
 
     // =================================================================================
-    //                      Device Self-Check and Health Monitoring
+    //                               Grow Light Control
     // =================================================================================
-    #[task]
-    async fn self_test(_cx: self_test::Context) {}
+
+    /// Sets the grow light's intensity, in %.
+    ///
+    /// The photoperiod takes over again at its next update.
+    #[task(shared = [grow_light])]
+    async fn set_grow_light(mut cx: set_grow_light::Context, intensity: f64) {
+        defmt::info!("[set_grow_light] Setting intensity to {}%.", intensity);
+
+        cx.shared
+            .grow_light
+            .lock(|grow_light| grow_light.set_intensity(intensity));
+    }
+
+    /// Keeps the grow light at the photoperiod's intensity for the local time of day.
+    ///
+    /// Local time is UTC shifted by the configured offset. The light's daily light integral is tracked as it goes, and the
+    /// photoperiod is adapted to reach the DLI target, if there is one. The
+    /// light stays off until the real-time clock's time is known, and is
+    /// derated while the chamber is too hot.
+    ///
+    /// The integral stored before a reboot is restored once the time is known,
+    /// so the target isn't delivered twice in a day.
+    #[task(shared = [grow_light, photoperiod, dli, derating, air_temperature, config])]
+    async fn photoperiod(mut cx: photoperiod::Context, mut restored: Option<DliProgress>) {
+        let mut last_update = Systick::now();
+        let mut last_save = Systick::now();
+
+        loop {
+            let now = Systick::now();
+            let timestamp = clock::now();
+            let utc_offset = cx.shared.config.lock(|config| config.utc_offset);
+            let time = timestamp
+                .map(|timestamp| TimeOfDay::from_utc(timestamp.seconds_of_day(), utc_offset));
+
+            let intensity = match timestamp.zip(time) {
+                Some((timestamp, time)) => {
+                    // The light was at its current intensity since the last update
+                    let previous = cx.shared.grow_light.lock(|light| light.intensity());
+                    let elapsed = (now - last_update).to_secs();
+
+                    (&mut cx.shared.photoperiod, &mut cx.shared.dli).lock(|photoperiod, dli| {
+                        let day = dli.day_number(timestamp, utc_offset);
+
+                        if let Some(progress) = restored.take() {
+                            dli.restore(progress, day);
+                            defmt::info!(
+                                "[photoperiod] Restored {} mol/m² of light today.",
+                                dli.today()
+                            );
+                        }
+
+                        let completed = dli.accumulate(previous, elapsed, time);
+                        if let Some(completed) = completed {
+                            defmt::info!(
+                                "[photoperiod] Daily light integral was {} mol/m².",
+                                completed
+                            );
+                        }
+
+                        debug!("DLI so far today is {} mol/m²", dli.today());
+
+                        if completed.is_some() || now - last_save >= DLI_SAVE_INTERVAL {
+                            last_save = now;
+                            save_dli_progress::spawn(dli.progress(day)).ok();
+                        }
+
+                        dli.adapt(photoperiod, time)
+                    })
+                }
+                None => 0.0,
+            };
+            last_update = now;
+
+            // Without a current temperature, the derating holds its level
+            let air_temperature = cx.shared.air_temperature.lock(|temperature| *temperature);
+            let intensity = cx.shared.derating.lock(|derating| {
+                if let Some(event) =
+                    air_temperature.and_then(|temperature| derating.update(temperature, timestamp))
+                {
+                    defmt::warn!(
+                        "[photoperiod] {} to level {} at {}°C, intensity limited to {}%.",
+                        event.change,
+                        event.level,
+                        event.temperature,
+                        event.max_intensity
+                    );
+                }
+
+                derating.limit(intensity)
+            });
+
+            cx.shared
+                .grow_light
+                .lock(|grow_light| grow_light.set_intensity(intensity));
+
+            Systick::delay_until(now + PHOTOPERIOD_UPDATE_INTERVAL).await;
+        }
+    }
+
+    /// Stores the day's light integral, so a reboot carries on with the day.
+    #[task(shared = [record_store])]
+    async fn save_dli_progress(mut cx: save_dli_progress::Context, progress: DliProgress) {
+        if let Err(error) = cx.shared.record_store.lock(|store| progress.save(store)) {
+            defmt::error!(
+                "[save_dli_progress] Couldn't store light integral: {}",
+                error
+            );
+        }
+    }
+
+    /// Sets the daily light integral the photoperiod is adapted to, or `None` to follow it as is.
+    #[task(shared = [dli, config])]
+    async fn set_dli_target(mut cx: set_dli_target::Context, target: Option<DliTarget>) {
+        defmt::info!("[set_dli_target] Setting DLI target to {}.", target);
+
+        cx.shared.dli.lock(|dli| dli.target = target);
+        cx.shared.config.lock(|config| config.dli_target = target);
+        save_config::spawn().ok();
+    }
+
+    /// Sets the offset of local time from UTC in seconds, taking effect at the photoperiod's next update.
+    #[task(shared = [config])]
+    async fn set_utc_offset(mut cx: set_utc_offset::Context, utc_offset: i32) {
+        defmt::info!("[set_utc_offset] Setting UTC offset to {}s.", utc_offset);
+
+        cx.shared
+            .config
+            .lock(|config| config.utc_offset = utc_offset);
+        save_config::spawn().ok();
+    }
+
+    /// Replaces the photoperiod, taking effect at its next update.
+    #[task(shared = [photoperiod, config])]
+    async fn set_photoperiod(mut cx: set_photoperiod::Context, photoperiod: Photoperiod) {
+        defmt::info!(
+            "[set_photoperiod] Setting {} segments.",
+            photoperiod.segments().len()
+        );
+
+        cx.shared
+            .config
+            .lock(|config| config.photoperiod = photoperiod.clone());
+        cx.shared.photoperiod.lock(|current| *current = photoperiod);
+        save_config::spawn().ok();
+    }
+
+    // =================================================================================
+    //                              Persistent Configuration
+    // =================================================================================
+
+    /// Stores the configuration in flash, so it's kept across reboots.
+    ///
+    /// Writing the flash blocks, so no other change to the configuration can
+    /// be made while it's stored. A change made before this task runs is
+    /// stored along with the change that spawned it.
+    #[task(shared = [config, record_store])]
+    async fn save_config(mut cx: save_config::Context) {
+        let config = cx.shared.config.lock(|config| config.clone());
+
+        match cx.shared.record_store.lock(|store| config.save(store)) {
+            Ok(()) => defmt::info!("[save_config] Configuration stored."),
+            Err(error) => defmt::error!("[save_config] Couldn't store configuration: {}", error),
+        }
+    }
+
+    /// Moves a sensor's device to its configured address, if it was detected elsewhere.
+    ///
+    /// Nothing is moved if a device is already at the configured address. The
+    /// sensor is left at the address the device was detected at if the move
+    /// fails, so it's still sampled.
+    fn provision_configured_address(
+        i2c: &mut I2c<I2C1>,
+        detected: &mut [DetectedDevice],
+        sensor: &mut dyn AtlasSensor,
+        configured: u32,
+        wait_ms: impl FnMut(u32),
+    ) {
+        let device_type = sensor.device_type();
+        let Ok(new_address) = u8::try_from(configured) else {
+            defmt::warn!(
+                "[init] Configured address {} of {} sensor is out of range.",
+                configured,
+                device_type
+            );
+            return;
+        };
+
+        if detected.iter().any(|device| device.address == new_address) {
+            return;
+        }
+
+        let Some(device) = detected
+            .iter_mut()
+            .find(|device| device.info.device_type == device_type)
+        else {
+            return;
+        };
+
+        sensor.set_address(u32::from(device.address));
+
+        match provision_address(i2c, sensor, new_address, wait_ms) {
+            Ok(()) => device.address = new_address,
+            Err(error) => defmt::warn!(
+                "[init] Couldn't move {} sensor from {} to its configured address {}: {}",
+                device_type,
+                device.address,
+                new_address,
+                error
+            ),
+        }
+    }
+
+    // =================================================================================
+    //                                 Calibration
+    // =================================================================================
+
+    /// Starts calibrating a sensor, which puts the device into maintenance until it's finished.
+    ///
+    /// The operator confirms each point with [`confirm_calibration`], and the
+    /// sensor's readings are then watched until they're stable enough to
+    /// calibrate against.
+    #[task(shared = [calibration, device_state])]
+    async fn start_calibration(
+        mut cx: start_calibration::Context,
+        procedure: CalibrationProcedure,
+    ) {
+        let address = procedure.address;
+        let started = cx.shared.calibration.lock(|job| {
+            if job.is_some() {
+                return false;
+            }
+
+            *job = Some(CalibrationJob::Procedure(procedure));
+            true
+        });
+
+        if started {
+            defmt::info!("[start_calibration] Calibrating sensor {}.", address);
+            change_state(&mut cx.shared.device_state, DeviceEvent::MaintenanceStarted);
+        } else {
+            defmt::warn!("[start_calibration] A calibration is already running.");
+        }
+    }
+
+    /// Confirms that the operator has prepared the current calibration point.
+    #[task(shared = [calibration])]
+    async fn confirm_calibration(mut cx: confirm_calibration::Context) {
+        cx.shared.calibration.lock(|job| match job {
+            Some(CalibrationJob::Procedure(procedure)) => procedure.confirm(),
+            _ => defmt::warn!("[confirm_calibration] No calibration is running."),
+        });
+    }
+
+    /// Abandons the running calibration job, leaving maintenance.
+    #[task(shared = [calibration, device_state])]
+    async fn cancel_calibration(mut cx: cancel_calibration::Context) {
+        match cx.shared.calibration.lock(|job| job.take()) {
+            Some(job) => {
+                defmt::warn!(
+                    "[cancel_calibration] Cancelled calibration of sensor {}.",
+                    job.address()
+                );
+                change_state(
+                    &mut cx.shared.device_state,
+                    DeviceEvent::MaintenanceFinished,
+                );
+            }
+            None => defmt::warn!("[cancel_calibration] No calibration is running."),
+        }
+    }
+
+    /// Restores a sensor's stored calibration data, such as after its board was replaced.
+    #[task(shared = [calibration, record_store, device_state])]
+    async fn restore_calibration(mut cx: restore_calibration::Context, address: u8) {
+        let data = match cx
+            .shared
+            .record_store
+            .lock(|store| load_calibration_data(store, address))
+        {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                defmt::warn!(
+                    "[restore_calibration] No calibration stored for sensor {}.",
+                    address
+                );
+                return;
+            }
+            Err(error) => {
+                defmt::error!(
+                    "[restore_calibration] Couldn't read calibration of sensor {}: {}",
+                    address,
+                    error
+                );
+                return;
+            }
+        };
+
+        let job = CalibrationJob::Import {
+            address: u32::from(address),
+            import: CalibrationImport::new(data),
+        };
+        let started = cx.shared.calibration.lock(|current| {
+            if current.is_some() {
+                return false;
+            }
+
+            *current = Some(job);
+            true
+        });
+
+        if started {
+            change_state(&mut cx.shared.device_state, DeviceEvent::MaintenanceStarted);
+        } else {
+            defmt::warn!("[restore_calibration] A calibration is already running.");
+        }
+    }
+
+    /// Stores the result of a finished calibration job, then starts the next one if any.
+    ///
+    /// A completed calibration is logged, then the sensor's calibration data is
+    /// exported and stored, so it can be restored with [`restore_calibration`].
+    #[task(local = [calibration_log], shared = [calibration, record_store, device_state])]
+    async fn calibration_finished(mut cx: calibration_finished::Context, job: CalibrationJob) {
+        let next = match job {
+            CalibrationJob::Procedure(procedure) => match procedure.state {
+                CalibrationState::Complete => {
+                    let log = cx.local.calibration_log;
+                    log.record(&procedure, clock::now());
+
+                    if let Err(error) = cx.shared.record_store.lock(|store| log.save(store)) {
+                        defmt::error!(
+                            "[calibration_finished] Couldn't store calibration log: {}",
+                            error
+                        );
+                    }
+
+                    Some(CalibrationJob::Export {
+                        address: procedure.address,
+                        export: CalibrationExport::new(),
+                    })
+                }
+                // The procedure logs why it failed
+                _ => None,
+            },
+            CalibrationJob::Export { address, export } => {
+                match (export.result(), u8::try_from(address)) {
+                    (Some(data), Ok(bus_address)) => {
+                        match cx
+                            .shared
+                            .record_store
+                            .lock(|store| save_calibration_data(store, bus_address, data))
+                        {
+                            Ok(()) => defmt::info!(
+                                "[calibration_finished] Stored calibration of sensor {}.",
+                                address
+                            ),
+                            Err(error) => defmt::error!(
+                                "[calibration_finished] Couldn't store calibration of sensor {}: {}",
+                                address,
+                                error
+                            ),
+                        }
+                    }
+                    _ => defmt::warn!(
+                        "[calibration_finished] Couldn't export calibration of sensor {}.",
+                        address
+                    ),
+                }
+
+                None
+            }
+            CalibrationJob::Import { address, import } => {
+                if import.succeeded() {
+                    defmt::info!(
+                        "[calibration_finished] Restored calibration of sensor {}.",
+                        address
+                    );
+                } else {
+                    defmt::warn!(
+                        "[calibration_finished] Couldn't restore calibration of sensor {}.",
+                        address
+                    );
+                }
+
+                None
+            }
+        };
+
+        match next {
+            Some(job) => cx.shared.calibration.lock(|current| *current = Some(job)),
+            None => change_state(
+                &mut cx.shared.device_state,
+                DeviceEvent::MaintenanceFinished,
+            ),
+        }
+    }
+
+    // =================================================================================
+    //                                   Data Log
+    // =================================================================================
+
+    /// Sends the records logged in a time range to the coordinator, a chunk at a time.
+    ///
+    /// The log is only locked while a chunk is read, so readings are still
+    /// logged during a long export.
+    #[task(shared = [data_log])]
+    async fn export_log(mut cx: export_log::Context, request: LogExportRequest) {
+        defmt::info!(
+            "[export_log] Exporting records from {} to {}.",
+            request.from,
+            request.to
+        );
+
+        let mut cursor = cx.shared.data_log.lock(|data_log| data_log.cursor());
+        let mut count = 0;
+
+        loop {
+            let mut chunk = LogExportChunk::default();
+
+            let result = cx.shared.data_log.lock(|data_log| {
+                while !chunk.records.is_full() {
+                    match data_log.next(&mut cursor)? {
+                        Some(record) if (request.from..request.to).contains(&record.at) => {
+                            let _ = chunk.records.push(record);
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+
+                Ok::<_, StoreError>(())
+            });
+
+            if let Err(error) = result {
+                defmt::error!("[export_log] Couldn't read data log: {}", error);
+                return;
+            }
+
+            count += chunk.records.len();
+
+            // The previous chunk may still be being sent
+            while xbee_send::spawn(chunk.encode()).is_err() {
+                Systick::delay(LOG_EXPORT_DELAY).await;
+            }
+
+            if chunk.is_last() {
+                break;
+            }
+
+            Systick::delay(LOG_EXPORT_DELAY).await;
+        }
+
+        defmt::info!("[export_log] Exported {} records.", count);
+    }
+
+    /// Prints the records logged in a time range over defmt, for a log dump request.
+    #[task(shared = [data_log])]
+    async fn dump_log(mut cx: dump_log::Context, from: Timestamp, to: Timestamp) {
+        let result = cx.shared.data_log.lock(|data_log| {
+            data_log.for_each_between(from, to, |record| defmt::info!("[dump_log] {}", record))
+        });
+
+        match result {
+            Ok(count) => defmt::info!("[dump_log] {} records from {} to {}.", count, from, to),
+            Err(error) => defmt::error!("[dump_log] Couldn't read data log: {}", error),
+        }
+    }
+
+    /// Copies new records from the data log to daily CSV files on the SD card.
+    ///
+    /// Records are copied from where the data log ended at boot, so they
+    /// aren't copied twice. A record that can't be written is tried again at
+    /// the next interval.
+    #[cfg(feature = "sd-log")]
+    #[task(local = [csv_log, sensor_types], shared = [data_log])]
+    async fn sd_log(mut cx: sd_log::Context) {
+        let Some(csv_log) = cx.local.csv_log.as_mut() else {
+            return;
+        };
+
+        let mut cursor = cx.shared.data_log.lock(|data_log| data_log.end_cursor());
+
+        loop {
+            loop {
+                let previous = cursor;
+
+                match cx
+                    .shared
+                    .data_log
+                    .lock(|data_log| data_log.next(&mut cursor))
+                {
+                    Ok(Some(record)) => {
+                        let Some(&device_type) = cx.local.sensor_types.get(&record.address) else {
+                            defmt::warn!(
+                                "[sd_log] No sensor at address {}, skipping its record.",
+                                record.address
+                            );
+                            continue;
+                        };
+
+                        if let Err(error) = csv_log.log(device_type, &record) {
+                            defmt::error!("[sd_log] Couldn't write to SD card: {}", error);
+                            cursor = previous;
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(error) => {
+                        defmt::error!("[sd_log] Couldn't read data log: {}", error);
+                        break;
+                    }
+                }
+            }
+
+            Systick::delay(SD_LOG_INTERVAL).await;
+        }
+    }
+
+    /// Asks the coordinator for its time whenever a synchronisation is due.
+    ///
+    /// Readings taken before the first successful synchronisation are flagged,
+    /// since the real-time clock may have drifted while the chamber was off.
+    #[task(shared = [time_sync])]
+    async fn time_sync(mut cx: time_sync::Context) {
+        loop {
+            let now = Systick::now();
+            let (request, next_sync) = cx.shared.time_sync.lock(|time_sync| {
+                let request = time_sync.is_due(now).then(|| time_sync.request(now));
+
+                (request, time_sync.next_sync())
+            });
+
+            if let Some(request) = request {
+                defmt::debug!("[time_sync] Requesting time {}.", request.sequence);
+
+                let payload = Payload::from_slice(&request.encode()).unwrap_or_default();
+
+                if xbee_send::spawn(payload).is_err() {
+                    defmt::warn!("[time_sync] Radio is busy, time sync request dropped.");
+                }
+            }
+
+            if let Some(next_sync) = next_sync {
+                Systick::delay_until(next_sync).await;
+            }
+        }
+    }
+
+    /// Sets the real-time clock from the coordinator's time.
+    ///
+    /// The RTC is set to whole seconds, so it's set on the coordinator's next
+    /// whole second.
+    #[task]
+    async fn synchronise_clock(_cx: synchronise_clock::Context, sample: TimeSyncSample) {
+        let now = Systick::now();
+        let time = sample.time_at(now);
+        let Some(next_second) = Timestamp::from_seconds(time.seconds() + 1) else {
+            defmt::error!("[synchronise_clock] Time {} is out of range.", time);
+            return;
+        };
+
+        Systick::delay_until(now + (next_second.millis_since(time) as u32).millis()).await;
+
+        // Drift since the last synchronisation, or since the RTC was last set
+        if let Some(rtc_time) = clock::now() {
+            let drift = next_second.millis() as i64 - rtc_time.millis() as i64;
+
+            defmt::info!("[synchronise_clock] Clock was {} ms behind.", drift);
+        }
+
+        match clock::synchronise(next_second) {
+            Ok(()) => defmt::info!(
+                "[synchronise_clock] Synchronised to {} within {} ms.",
+                next_second,
+                sample.accuracy
+            ),
+            Err(error) => defmt::error!("[synchronise_clock] Couldn't set time: {}.", error),
+        }
+    }
+
+    /// Sets the real-time clock to `time`, in UTC.
+    #[task]
+    async fn set_time(_cx: set_time::Context, time: Timestamp) {
+        defmt::info!("[set_time] Setting time to {}.", time);
+
+        if let Err(error) = clock::set(time) {
+            defmt::error!("[set_time] Couldn't set time: {}.", error);
+        }
+    }
+
+    // =================================================================================
+    //                         XBEE Operation and Communication
+    // =================================================================================
+
+    /// Handles a message received from the coordinator.
+    #[task(shared = [time_sync])]
+    async fn xbee_handler(mut cx: xbee_handler::Context, payload: Payload) {
+        match MessageType::of(&payload) {
+            Some(MessageType::TimeSyncResponse) => {
+                let Some(response) = TimeSyncResponse::decode(&payload) else {
+                    defmt::warn!(
+                        "[xbee_handler] Malformed time sync response {}.",
+                        &payload[..]
+                    );
+                    return;
+                };
+
+                let now = Systick::now();
+
+                match cx
+                    .shared
+                    .time_sync
+                    .lock(|time_sync| time_sync.handle_response(&response, now))
+                {
+                    Ok(sample) => {
+                        if synchronise_clock::spawn(sample).is_err() {
+                            defmt::warn!("[xbee_handler] Clock is already being synchronised.");
+                        }
+                    }
+                    Err(error) => {
+                        defmt::warn!("[xbee_handler] Ignoring time sync response: {}.", error)
+                    }
+                }
+            }
+            Some(MessageType::LogExportRequest) => {
+                let Some(request) = LogExportRequest::decode(&payload) else {
+                    defmt::warn!(
+                        "[xbee_handler] Malformed log export request {}.",
+                        &payload[..]
+                    );
+                    return;
+                };
+
+                if export_log::spawn(request).is_err() {
+                    defmt::warn!("[xbee_handler] Log is already being exported.");
+                }
+            }
+            Some(MessageType::LogDumpRequest) => {
+                let Some(request) = LogExportRequest::decode(&payload) else {
+                    defmt::warn!(
+                        "[xbee_handler] Malformed log dump request {}.",
+                        &payload[..]
+                    );
+                    return;
+                };
+
+                if dump_log::spawn(request.from, request.to).is_err() {
+                    defmt::warn!("[xbee_handler] Log is already being dumped.");
+                }
+            }
+            Some(message_type) => match Command::decode(&payload) {
+                Some(command) => dispatch_command(message_type, command),
+                None => defmt::warn!(
+                    "[xbee_handler] Malformed or unexpected {} message {}.",
+                    message_type,
+                    &payload[..]
+                ),
+            },
+            None => {
+                if let Some(message_type) = payload.first() {
+                    defmt::warn!("[xbee_handler] Unknown message type {}.", message_type)
+                }
+            }
+        }
+    }
+
+    /// Spawns the task that carries out a command from the coordinator.
+    fn dispatch_command(message_type: MessageType, command: Command) {
+        let spawned = match command {
+            Command::SetPhotoperiod(photoperiod) => set_photoperiod::spawn(photoperiod).is_ok(),
+            Command::SetUtcOffset(utc_offset) => set_utc_offset::spawn(utc_offset).is_ok(),
+            Command::SetDliTarget(target) => set_dli_target::spawn(target).is_ok(),
+            Command::SetGrowLight(intensity) => set_grow_light::spawn(intensity).is_ok(),
+            Command::SetTime(time) => set_time::spawn(time).is_ok(),
+            Command::SetMaintenance(active) => set_maintenance::spawn(active).is_ok(),
+            Command::Dispense(request) => dispense::spawn(request).is_ok(),
+            Command::StopPump => stop_pump::spawn().is_ok(),
+            Command::StartCalibration(request) => {
+                start_calibration::spawn(request.procedure()).is_ok()
+            }
+            Command::ConfirmCalibration => confirm_calibration::spawn().is_ok(),
+            Command::CancelCalibration => cancel_calibration::spawn().is_ok(),
+            Command::RestoreCalibration(address) => restore_calibration::spawn(address).is_ok(),
+        };
+
+        if !spawned {
+            defmt::warn!(
+                "[xbee_handler] Still carrying out the last {} command, dropping the next.",
+                message_type
+            );
+        }
+    }
+
+    /// Sends a payload to the coordinator set in the radio configuration.
+    ///
+    /// The radio answers with a transmit status, once the coordinator has the payload or it's given up.
+    #[task(local = [radio, xbee_tx, frame_id: u8 = 0])]
+    async fn xbee_send(cx: xbee_send::Context, payload: Payload) {
+        // Frame ID 0 would ask the radio not to answer
+        *cx.local.frame_id = cx.local.frame_id.checked_add(1).unwrap_or(1);
+
+        let frame = Frame {
+            id: Some(*cx.local.frame_id),
+            data: TransmitRequest::new(cx.local.radio.coordinator, &payload),
+        };
+
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let Some(length) = frame.write(&mut buffer) else {
+            defmt::error!(
+                "[xbee_send] Payload of {} bytes doesn't fit in a frame.",
+                payload.len()
+            );
+            return;
+        };
+
+        for byte in &buffer[..length] {
+            while cx.local.xbee_tx.write(*byte).is_err() {
+                Systick::delay(XBEE_TX_POLL).await;
+            }
+        }
+    }
+
+    /// Reads the bytes the XBee sends, and dispatches each frame they complete.
+    ///
+    /// This runs above the software tasks, so bytes aren't lost while they're busy.
+    #[task(binds = USART1, priority = 2, local = [xbee_rx, frame_reader: FrameReader = FrameReader::new()])]
+    fn xbee_recv(cx: xbee_recv::Context) {
+        loop {
+            let byte = match cx.local.xbee_rx.read() {
+                Ok(byte) => byte,
+                Err(nb::Error::WouldBlock) => return,
+                Err(nb::Error::Other(error)) => {
+                    defmt::warn!("[xbee_recv] Serial error: {}", defmt::Debug2Format(&error));
+                    continue;
+                }
+            };
+
+            let Some(frame_data) = cx.local.frame_reader.push(byte) else {
+                continue;
+            };
+
+            match ReceivedFrame::decode(frame_data) {
+                Some(ReceivedFrame::ReceivePacket(packet)) => {
+                    let Ok(payload) = Payload::from_slice(packet.data) else {
+                        defmt::warn!(
+                            "[xbee_recv] Dropping message of {} bytes, it's too long.",
+                            packet.data.len()
+                        );
+                        continue;
+                    };
+
+                    if xbee_handler::spawn(payload).is_err() {
+                        defmt::warn!("[xbee_recv] Still handling a message, dropping the next.");
+                    }
+                }
+                Some(ReceivedFrame::TransmitStatus(status)) => {
+                    if !status.delivered() {
+                        defmt::warn!(
+                            "[xbee_recv] Frame {} wasn't delivered: status {:#x}.",
+                            status.id,
+                            status.delivery_status
+                        );
+                    }
+                }
+                Some(ReceivedFrame::LocalATCommandResponse(response)) => {
+                    if response.status != LocalATCommandResponseStatus::Ok {
+                        defmt::warn!(
+                            "[xbee_recv] AT command {}{} failed: {}.",
+                            response.command[0],
+                            response.command[1],
+                            response.status
+                        );
+                    }
+
+                    // The association is only queried at startup
+                    let joined = response.command == ASSOCIATION_INDICATION
+                        && response.status == LocalATCommandResponseStatus::Ok
+                        && response.data == [0];
+
+                    if joined && xbee_modem_status::spawn(ModemStatusType::JoinedNetwork).is_err() {
+                        defmt::warn!(
+                            "[xbee_recv] Still handling a modem status, dropping the association."
+                        );
+                    }
+                }
+                Some(ReceivedFrame::ModemStatus(modem_status)) => {
+                    if xbee_modem_status::spawn(modem_status.status).is_err() {
+                        defmt::warn!(
+                            "[xbee_recv] Still handling a modem status, dropping {}.",
+                            modem_status.status
+                        );
+                    }
+                }
+                None => defmt::debug!("[xbee_recv] Ignoring frame {}.", frame_data),
+            }
+        }
+    }
+
+    /// Follows the radio joining and leaving the coordinator's network.
+    #[task(shared = [device_state])]
+    async fn xbee_modem_status(mut cx: xbee_modem_status::Context, status: ModemStatusType) {
+        match status {
+            ModemStatusType::JoinedNetwork => {
+                change_state(&mut cx.shared.device_state, DeviceEvent::RadioJoined)
+            }
+            ModemStatusType::Disassociated => {
+                change_state(&mut cx.shared.device_state, DeviceEvent::RadioLost)
+            }
+            _ => {}
+        }
+    }
+
+    // =================================================================================
+    //                      Device Self-Check and Health Monitoring
+    // =================================================================================
+
+    /// Handles a device event, reporting the new state to the coordinator if it changed.
+    fn change_state(device_state: &mut impl Mutex<T = DeviceStateMachine>, event: DeviceEvent) {
+        let now = Systick::now();
+
+        if let Some(report) = device_state.lock(|device_state| {
+            device_state
+                .handle(event, now)
+                .map(|_| device_state.report(now))
+        }) {
+            send_state_report(report);
+        }
+    }
+
+    fn send_state_report(report: StateReport) {
+        let payload = Payload::from_slice(&report.encode()).unwrap_or_default();
+
+        if xbee_send::spawn(payload).is_err() {
+            defmt::warn!("[device_state] Radio is busy, state report dropped.");
+        }
+    }
+
+    /// Reports the device state to the coordinator periodically.
+    #[task(shared = [device_state])]
+    async fn report_state(mut cx: report_state::Context) {
+        loop {
+            let now = Systick::now();
+
+            send_state_report(cx.shared.device_state.lock(|state| state.report(now)));
+
+            Systick::delay_until(now + STATE_REPORT_INTERVAL).await;
+        }
+    }
+
+    /// Reports the values derived from the sensors to the coordinator periodically.
+    #[task(shared = [telemetry, dli])]
+    async fn report_telemetry(mut cx: report_telemetry::Context) {
+        loop {
+            let now = Systick::now();
+            let (dli_today, dli_previous_day) =
+                cx.shared.dli.lock(|dli| (dli.today(), dli.previous_day()));
+            let report = TelemetryReport {
+                dli_today: Some(dli_today),
+                dli_previous_day,
+                ..cx.shared.telemetry.lock(|telemetry| *telemetry)
+            };
+
+            defmt::info!("[report_telemetry] {}", report);
+
+            let payload = Payload::from_slice(&report.encode()).unwrap_or_default();
+            if xbee_send::spawn(payload).is_err() {
+                defmt::warn!("[report_telemetry] Radio is busy, telemetry report dropped.");
+            }
+
+            Systick::delay_until(now + TELEMETRY_REPORT_INTERVAL).await;
+        }
+    }
+
+    /// Blinks the status LED in the pattern of the device state.
+    #[task(local = [status_led], shared = [device_state])]
+    async fn status_led(mut cx: status_led::Context) {
+        loop {
+            let pattern = cx
+                .shared
+                .device_state
+                .lock(|state| state.state().blink_pattern());
+
+            for (index, &ms) in pattern.0.iter().enumerate() {
+                if index % 2 == 0 {
+                    cx.local.status_led.set_high();
+                } else {
+                    cx.local.status_led.set_low();
+                }
+
+                Systick::delay(ms.millis()).await;
+            }
+        }
+    }
+
+    /// Starts or finishes maintenance, such as calibrating sensors.
+    #[task(shared = [device_state])]
+    async fn set_maintenance(mut cx: set_maintenance::Context, active: bool) {
+        let event = if active {
+            DeviceEvent::MaintenanceStarted
+        } else {
+            DeviceEvent::MaintenanceFinished
+        };
+
+        change_state(&mut cx.shared.device_state, event);
+    }
+
+    /// Checks the hardware set up in init, then starts setting up the sensors.
+    ///
+    /// The chamber can't do anything useful without sensors. Not knowing the
+    /// time isn't a failure, since it's synchronised over the radio.
+    #[task(shared = [device_state, grow_light])]
+    async fn self_test(mut cx: self_test::Context, atlas_sensor_count: usize) {
+        let light_off = cx.shared.grow_light.lock(|light| light.intensity() == 0.0);
+
+        if clock::now().is_none() {
+            defmt::warn!("[self_test] Real-time clock isn't set.");
+        }
+        if !light_off {
+            defmt::error!("[self_test] Grow light didn't start off.");
+        }
+        if atlas_sensor_count == 0 {
+            defmt::error!("[self_test] No Atlas Scientific sensors detected.");
+        }
+
+        if light_off && atlas_sensor_count > 0 {
+            change_state(&mut cx.shared.device_state, DeviceEvent::SelfTestPassed);
+            atlas_sensors::spawn().unwrap();
+        } else {
+            change_state(&mut cx.shared.device_state, DeviceEvent::SelfTestFailed);
+        }
+    }
 
     #[task]
     async fn validate_setup(_cx: validate_setup::Context) {}
@@ -0,0 +1,313 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+/// Number of milliseconds in a second.
+pub const MILLIS_PER_SECOND: u64 = 1000;
+
+/// Number of seconds in a day.
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A point in time, in milliseconds since the Unix epoch, in UTC.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub const EPOCH: Self = Self(0);
+
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    /// Returns the timestamp `seconds` after the Unix epoch, or `None` if it's past the end of time.
+    pub const fn from_seconds(seconds: u64) -> Option<Self> {
+        match seconds.checked_mul(MILLIS_PER_SECOND) {
+            Some(millis) => Some(Self(millis)),
+            None => None,
+        }
+    }
+
+    /// Returns the number of milliseconds since the Unix epoch.
+    pub const fn millis(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the number of whole seconds since the Unix epoch.
+    pub const fn seconds(&self) -> u64 {
+        self.0 / MILLIS_PER_SECOND
+    }
+
+    /// Returns the number of milliseconds past the whole second.
+    pub const fn subsec_millis(&self) -> u16 {
+        (self.0 % MILLIS_PER_SECOND) as u16
+    }
+
+    /// Returns the number of whole seconds since midnight UTC.
+    pub const fn seconds_of_day(&self) -> u32 {
+        (self.seconds() % SECONDS_PER_DAY) as u32
+    }
+
+    /// Returns the number of whole days since the Unix epoch.
+    pub const fn days(&self) -> u64 {
+        self.seconds() / SECONDS_PER_DAY
+    }
+
+    /// Returns the timestamp `millis` later, saturating at the end of time.
+    pub const fn add_millis(&self, millis: u64) -> Self {
+        Self(self.0.saturating_add(millis))
+    }
+
+    /// Returns the number of milliseconds from `earlier` until this timestamp, or 0 if it's later.
+    pub const fn millis_since(&self, earlier: Timestamp) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Returns the calendar date and time of the timestamp.
+    ///
+    /// Times past the end of year [`u16::MAX`] are given as [`DateTime::MAX`].
+    pub fn date_time(&self) -> DateTime {
+        self.naive()
+            .and_then(DateTime::from_naive)
+            .unwrap_or(DateTime::MAX)
+    }
+
+    /// Converts a chrono date and time in UTC, returning `None` if it's before the Unix epoch.
+    pub fn from_naive(date_time: NaiveDateTime) -> Option<Self> {
+        u64::try_from(date_time.and_utc().timestamp_millis())
+            .ok()
+            .map(Self)
+    }
+
+    /// Returns the timestamp as a chrono date and time in UTC, or `None` if chrono can't hold it.
+    pub fn naive(&self) -> Option<NaiveDateTime> {
+        let millis = i64::try_from(self.0).ok()?;
+
+        chrono::DateTime::from_timestamp_millis(millis).map(|date_time| date_time.naive_utc())
+    }
+}
+
+impl defmt::Format for Timestamp {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u64:iso8601ms}", self.0)
+    }
+}
+
+/// A calendar date and time of day, in UTC.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub struct DateTime {
+    pub year: u16,
+    /// Month of the year, from 1.
+    pub month: u8,
+    /// Day of the month, from 1.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millisecond: u16,
+}
+
+impl DateTime {
+    /// The last millisecond of year [`u16::MAX`].
+    pub const MAX: Self = Self {
+        year: u16::MAX,
+        month: 12,
+        day: 31,
+        hour: 23,
+        minute: 59,
+        second: 59,
+        millisecond: 999,
+    };
+
+    /// Creates a date and time on a whole second.
+    ///
+    /// Returns `None` if it's out of range or before the Unix epoch.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        let date_time = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            millisecond: 0,
+        };
+
+        date_time.is_valid().then_some(date_time)
+    }
+
+    /// Converts a chrono date and time, returning `None` if its year is out of range.
+    ///
+    /// A leap second is given as the second before it.
+    pub fn from_naive(date_time: NaiveDateTime) -> Option<Self> {
+        let year = u16::try_from(date_time.year()).ok()?;
+
+        Some(Self {
+            year,
+            month: date_time.month() as u8,
+            day: date_time.day() as u8,
+            hour: date_time.hour() as u8,
+            minute: date_time.minute() as u8,
+            second: date_time.second() as u8,
+            millisecond: (date_time.nanosecond() / 1_000_000).min(999) as u16,
+        })
+        .filter(Self::is_valid)
+    }
+
+    /// Returns the date and time as a chrono date and time, or `None` if it isn't valid.
+    pub fn naive(&self) -> Option<NaiveDateTime> {
+        // chrono takes milliseconds past 999 as a leap second
+        if self.year < 1970 || self.millisecond >= 1000 {
+            return None;
+        }
+
+        NaiveDate::from_ymd_opt(
+            i32::from(self.year),
+            u32::from(self.month),
+            u32::from(self.day),
+        )?
+        .and_hms_milli_opt(
+            u32::from(self.hour),
+            u32::from(self.minute),
+            u32::from(self.second),
+            u32::from(self.millisecond),
+        )
+    }
+
+    /// Returns whether every field is in range, and the date isn't before the Unix epoch.
+    pub fn is_valid(&self) -> bool {
+        self.naive().is_some()
+    }
+
+    /// Returns the timestamp of the date and time, or `None` if it isn't valid.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.naive().and_then(Timestamp::from_naive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_is_day_zero() {
+        let epoch = DateTime::new(1970, 1, 1, 0, 0, 0).unwrap();
+
+        assert!(Timestamp::EPOCH.date_time() == epoch);
+        assert!(epoch.timestamp() == Some(Timestamp::EPOCH));
+        assert_eq!(Timestamp::from_seconds(86_399).unwrap().date_time().day, 1);
+        assert_eq!(Timestamp::from_seconds(86_400).unwrap().date_time().day, 2);
+    }
+
+    #[test]
+    fn days_follow_the_calendar() {
+        let mut days = 0;
+
+        for year in 1970..2500 {
+            for month in 1..=12 {
+                let next_month = if month == 12 {
+                    NaiveDate::from_ymd_opt(i32::from(year) + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(i32::from(year), month + 1, 1)
+                };
+                let month_len = next_month.unwrap().pred_opt().unwrap().day();
+
+                for day in 1..=month_len {
+                    let date_time = DateTime::new(year, month as u8, day as u8, 12, 0, 0).unwrap();
+                    let timestamp = date_time.timestamp().unwrap();
+
+                    assert_eq!(timestamp.days(), days);
+                    assert!(timestamp.date_time() == date_time);
+                    days += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leap_years() {
+        // Centuries are only leap years every 400 years
+        assert!(DateTime::new(2024, 2, 29, 0, 0, 0).is_some());
+        assert!(DateTime::new(2000, 2, 29, 0, 0, 0).is_some());
+        assert!(DateTime::new(2100, 2, 29, 0, 0, 0).is_none());
+        assert!(DateTime::new(2400, 2, 29, 0, 0, 0).is_some());
+
+        let day_after = |year| {
+            let date_time = DateTime::new(year, 2, 28, 12, 0, 0).unwrap();
+            date_time
+                .timestamp()
+                .unwrap()
+                .add_millis(86_400_000)
+                .date_time()
+        };
+        assert_eq!((day_after(2024).month, day_after(2024).day), (2, 29));
+        assert_eq!((day_after(2100).month, day_after(2100).day), (3, 1));
+    }
+
+    #[test]
+    fn timestamp_of_date_time() {
+        let date_time = DateTime::new(2024, 2, 29, 13, 45, 30).unwrap();
+
+        assert_eq!(
+            date_time.timestamp().map(|timestamp| timestamp.seconds()),
+            Some(1_709_214_330)
+        );
+        assert!(Timestamp::from_seconds(1_709_214_330).unwrap().date_time() == date_time);
+        assert_eq!(
+            DateTime::new(1970, 1, 1, 0, 0, 0)
+                .and_then(|date_time| date_time.timestamp())
+                .map(|timestamp| timestamp.millis()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn timestamp_round_trip_keeps_milliseconds() {
+        let timestamp = Timestamp::from_millis(4_102_444_799_999);
+        let date_time = timestamp.date_time();
+
+        assert!(
+            date_time
+                == DateTime {
+                    year: 2099,
+                    month: 12,
+                    day: 31,
+                    hour: 23,
+                    minute: 59,
+                    second: 59,
+                    millisecond: 999,
+                }
+        );
+        assert!(date_time.timestamp() == Some(timestamp));
+    }
+
+    #[test]
+    fn far_future_times_dont_wrap() {
+        assert!(Timestamp::from_seconds(u64::MAX).is_none());
+        assert!(Timestamp::from_seconds(u64::MAX / MILLIS_PER_SECOND).is_some());
+
+        // Year 65 536 would otherwise wrap round to year 0
+        assert!(Timestamp::from_millis(u64::MAX).date_time() == DateTime::MAX);
+        assert!(Timestamp::from_millis(2_005_949_145_600_000).date_time() == DateTime::MAX);
+        assert!(DateTime::MAX.timestamp().unwrap().add_millis(1).date_time() == DateTime::MAX);
+        assert!(DateTime::MAX.timestamp().unwrap().millis() < 2_005_949_145_600_000);
+    }
+
+    #[test]
+    fn invalid_date_times_are_rejected() {
+        assert!(DateTime::new(2023, 2, 29, 0, 0, 0).is_none());
+        assert!(DateTime::new(1969, 12, 31, 23, 59, 59).is_none());
+        assert!(DateTime::new(2024, 4, 31, 0, 0, 0).is_none());
+        assert!(DateTime::new(2024, 1, 1, 24, 0, 0).is_none());
+
+        let date_time = DateTime {
+            millisecond: 1000,
+            ..DateTime::new(2024, 1, 1, 0, 0, 0).unwrap()
+        };
+        assert!(date_time.timestamp().is_none());
+
+        // Out of range fields that chrono would otherwise take as a leap second
+        let date_time = DateTime {
+            second: 60,
+            ..DateTime::new(2024, 1, 1, 0, 0, 0).unwrap()
+        };
+        assert!(date_time.timestamp().is_none());
+    }
+}
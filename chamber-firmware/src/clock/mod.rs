@@ -0,0 +1,5 @@
+mod calendar;
+mod rtc;
+
+pub use calendar::*;
+pub use rtc::*;
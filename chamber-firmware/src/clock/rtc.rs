@@ -0,0 +1,109 @@
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::Timelike;
+use cortex_m::interrupt::Mutex;
+use stm32h7xx_hal::prelude::*;
+use stm32h7xx_hal::rcc::{backup, CoreClocks};
+use stm32h7xx_hal::rtc::{Rtc, RtcClock};
+use stm32h7xx_hal::stm32::RTC as RtcPeripheral;
+
+use super::Timestamp;
+
+/// Frequency of the LSE crystal clocking the RTC, in Hz.
+pub const LSE_FREQUENCY: u32 = 32_768;
+
+/// Earliest year the RTC's calendar can hold.
+pub const MIN_RTC_YEAR: u16 = 2001;
+
+/// Latest year the RTC's calendar can hold.
+pub const MAX_RTC_YEAR: u16 = 2099;
+
+/// Returns whether `time` is within the years the RTC's calendar can hold.
+pub fn can_hold(time: Timestamp) -> bool {
+    (MIN_RTC_YEAR..=MAX_RTC_YEAR).contains(&time.date_time().year)
+}
+
+/// Error setting the real-time clock.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ClockError {
+    /// The RTC hasn't been started.
+    NotStarted,
+    /// The time is outside the years the RTC's calendar can hold.
+    OutOfRange,
+}
+
+/// Real-time clock, shared with the defmt timestamp.
+static RTC: Mutex<RefCell<Option<Rtc>>> = Mutex::new(RefCell::new(None));
+
+/// Whether the RTC has been synchronised with the coordinator since boot.
+static SYNCED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the RTC from the LSE, keeping the time if it survived a reset.
+///
+/// The RTC lives in the backup domain, which the coin cell on VBAT keeps
+/// running while the chamber is unpowered. Its calendar is only reset if it
+/// wasn't running from the LSE, in which case the time is unknown until it's
+/// set.
+///
+/// Returns whether the time is known.
+pub fn start(rtc: RtcPeripheral, prec: backup::Rtc, clocks: &CoreClocks) -> bool {
+    let rtc = Rtc::open_or_init(
+        rtc,
+        prec,
+        RtcClock::Lse {
+            freq: LSE_FREQUENCY.Hz(),
+            bypass: false,
+            css: false,
+        },
+        clocks,
+    );
+    let known = rtc.date_time().is_some();
+
+    cortex_m::interrupt::free(|cs| *RTC.borrow(cs).borrow_mut() = Some(rtc));
+    known
+}
+
+/// Returns the current time, if the RTC was started and its time is known.
+pub fn now() -> Option<Timestamp> {
+    cortex_m::interrupt::free(|cs| {
+        // Logging while the time is being set mustn't panic
+        let rtc = RTC.borrow(cs).try_borrow().ok()?;
+
+        Timestamp::from_naive(rtc.as_ref()?.date_time()?)
+    })
+}
+
+/// Sets the RTC to `time`, to the whole second.
+pub fn set(time: Timestamp) -> Result<(), ClockError> {
+    if !can_hold(time) {
+        return Err(ClockError::OutOfRange);
+    }
+
+    let date_time = time
+        .naive()
+        .and_then(|date_time| date_time.with_nanosecond(0))
+        .ok_or(ClockError::OutOfRange)?;
+
+    cortex_m::interrupt::free(|cs| {
+        let mut rtc = RTC.borrow(cs).borrow_mut();
+        let rtc = rtc.as_mut().ok_or(ClockError::NotStarted)?;
+
+        rtc.set_date_time(date_time);
+        Ok(())
+    })
+}
+
+/// Sets the RTC to `time` from the coordinator, marking the clock as synchronised.
+pub fn synchronise(time: Timestamp) -> Result<(), ClockError> {
+    set(time)?;
+    SYNCED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns whether the RTC has been synchronised with the coordinator since boot.
+///
+/// Until then, the RTC's time may have drifted, or been set by hand.
+pub fn is_synced() -> bool {
+    SYNCED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,578 @@
+use rtic_monotonics::systick::fugit::Duration;
+
+use crate::atlas::DeviceType;
+use crate::light::{
+    DeratingSettings, DeratingStep, DliTarget, Photoperiod, PhotoperiodSegment, TimeOfDay,
+    MAX_DERATING_STEPS, MAX_PHOTOPERIOD_SEGMENTS,
+};
+use crate::storage::{Flash, RecordStore, StoreError, MAX_RECORD_LEN};
+
+/// Key of the configuration record.
+pub const CONFIG_KEY: u8 = 1;
+
+/// Version of the configuration's layout.
+///
+/// Fields are tagged, so firmware reads configurations with fields it doesn't
+/// know, or without fields it does, and adding a field doesn't need a new
+/// version. The version only changes when an existing field changes meaning,
+/// and firmware leaves configurations of other versions alone.
+pub const CONFIG_VERSION: u8 = 1;
+
+/// Shortest time between samples of a sensor that's accepted from a stored configuration.
+///
+/// Shorter intervals would keep the bus busy with a single sensor.
+pub const MIN_SAMPLE_INTERVAL: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(1_000);
+
+/// Largest number of per-sensor settings of each kind.
+pub const MAX_SENSOR_SETTINGS: usize = 8;
+
+const TAG_SAMPLE_INTERVAL: u8 = 1;
+const TAG_SENSOR_ADDRESS: u8 = 2;
+const TAG_LIGHT_RAMPS: u8 = 3;
+const TAG_PHOTOPERIOD_SEGMENT: u8 = 4;
+const TAG_DLI_TARGET: u8 = 5;
+const TAG_RADIO: u8 = 6;
+const TAG_UTC_OFFSET: u8 = 7;
+const TAG_DERATING_HYSTERESIS: u8 = 8;
+const TAG_DERATING_STEP: u8 = 9;
+
+/// How often the sensor at an address is sampled.
+#[derive(Clone, Copy)]
+pub struct SampleIntervalSetting {
+    pub address: u32,
+    pub interval: Duration<u32, 1, 1000>,
+}
+
+/// The address a type of sensor is at.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct SensorAddressSetting {
+    pub device_type: DeviceType,
+    pub address: u32,
+}
+
+/// Settings of the XBee radio.
+#[derive(Clone, Copy, Default, defmt::Format)]
+pub struct RadioConfig {
+    /// PAN ID of the network to join, or 0 to join any network.
+    pub pan_id: u64,
+    /// 64-bit address of the coordinator, which is 0 for the network's coordinator.
+    pub coordinator: u64,
+}
+
+/// Settings that can be changed without rebuilding the firmware.
+///
+/// Sensors without a setting use their driver's defaults.
+#[derive(Clone, Default)]
+pub struct Config {
+    pub sample_intervals: heapless::Vec<SampleIntervalSetting, MAX_SENSOR_SETTINGS>,
+    pub sensor_addresses: heapless::Vec<SensorAddressSetting, MAX_SENSOR_SETTINGS>,
+    pub photoperiod: Photoperiod,
+    pub dli_target: Option<DliTarget>,
+    pub radio: RadioConfig,
+    /// Offset of the chamber's local time from UTC in seconds, positive east of Greenwich.
+    ///
+    /// The photoperiod and the daily light integral follow local time.
+    pub utc_offset: i32,
+    /// Temperatures at which the grow light is derated as the chamber heats up.
+    pub derating: DeratingSettings,
+}
+
+impl Config {
+    /// Returns the configured address of a type of sensor.
+    pub fn sensor_address(&self, device_type: DeviceType) -> Option<u32> {
+        self.sensor_addresses
+            .iter()
+            .find(|setting| setting.device_type == device_type)
+            .map(|setting| setting.address)
+    }
+
+    /// Loads the configuration from `store`.
+    ///
+    /// The defaults are used if there's no configuration, or it can't be
+    /// read. A configuration of another version is left as it is, so it's
+    /// still there once the firmware that stored it is back.
+    pub fn load<F: Flash>(store: &mut RecordStore<F>) -> Self {
+        let mut buffer = [0; MAX_RECORD_LEN];
+
+        let (version, len) = match store.fetch(CONFIG_KEY, &mut buffer) {
+            Ok(Some(record)) => record,
+            Ok(None) => {
+                defmt::info!("[config] No configuration stored, using defaults.");
+                return Self::default();
+            }
+            Err(error) => {
+                defmt::error!(
+                    "[config] Couldn't read configuration, using defaults: {}",
+                    error
+                );
+                return Self::default();
+            }
+        };
+
+        if version != CONFIG_VERSION {
+            defmt::warn!(
+                "[config] Stored configuration has unknown version {}, using defaults.",
+                version
+            );
+            return Self::default();
+        }
+
+        Self::decode(&buffer[..len]).unwrap_or_else(|| {
+            defmt::error!("[config] Stored configuration is corrupt, using defaults.");
+            Self::default()
+        })
+    }
+
+    /// Stores the configuration in `store`.
+    pub fn save<F: Flash>(&self, store: &mut RecordStore<F>) -> Result<(), StoreError> {
+        let data = self.encode().ok_or(StoreError::TooLarge)?;
+
+        store.store(CONFIG_KEY, CONFIG_VERSION, &data)
+    }
+
+    /// Encodes the configuration as a list of fields, each a tag, length and value.
+    ///
+    /// Returns `None` if it doesn't fit in a record.
+    pub fn encode(&self) -> Option<heapless::Vec<u8, MAX_RECORD_LEN>> {
+        let mut data = heapless::Vec::new();
+
+        let mut field = |tag: u8, values: &[&[u8]]| -> Option<()> {
+            let len: usize = values.iter().map(|value| value.len()).sum();
+
+            data.push(tag).ok()?;
+            data.push(u8::try_from(len).ok()?).ok()?;
+            values
+                .iter()
+                .try_for_each(|value| data.extend_from_slice(value))
+                .ok()
+        };
+
+        for setting in &self.sample_intervals {
+            field(
+                TAG_SAMPLE_INTERVAL,
+                &[
+                    &setting.address.to_le_bytes(),
+                    &setting.interval.ticks().to_le_bytes(),
+                ],
+            )?;
+        }
+
+        for setting in &self.sensor_addresses {
+            field(
+                TAG_SENSOR_ADDRESS,
+                &[
+                    &setting.address.to_le_bytes(),
+                    setting.device_type.code().as_bytes(),
+                ],
+            )?;
+        }
+
+        field(
+            TAG_LIGHT_RAMPS,
+            &[
+                &self.photoperiod.sunrise.to_le_bytes(),
+                &self.photoperiod.sunset.to_le_bytes(),
+            ],
+        )?;
+
+        for segment in self.photoperiod.segments() {
+            field(
+                TAG_PHOTOPERIOD_SEGMENT,
+                &[
+                    &segment.on.seconds().to_le_bytes(),
+                    &segment.off.seconds().to_le_bytes(),
+                    &segment.intensity.to_le_bytes(),
+                ],
+            )?;
+        }
+
+        if let Some(target) = self.dli_target {
+            field(
+                TAG_DLI_TARGET,
+                &[&target.dli.to_le_bytes(), &target.max_stretch.to_le_bytes()],
+            )?;
+        }
+
+        field(
+            TAG_RADIO,
+            &[
+                &self.radio.pan_id.to_le_bytes(),
+                &self.radio.coordinator.to_le_bytes(),
+            ],
+        )?;
+
+        field(TAG_UTC_OFFSET, &[&self.utc_offset.to_le_bytes()])?;
+
+        field(
+            TAG_DERATING_HYSTERESIS,
+            &[&self.derating.hysteresis.to_le_bytes()],
+        )?;
+
+        for step in self.derating.steps() {
+            field(
+                TAG_DERATING_STEP,
+                &[
+                    &step.temperature.to_le_bytes(),
+                    &step.max_intensity.to_le_bytes(),
+                ],
+            )?;
+        }
+
+        Some(data)
+    }
+
+    /// Decodes a configuration encoded by [`Config::encode`].
+    ///
+    /// Unknown fields, such as from newer firmware, are skipped. Missing
+    /// fields, such as from older firmware, keep their defaults. Sample
+    /// intervals are raised to at least [`MIN_SAMPLE_INTERVAL`]. Returns
+    /// `None` if a field is malformed.
+    pub fn decode(mut data: &[u8]) -> Option<Self> {
+        let mut config = Self::default();
+        let mut ramps = None;
+        let mut segments: heapless::Vec<PhotoperiodSegment, MAX_PHOTOPERIOD_SEGMENTS> =
+            heapless::Vec::new();
+        let mut hysteresis = None;
+        let mut derating_steps: heapless::Vec<DeratingStep, MAX_DERATING_STEPS> =
+            heapless::Vec::new();
+
+        while let [tag, len, rest @ ..] = data {
+            let (value, rest) = rest.split_at_checked(usize::from(*len))?;
+            let mut value = FieldReader(value);
+            data = rest;
+
+            match *tag {
+                TAG_SAMPLE_INTERVAL => {
+                    let setting = SampleIntervalSetting {
+                        address: value.u32()?,
+                        interval: Duration::<u32, 1, 1000>::from_ticks(value.u32()?)
+                            .max(MIN_SAMPLE_INTERVAL),
+                    };
+                    config.sample_intervals.push(setting).ok()?;
+                }
+                TAG_SENSOR_ADDRESS => {
+                    let address = value.u32()?;
+                    let device_type = core::str::from_utf8(value.0).ok()?.parse().ok()?;
+
+                    config
+                        .sensor_addresses
+                        .push(SensorAddressSetting {
+                            device_type,
+                            address,
+                        })
+                        .ok()?;
+                }
+                TAG_LIGHT_RAMPS => ramps = Some((value.u32()?, value.u32()?)),
+                TAG_PHOTOPERIOD_SEGMENT => {
+                    let segment = PhotoperiodSegment {
+                        on: TimeOfDay::from_seconds(value.u32()?),
+                        off: TimeOfDay::from_seconds(value.u32()?),
+                        intensity: value.f64()?,
+                    };
+                    segments.push(segment).ok()?;
+                }
+                TAG_DLI_TARGET => {
+                    config.dli_target = Some(DliTarget {
+                        dli: value.f64()?,
+                        max_stretch: value.u32()?,
+                    })
+                }
+                TAG_RADIO => {
+                    config.radio = RadioConfig {
+                        pan_id: value.u64()?,
+                        coordinator: value.u64()?,
+                    }
+                }
+                TAG_UTC_OFFSET => config.utc_offset = value.i32()?,
+                TAG_DERATING_HYSTERESIS => hysteresis = Some(value.f64()?),
+                TAG_DERATING_STEP => {
+                    let step = DeratingStep {
+                        temperature: value.f64()?,
+                        max_intensity: value.f64()?,
+                    };
+                    derating_steps.push(step).ok()?;
+                }
+                _ => {}
+            }
+        }
+
+        if !data.is_empty() {
+            return None;
+        }
+
+        if let Some((sunrise, sunset)) = ramps {
+            config.photoperiod = Photoperiod::new(&segments, sunrise, sunset)?;
+        }
+
+        // The hysteresis is always stored, so a rule without steps is kept as one
+        if let Some(hysteresis) = hysteresis {
+            config.derating = DeratingSettings::new(&derating_steps, hysteresis)?;
+        }
+
+        Some(config)
+    }
+}
+
+/// Reads little-endian values from the start of a field.
+struct FieldReader<'a>(&'a [u8]);
+
+impl FieldReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (value, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::{DEFAULT_DERATING_HYSTERESIS, DEFAULT_DERATING_STEPS};
+    use crate::storage::RamFlash;
+
+    type TestFlash = RamFlash<{ 2 * 1024 }, 1024, 16>;
+
+    fn open(flash: TestFlash) -> RecordStore<TestFlash> {
+        let Ok(store) = RecordStore::new(flash) else {
+            panic!("couldn't open the store");
+        };
+        store
+    }
+
+    fn example() -> Config {
+        let mut config = Config::default();
+
+        config
+            .sample_intervals
+            .push(SampleIntervalSetting {
+                address: 0x6F,
+                interval: Duration::<u32, 1, 1000>::from_ticks(30_000),
+            })
+            .ok()
+            .unwrap();
+        config
+            .sensor_addresses
+            .push(SensorAddressSetting {
+                device_type: DeviceType::Humidity,
+                address: 0x70,
+            })
+            .ok()
+            .unwrap();
+        config.photoperiod = Photoperiod::new(
+            &[PhotoperiodSegment {
+                on: TimeOfDay::from_seconds(6 * 3600),
+                off: TimeOfDay::from_seconds(22 * 3600),
+                intensity: 75.0,
+            }],
+            900,
+            600,
+        )
+        .unwrap();
+        config.dli_target = Some(DliTarget {
+            dli: 35.0,
+            max_stretch: 3600,
+        });
+        config.radio = RadioConfig {
+            pan_id: 0x1234,
+            coordinator: 0x0013_A200_4052_2BAA,
+        };
+        config.utc_offset = -5 * 3600;
+        config.derating = DeratingSettings::new(
+            &[
+                DeratingStep {
+                    temperature: 28.0,
+                    max_intensity: 60.0,
+                },
+                DeratingStep {
+                    temperature: 32.0,
+                    max_intensity: 0.0,
+                },
+            ],
+            2.0,
+        )
+        .unwrap();
+
+        config
+    }
+
+    /// Checks every field of `config` matches [`example`].
+    fn assert_example(config: &Config) {
+        let [interval] = &config.sample_intervals[..] else {
+            panic!("expected one sample interval");
+        };
+        assert_eq!(interval.address, 0x6F);
+        assert_eq!(interval.interval.ticks(), 30_000);
+
+        assert_eq!(config.sensor_address(DeviceType::Humidity), Some(0x70));
+        assert_eq!(config.sensor_address(DeviceType::Oxygen), None);
+
+        let [segment] = config.photoperiod.segments() else {
+            panic!("expected one photoperiod segment");
+        };
+        assert_eq!(segment.on.seconds(), 6 * 3600);
+        assert_eq!(segment.off.seconds(), 22 * 3600);
+        assert_eq!(segment.intensity, 75.0);
+        assert_eq!(config.photoperiod.sunrise, 900);
+        assert_eq!(config.photoperiod.sunset, 600);
+
+        let target = config.dli_target.unwrap();
+        assert_eq!(target.dli, 35.0);
+        assert_eq!(target.max_stretch, 3600);
+
+        assert_eq!(config.radio.pan_id, 0x1234);
+        assert_eq!(config.radio.coordinator, 0x0013_A200_4052_2BAA);
+        assert_eq!(config.utc_offset, -5 * 3600);
+
+        let [first, second] = config.derating.steps() else {
+            panic!("expected two derating steps");
+        };
+        assert_eq!((first.temperature, first.max_intensity), (28.0, 60.0));
+        assert_eq!((second.temperature, second.max_intensity), (32.0, 0.0));
+        assert_eq!(config.derating.hysteresis, 2.0);
+    }
+
+    /// Checks `config` has the defaults.
+    fn assert_default(config: &Config) {
+        assert!(config.sample_intervals.is_empty());
+        assert!(config.sensor_addresses.is_empty());
+        assert!(config.dli_target.is_none());
+        assert_eq!(config.radio.coordinator, 0);
+        assert_eq!(config.utc_offset, 0);
+        assert_eq!(config.derating.steps().len(), DEFAULT_DERATING_STEPS.len());
+        assert_eq!(config.derating.hysteresis, DEFAULT_DERATING_HYSTERESIS);
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let data = example().encode().unwrap();
+
+        assert_example(&Config::decode(&data).unwrap());
+    }
+
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let mut data = example().encode().unwrap();
+        data.extend_from_slice(&[0xF0, 3, 1, 2, 3]).unwrap();
+
+        assert_example(&Config::decode(&data).unwrap());
+    }
+
+    #[test]
+    fn malformed_fields_are_rejected() {
+        let data = example().encode().unwrap();
+
+        // Cut short in the middle of a field
+        assert!(Config::decode(&data[..data.len() - 1]).is_none());
+        // A field too short for its value
+        assert!(Config::decode(&[TAG_UTC_OFFSET, 2, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn derating_can_be_turned_off() {
+        let mut config = Config::default();
+        config.derating = DeratingSettings::new(&[], 0.0).unwrap();
+
+        let decoded = Config::decode(&config.encode().unwrap()).unwrap();
+        assert!(decoded.derating.steps().is_empty());
+    }
+
+    #[test]
+    fn derating_steps_out_of_order_are_rejected() {
+        let mut data = std::vec![TAG_DERATING_HYSTERESIS, 8];
+        data.extend_from_slice(&1.0f64.to_le_bytes());
+        for temperature in [33.0f64, 30.0] {
+            data.extend_from_slice(&[TAG_DERATING_STEP, 16]);
+            data.extend_from_slice(&temperature.to_le_bytes());
+            data.extend_from_slice(&50.0f64.to_le_bytes());
+        }
+
+        assert!(Config::decode(&data).is_none());
+    }
+
+    #[test]
+    fn short_sample_intervals_are_raised() {
+        let mut config = Config::default();
+        for (address, interval) in [(0x63, 0), (0x64, 10), (0x65, 5_000)] {
+            config
+                .sample_intervals
+                .push(SampleIntervalSetting {
+                    address,
+                    interval: Duration::<u32, 1, 1000>::from_ticks(interval),
+                })
+                .ok()
+                .unwrap();
+        }
+
+        let decoded = Config::decode(&config.encode().unwrap()).unwrap();
+        let intervals: std::vec::Vec<u32> = decoded
+            .sample_intervals
+            .iter()
+            .map(|setting| setting.interval.ticks())
+            .collect();
+
+        assert_eq!(intervals, [1_000, 1_000, 5_000]);
+    }
+
+    #[test]
+    fn defaults_without_a_record() {
+        let mut store = open(TestFlash::new());
+
+        assert_default(&Config::load(&mut store));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut store = open(TestFlash::new());
+        assert!(example().save(&mut store).is_ok());
+
+        let mut store = open(store.into_inner());
+        assert_example(&Config::load(&mut store));
+    }
+
+    #[test]
+    fn corrupt_record_falls_back_to_defaults() {
+        let mut store = open(TestFlash::new());
+        assert!(store
+            .store(CONFIG_KEY, CONFIG_VERSION, &[TAG_RADIO, 16, 0])
+            .is_ok());
+
+        assert_default(&Config::load(&mut store));
+    }
+
+    #[test]
+    fn other_versions_are_left_alone() {
+        // Decodable, but its fields may mean something else to this firmware
+        let other = example().encode().unwrap();
+
+        for other_version in [0, CONFIG_VERSION + 1] {
+            let mut store = open(TestFlash::new());
+            assert!(store.store(CONFIG_KEY, other_version, &other).is_ok());
+
+            assert_default(&Config::load(&mut store));
+
+            // The other firmware's configuration is still there for it
+            let mut buffer = [0; MAX_RECORD_LEN];
+            let Ok(Some((version, len))) = store.fetch(CONFIG_KEY, &mut buffer) else {
+                panic!("configuration record is gone");
+            };
+            assert_eq!(version, other_version);
+            assert_eq!(&buffer[..len], &other[..]);
+        }
+    }
+}
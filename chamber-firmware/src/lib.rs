@@ -1,34 +1,102 @@
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)]
 
 pub mod atlas;
+pub mod clock;
+pub mod config;
+pub mod light;
+#[cfg(feature = "sd-log")]
+pub mod sd_log;
 pub mod sensors;
 pub mod state;
+pub mod storage;
+pub mod telemetry;
 pub mod xbee;
 
-use core::sync::atomic::{AtomicUsize, Ordering};
 use defmt_brtt as _; // global logger
-
-use panic_probe as _;
+#[cfg(not(test))]
+use rtic_monotonics::systick::Systick;
+#[cfg(not(test))]
+use rtic_monotonics::Monotonic;
 
 // TODO(6) Import your HAL
 use stm32h7xx_hal as _; // memory layout
 
+// same panicking *behavior* as `panic-probe`, but the grow light is turned off first
+// so a crash can't leave it stuck on
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    light::force_off();
+    defmt::error!("{}", defmt::Display2Format(info));
+    cortex_m::asm::udf()
+}
+
 // same panicking *behavior* as `panic-probe` but doesn't print a panic message
 // this prevents the panic message being printed *twice* when `defmt::panic` is invoked
+#[cfg(not(test))]
 #[defmt::panic_handler]
-fn panic() -> ! {
+fn defmt_panic() -> ! {
+    light::force_off();
     cortex_m::asm::udf()
 }
 
-static COUNT: AtomicUsize = AtomicUsize::new(0);
-defmt::timestamp!("{=usize}", {
-    // NOTE(no-CAS) `timestamps` runs with interrupts disabled
-    let n = COUNT.load(Ordering::Relaxed);
-    COUNT.store(n + 1, Ordering::Relaxed);
-    n
+// UTC from the RTC once its time is known, otherwise the time since boot
+#[cfg(not(test))]
+defmt::timestamp!("{=u64:iso8601ms}", {
+    clock::now().map_or_else(
+        || u64::from(Systick::now().duration_since_epoch().to_millis()),
+        |now| now.millis(),
+    )
 });
 
+// The host tests have no clock to stamp their logs with
+#[cfg(test)]
+defmt::timestamp!("");
+
+// The logger takes a critical section, which cortex-m only provides on the target
+#[cfg(test)]
+mod host_critical_section {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct HostCriticalSection;
+    critical_section::set_impl!(HostCriticalSection);
+
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+
+    std::thread_local! {
+        static IN_SECTION: Cell<bool> = const { Cell::new(false) };
+    }
+
+    // SAFETY: Only one thread is in a critical section at a time, and nested sections are no-ops
+    unsafe impl critical_section::Impl for HostCriticalSection {
+        unsafe fn acquire() -> critical_section::RawRestoreState {
+            if IN_SECTION.get() {
+                return 1;
+            }
+
+            while LOCKED
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                std::thread::yield_now();
+            }
+
+            IN_SECTION.set(true);
+            0
+        }
+
+        unsafe fn release(nested: critical_section::RawRestoreState) {
+            if nested == 0 {
+                IN_SECTION.set(false);
+                LOCKED.store(false, Ordering::Release);
+            }
+        }
+    }
+}
+
 /// Terminates the application and makes `probe-run` exit with exit-code = 0
 pub fn exit() -> ! {
     loop {
@@ -0,0 +1,289 @@
+use heapless::HistoryBuffer;
+
+use crate::clock::Timestamp;
+
+/// Maximum number of steps in a [`HeatDerating`] rule.
+pub const MAX_DERATING_STEPS: usize = 4;
+
+/// Number of derating events kept by a [`HeatDerating`] rule.
+pub const DERATING_LOG_SIZE: usize = 16;
+
+/// Default steps, from the temperature the SF2000 drivers start to suffer at.
+pub const DEFAULT_DERATING_STEPS: &[DeratingStep] = &[
+    DeratingStep {
+        temperature: 30.0,
+        max_intensity: 75.0,
+    },
+    DeratingStep {
+        temperature: 33.0,
+        max_intensity: 50.0,
+    },
+    DeratingStep {
+        temperature: 36.0,
+        max_intensity: 0.0,
+    },
+];
+
+/// Default drop in temperature, in °C, below a step before it's lifted.
+pub const DEFAULT_DERATING_HYSTERESIS: f64 = 1.5;
+
+/// Caps the light intensity once the chamber temperature reaches a threshold.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct DeratingStep {
+    /// Chamber temperature in °C at which the step applies.
+    pub temperature: f64,
+    /// Highest intensity in % allowed while the step applies.
+    pub max_intensity: f64,
+}
+
+/// Thresholds of a [`HeatDerating`] rule, as they're configured.
+#[derive(Clone)]
+pub struct DeratingSettings {
+    steps: heapless::Vec<DeratingStep, MAX_DERATING_STEPS>,
+    /// Drop in temperature, in °C, below a step before it's lifted.
+    pub hysteresis: f64,
+}
+
+impl DeratingSettings {
+    /// Creates settings from steps in order of increasing temperature.
+    ///
+    /// Returns `None` if there are too many steps, they're out of order, or
+    /// the hysteresis is negative.
+    pub fn new(steps: &[DeratingStep], hysteresis: f64) -> Option<Self> {
+        let steps = heapless::Vec::from_slice(steps).ok()?;
+        let ordered = steps
+            .windows(2)
+            .all(|pair: &[DeratingStep]| pair[0].temperature < pair[1].temperature);
+
+        (ordered && hysteresis >= 0.0).then_some(Self { steps, hysteresis })
+    }
+
+    /// Returns the steps, in order of increasing temperature.
+    pub fn steps(&self) -> &[DeratingStep] {
+        &self.steps
+    }
+}
+
+impl Default for DeratingSettings {
+    fn default() -> Self {
+        Self {
+            steps: heapless::Vec::from_slice(DEFAULT_DERATING_STEPS).unwrap_or_default(),
+            hysteresis: DEFAULT_DERATING_HYSTERESIS,
+        }
+    }
+}
+
+/// A change in how far the light is derated.
+#[derive(Clone, Copy, defmt::Format)]
+pub enum DeratingChange {
+    /// The chamber got hotter, and the light was derated further.
+    Derated,
+    /// The chamber cooled down, and some or all of the derating was lifted.
+    Restored,
+}
+
+/// A recorded change in derating.
+#[derive(Clone, Copy)]
+pub struct DeratingEvent {
+    pub change: DeratingChange,
+    /// Number of steps that apply after the change, 0 if the light isn't derated.
+    pub level: usize,
+    /// Chamber temperature in °C that caused the change.
+    pub temperature: f64,
+    /// Highest intensity in % allowed after the change.
+    pub max_intensity: f64,
+    /// When the change happened, if the time was known.
+    pub at: Option<Timestamp>,
+}
+
+/// Derates the grow light in steps as the chamber heats up, such as when the AC fails.
+///
+/// Each step applies once the temperature reaches its threshold, and is only
+/// lifted once the temperature drops the hysteresis below it, so the light
+/// doesn't flicker between steps.
+pub struct HeatDerating {
+    settings: DeratingSettings,
+    /// Number of steps that currently apply.
+    level: usize,
+    log: HistoryBuffer<DeratingEvent, DERATING_LOG_SIZE>,
+}
+
+impl HeatDerating {
+    /// Creates a rule from steps in order of increasing temperature.
+    ///
+    /// Returns `None` if the steps or hysteresis aren't valid, see [`DeratingSettings::new`].
+    pub fn new(steps: &[DeratingStep], hysteresis: f64) -> Option<Self> {
+        DeratingSettings::new(steps, hysteresis).map(Self::from_settings)
+    }
+
+    /// Creates a rule from its settings, with the light not derated.
+    pub fn from_settings(settings: DeratingSettings) -> Self {
+        Self {
+            settings,
+            level: 0,
+            log: HistoryBuffer::new(),
+        }
+    }
+
+    /// Returns the rule's settings.
+    pub fn settings(&self) -> &DeratingSettings {
+        &self.settings
+    }
+
+    /// Returns the number of steps that currently apply.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Returns the highest intensity in % currently allowed.
+    pub fn max_intensity(&self) -> f64 {
+        match self.level {
+            0 => 100.0,
+            level => self.settings.steps[level - 1].max_intensity,
+        }
+    }
+
+    /// Caps `intensity` in % to what's currently allowed.
+    pub fn limit(&self, intensity: f64) -> f64 {
+        intensity.min(self.max_intensity())
+    }
+
+    /// Returns the recorded derating events, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &DeratingEvent> {
+        self.log.oldest_ordered()
+    }
+
+    /// Updates the derating from the chamber temperature in °C.
+    ///
+    /// Returns the event that was recorded, if the derating changed.
+    pub fn update(&mut self, temperature: f64, now: Option<Timestamp>) -> Option<DeratingEvent> {
+        let previous = self.level;
+        let steps = &self.settings.steps;
+
+        while self.level < steps.len() && temperature >= steps[self.level].temperature {
+            self.level += 1;
+        }
+
+        while self.level > 0
+            && temperature < steps[self.level - 1].temperature - self.settings.hysteresis
+        {
+            self.level -= 1;
+        }
+
+        let change = match self.level.cmp(&previous) {
+            core::cmp::Ordering::Greater => DeratingChange::Derated,
+            core::cmp::Ordering::Less => DeratingChange::Restored,
+            core::cmp::Ordering::Equal => return None,
+        };
+
+        let event = DeratingEvent {
+            change,
+            level: self.level,
+            temperature,
+            max_intensity: self.max_intensity(),
+            at: now,
+        };
+
+        self.log.write(event);
+        Some(event)
+    }
+}
+
+impl Default for HeatDerating {
+    fn default() -> Self {
+        Self::from_settings(DeratingSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(temperature: f64, max_intensity: f64) -> DeratingStep {
+        DeratingStep {
+            temperature,
+            max_intensity,
+        }
+    }
+
+    fn derating() -> HeatDerating {
+        HeatDerating::new(&[step(30.0, 75.0), step(33.0, 50.0), step(36.0, 0.0)], 1.5).unwrap()
+    }
+
+    #[test]
+    fn steps_must_be_in_order() {
+        assert!(HeatDerating::new(&[step(33.0, 50.0), step(30.0, 75.0)], 1.5).is_none());
+        assert!(HeatDerating::new(&[step(30.0, 75.0), step(30.0, 50.0)], 1.5).is_none());
+        assert!(HeatDerating::new(&[step(30.0, 75.0)], -1.0).is_none());
+        assert!(HeatDerating::new(&[], 0.0).is_some());
+    }
+
+    #[test]
+    fn derates_in_steps_as_the_chamber_heats_up() {
+        let mut derating = derating();
+        assert!(derating.update(25.0, None).is_none());
+        assert_eq!(derating.limit(100.0), 100.0);
+
+        let levels: std::vec::Vec<(usize, f64)> = [30.0, 32.0, 33.5, 40.0]
+            .into_iter()
+            .map(|temperature| {
+                derating.update(temperature, None);
+                (derating.level(), derating.limit(100.0))
+            })
+            .collect();
+        assert_eq!(levels, [(1, 75.0), (1, 75.0), (2, 50.0), (3, 0.0)]);
+
+        // Cooling down lifts the steps the chamber is well below, and
+        // intensities below the cap are left alone
+        derating.update(33.0, None);
+        assert_eq!(derating.limit(40.0), 40.0);
+        assert_eq!(derating.limit(60.0), 50.0);
+    }
+
+    #[test]
+    fn a_sudden_rise_skips_to_the_highest_step() {
+        let mut derating = derating();
+
+        let Some(event) = derating.update(37.0, None) else {
+            panic!("no event");
+        };
+        assert!(matches!(event.change, DeratingChange::Derated));
+        assert_eq!(event.level, 3);
+        assert_eq!(event.max_intensity, 0.0);
+        assert_eq!(derating.events().count(), 1);
+    }
+
+    #[test]
+    fn doesnt_flap_inside_the_hysteresis_band() {
+        let mut derating = derating();
+        assert!(derating.update(30.0, None).is_some());
+
+        // Hovering around the threshold keeps the step
+        for temperature in [29.9, 30.1, 28.6, 29.5, 28.5, 30.0] {
+            assert!(derating.update(temperature, None).is_none());
+            assert_eq!(derating.level(), 1);
+        }
+
+        // It's lifted once the chamber is the hysteresis below the threshold
+        let Some(event) = derating.update(28.4, None) else {
+            panic!("no event");
+        };
+        assert!(matches!(event.change, DeratingChange::Restored));
+        assert_eq!(derating.level(), 0);
+        assert_eq!(derating.max_intensity(), 100.0);
+        assert_eq!(derating.events().count(), 2);
+    }
+
+    #[test]
+    fn cooling_down_lifts_steps_one_band_at_a_time() {
+        let mut derating = derating();
+        derating.update(36.0, None);
+
+        derating.update(34.6, None);
+        assert_eq!(derating.level(), 3);
+        derating.update(34.4, None);
+        assert_eq!(derating.level(), 2);
+        derating.update(20.0, None);
+        assert_eq!(derating.level(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use core::cell::Cell;
+
+use cortex_m::interrupt::Mutex;
+use stm32h7xx_hal::hal::PwmPin;
+
+/// Full scale of the grow light's 0–10 V dimming input, in V.
+pub const MAX_DIMMING_VOLTAGE: f64 = 10.0;
+
+/// Maximum number of points in a [`DimmingCalibration`].
+pub const MAX_CALIBRATION_POINTS: usize = 12;
+
+/// Calibration of the Spider Farmer SF2000 driver.
+///
+/// The driver doesn't light below about 1 V, and its output flattens out
+/// towards the top of the range.
+pub const SF2000_CALIBRATION: &[DimmingPoint] = &[
+    DimmingPoint {
+        intensity: 0.0,
+        voltage: 0.0,
+    },
+    DimmingPoint {
+        intensity: 1.0,
+        voltage: 1.1,
+    },
+    DimmingPoint {
+        intensity: 25.0,
+        voltage: 3.2,
+    },
+    DimmingPoint {
+        intensity: 50.0,
+        voltage: 5.1,
+    },
+    DimmingPoint {
+        intensity: 75.0,
+        voltage: 7.0,
+    },
+    DimmingPoint {
+        intensity: 90.0,
+        voltage: 8.4,
+    },
+    DimmingPoint {
+        intensity: 100.0,
+        voltage: 10.0,
+    },
+];
+
+/// The dimming voltage, in V, that makes a grow light emit an intensity in %.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct DimmingPoint {
+    pub intensity: f64,
+    pub voltage: f64,
+}
+
+/// Maps light intensity onto dimming voltage, for drivers that don't respond linearly.
+///
+/// Intensities between points are interpolated linearly.
+pub struct DimmingCalibration {
+    points: heapless::Vec<DimmingPoint, MAX_CALIBRATION_POINTS>,
+}
+
+impl DimmingCalibration {
+    /// Creates a calibration from points in order of increasing intensity.
+    ///
+    /// Returns `None` if there are fewer than two points, too many points,
+    /// points out of order, or voltages outside the dimming range.
+    pub fn new(points: &[DimmingPoint]) -> Option<Self> {
+        let points = heapless::Vec::from_slice(points).ok()?;
+
+        let ordered = points
+            .windows(2)
+            .all(|pair: &[DimmingPoint]| pair[0].intensity < pair[1].intensity);
+        let in_range = points
+            .iter()
+            .all(|point| (0.0..=MAX_DIMMING_VOLTAGE).contains(&point.voltage));
+
+        (points.len() >= 2 && ordered && in_range).then_some(Self { points })
+    }
+
+    /// Returns the dimming voltage that produces `intensity`, in %.
+    ///
+    /// Intensities outside the calibrated range use the nearest point.
+    pub fn voltage(&self, intensity: f64) -> f64 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+
+        // A bad intensity turns the light down
+        if intensity.is_nan() || intensity <= first.intensity {
+            return first.voltage;
+        }
+        if intensity >= last.intensity {
+            return last.voltage;
+        }
+
+        self.points
+            .windows(2)
+            .find(|pair| intensity <= pair[1].intensity)
+            .map(|pair| {
+                let (low, high) = (pair[0], pair[1]);
+                let fraction = (intensity - low.intensity) / (high.intensity - low.intensity);
+
+                low.voltage + fraction * (high.voltage - low.voltage)
+            })
+            .unwrap_or(last.voltage)
+    }
+}
+
+impl Default for DimmingCalibration {
+    fn default() -> Self {
+        Self {
+            points: heapless::Vec::from_slice(SF2000_CALIBRATION).unwrap_or_default(),
+        }
+    }
+}
+
+/// Function that turns the grow light off without its driver.
+type ForceOffHook = Option<fn()>;
+
+/// Hook that turns the grow light off without its driver, such as from the panic handler.
+static FORCE_OFF: Mutex<Cell<ForceOffHook>> = Mutex::new(Cell::new(None));
+
+/// Registers the function [`force_off`] uses to turn the grow light off.
+///
+/// The function can't rely on the [`GrowLight`] driver, so it should drive
+/// the dimming output low through the registers directly.
+pub fn register_force_off(hook: fn()) {
+    cortex_m::interrupt::free(|cs| FORCE_OFF.borrow(cs).set(Some(hook)));
+}
+
+/// Turns the grow light off with the registered hook, if there is one.
+pub fn force_off() {
+    if let Some(hook) = cortex_m::interrupt::free(|cs| FORCE_OFF.borrow(cs).get()) {
+        hook();
+    }
+}
+
+/// Grow light dimmed through the 0–10 V input on the RJ11 port.
+///
+/// The PWM output is filtered and amplified to 0–10 V on the board, so the
+/// duty cycle sets the dimming voltage. The light is off until an intensity
+/// is set.
+pub struct GrowLight<P> {
+    pwm: P,
+    calibration: DimmingCalibration,
+    /// Current intensity in %.
+    intensity: f64,
+}
+
+impl<P: PwmPin<Duty = u16>> GrowLight<P> {
+    /// Takes over the PWM channel, turning the light off.
+    pub fn new(mut pwm: P, calibration: DimmingCalibration) -> Self {
+        pwm.set_duty(0);
+        pwm.enable();
+
+        Self {
+            pwm,
+            calibration,
+            intensity: 0.0,
+        }
+    }
+
+    /// Returns the current intensity in %.
+    pub fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    /// Sets the intensity in %, between 0 and 100.
+    pub fn set_intensity(&mut self, intensity: f64) {
+        let intensity = if intensity.is_nan() {
+            0.0
+        } else {
+            intensity.clamp(0.0, 100.0)
+        };
+
+        let voltage = self.calibration.voltage(intensity);
+        let duty = voltage / MAX_DIMMING_VOLTAGE * f64::from(self.pwm.get_max_duty());
+
+        self.pwm.set_duty(libm::round(duty) as u16);
+        self.intensity = intensity;
+    }
+
+    /// Turns the light off.
+    pub fn off(&mut self) {
+        self.pwm.set_duty(0);
+        self.intensity = 0.0;
+    }
+}
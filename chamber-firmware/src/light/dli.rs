@@ -0,0 +1,462 @@
+use super::{Photoperiod, TimeOfDay, SECONDS_PER_DAY};
+use crate::clock::Timestamp;
+use crate::storage::{Flash, RecordStore, StoreError};
+
+/// Key of the record holding the day's [`DliProgress`] in a [`RecordStore`].
+pub const DLI_PROGRESS_KEY: u8 = 3;
+
+/// Version of the layout the day's progress is stored in.
+pub const DLI_PROGRESS_VERSION: u8 = 1;
+
+/// Length of an encoded [`DliProgress`].
+const DLI_PROGRESS_LEN: usize = 24;
+
+/// PPFD of a Spider Farmer SF2000 at full intensity, in µmol/m²/s, at our canopy height.
+pub const SF2000_PPFD: f64 = 800.0;
+
+/// Step used to project the light still to come from a photoperiod, in seconds.
+const PROJECTION_STEP: u32 = 300;
+
+/// A daily light integral to aim for, by adapting the photoperiod.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct DliTarget {
+    /// Target daily light integral, in mol/m²/day.
+    pub dli: f64,
+    /// How long the lights may stay on past the photoperiod to reach the target, in seconds.
+    pub max_stretch: u32,
+}
+
+/// Accumulates the daily light integral (DLI) from the commanded intensity over time.
+///
+/// The PPFD is taken to be proportional to intensity, which the dimming
+/// calibration makes linear in light output.
+pub struct DliTracker {
+    /// PPFD at the canopy at full intensity, in µmol/m²/s.
+    pub ppfd_at_full: f64,
+    /// Time of day at which a new day's integral starts.
+    pub day_start: TimeOfDay,
+    pub target: Option<DliTarget>,
+    /// Integral so far today, in mol/m².
+    today: f64,
+    /// Integral of the last complete day, in mol/m².
+    previous_day: Option<f64>,
+    last_time: Option<TimeOfDay>,
+}
+
+impl DliTracker {
+    pub const fn new(ppfd_at_full: f64) -> Self {
+        Self {
+            ppfd_at_full,
+            day_start: TimeOfDay::MIDNIGHT,
+            target: None,
+            today: 0.0,
+            previous_day: None,
+            last_time: None,
+        }
+    }
+
+    /// Returns the integral so far today, in mol/m².
+    pub fn today(&self) -> f64 {
+        self.today
+    }
+
+    /// Returns the integral of the last complete day, in mol/m².
+    pub fn previous_day(&self) -> Option<f64> {
+        self.previous_day
+    }
+
+    /// Returns the number of the day `time` falls in, with days starting at
+    /// [`DliTracker::day_start`] local time.
+    pub fn day_number(&self, time: Timestamp, utc_offset: i32) -> i64 {
+        let seconds =
+            time.seconds() as i64 + i64::from(utc_offset) - i64::from(self.day_start.seconds());
+
+        seconds.div_euclid(i64::from(SECONDS_PER_DAY))
+    }
+
+    /// Returns the integrals so far, as of day number `day`.
+    pub fn progress(&self, day: i64) -> DliProgress {
+        DliProgress {
+            day,
+            today: self.today,
+            previous_day: self.previous_day,
+        }
+    }
+
+    /// Carries on from `progress` stored before a reboot, on day number `day`.
+    ///
+    /// The lights are off while the chamber is down, so a day's integral is
+    /// picked up where it was stored. Progress from the day before only
+    /// gives the previous day's integral, and older progress is ignored.
+    pub fn restore(&mut self, progress: DliProgress, day: i64) {
+        if progress.day == day {
+            self.today = progress.today;
+            self.previous_day = progress.previous_day;
+        } else if progress.day + 1 == day {
+            self.previous_day = Some(progress.today);
+        }
+    }
+
+    /// Returns the PPFD, in µmol/m²/s, at `intensity` in %.
+    pub fn ppfd(&self, intensity: f64) -> f64 {
+        self.ppfd_at_full * intensity.clamp(0.0, 100.0) / 100.0
+    }
+
+    /// Accumulates `seconds` of light at `intensity`, ending at `time`.
+    ///
+    /// Returns the completed day's integral when `time` starts a new day.
+    pub fn accumulate(&mut self, intensity: f64, seconds: u32, time: TimeOfDay) -> Option<f64> {
+        let since_day_start = time.seconds_since(self.day_start);
+        let new_day = self
+            .last_time
+            .is_some_and(|last| last.seconds_since(self.day_start) > since_day_start);
+        self.last_time = Some(time);
+
+        let ppfd = self.ppfd(intensity);
+        let dose = |seconds: u32| ppfd * f64::from(seconds) / 1_000_000.0;
+
+        if new_day {
+            // Split the interval at the start of the day
+            let after = since_day_start.min(seconds);
+            let completed = self.today + dose(seconds - after);
+
+            self.previous_day = Some(completed);
+            self.today = dose(after);
+
+            Some(completed)
+        } else {
+            self.today += dose(seconds);
+            None
+        }
+    }
+
+    /// Returns the intensity in % to run at `time`, adapting `photoperiod` towards the target.
+    ///
+    /// The rest of the day's photoperiod is dimmed, or brightened up to full
+    /// intensity, so the integral lands on the target. If the target hasn't
+    /// been reached when the photoperiod ends, the lights are kept on for up
+    /// to [`DliTarget::max_stretch`]. Without a target, the photoperiod is
+    /// followed as is.
+    pub fn adapt(&self, photoperiod: &Photoperiod, time: TimeOfDay) -> f64 {
+        let scheduled = photoperiod.intensity_at(time);
+
+        let Some(target) = self.target else {
+            return scheduled;
+        };
+
+        // Target dose still needed today, in mol/m²
+        let needed = target.dli - self.today;
+
+        if needed <= 0.0 {
+            return 0.0;
+        }
+
+        if scheduled <= 0.0 {
+            return self.stretch(photoperiod, time, target);
+        }
+
+        let remaining = self.projected_dose(photoperiod, time);
+
+        if remaining <= 0.0 {
+            return scheduled;
+        }
+
+        (scheduled * needed / remaining).clamp(0.0, 100.0)
+    }
+
+    /// Returns the intensity to keep the lights at after a segment ends, or 0.
+    fn stretch(&self, photoperiod: &Photoperiod, time: TimeOfDay, target: DliTarget) -> f64 {
+        let since_day_start = time.seconds_since(self.day_start);
+
+        // Only segments that ended today are stretched, never into the next day
+        photoperiod
+            .segments()
+            .iter()
+            .filter(|segment| {
+                let since_off = time.seconds_since(segment.off);
+
+                since_off < target.max_stretch && since_off < since_day_start
+            })
+            .map(|segment| segment.intensity)
+            .fold(0.0, f64::max)
+    }
+
+    /// Returns the dose the photoperiod delivers from `time` to the end of the day, in mol/m².
+    fn projected_dose(&self, photoperiod: &Photoperiod, time: TimeOfDay) -> f64 {
+        let until_day_end = SECONDS_PER_DAY - time.seconds_since(self.day_start);
+
+        (0..until_day_end)
+            .step_by(PROJECTION_STEP as usize)
+            .map(|offset| {
+                let step = PROJECTION_STEP.min(until_day_end - offset);
+                let intensity = photoperiod.intensity_at(time.add_seconds(offset));
+
+                self.ppfd(intensity) * f64::from(step) / 1_000_000.0
+            })
+            .sum()
+    }
+}
+
+/// The integrals of a day, stored so a reboot doesn't start the day over.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct DliProgress {
+    /// Number of the day, see [`DliTracker::day_number`].
+    pub day: i64,
+    /// Integral so far that day, in mol/m².
+    pub today: f64,
+    /// Integral of the day before, in mol/m².
+    pub previous_day: Option<f64>,
+}
+
+impl DliProgress {
+    /// Encodes the progress, with a missing previous day as NaN.
+    pub fn encode(&self) -> [u8; DLI_PROGRESS_LEN] {
+        let mut data = [0; DLI_PROGRESS_LEN];
+
+        data[..8].copy_from_slice(&self.day.to_le_bytes());
+        data[8..16].copy_from_slice(&self.today.to_le_bytes());
+        data[16..].copy_from_slice(&self.previous_day.unwrap_or(f64::NAN).to_le_bytes());
+
+        data
+    }
+
+    /// Decodes progress encoded by [`DliProgress::encode`], or returns `None` if it's malformed.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let data: &[u8; DLI_PROGRESS_LEN] = data.try_into().ok()?;
+        let day = i64::from_le_bytes(data[..8].try_into().ok()?);
+        let today = f64::from_le_bytes(data[8..16].try_into().ok()?);
+        let previous_day = f64::from_le_bytes(data[16..].try_into().ok()?);
+
+        if !today.is_finite() || today < 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            day,
+            today,
+            previous_day: previous_day.is_finite().then_some(previous_day),
+        })
+    }
+
+    /// Loads the progress from `store`, or returns `None` if none can be read.
+    pub fn load<F: Flash>(store: &mut RecordStore<F>) -> Option<Self> {
+        let mut buffer = [0; DLI_PROGRESS_LEN];
+
+        match store.fetch(DLI_PROGRESS_KEY, &mut buffer) {
+            Ok(Some((DLI_PROGRESS_VERSION, len))) => {
+                let progress = Self::decode(&buffer[..len]);
+
+                if progress.is_none() {
+                    defmt::warn!("[dli] Skipping corrupt light integral record.");
+                }
+
+                progress
+            }
+            Ok(Some((version, _))) => {
+                defmt::warn!(
+                    "[dli] Light integral record version {} isn't supported, ignoring it.",
+                    version
+                );
+                None
+            }
+            Ok(None) => None,
+            Err(error) => {
+                defmt::error!("[dli] Couldn't read light integral record: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Stores the progress in `store`, so it's kept across reboots.
+    pub fn save<F: Flash>(&self, store: &mut RecordStore<F>) -> Result<(), StoreError> {
+        store.store(DLI_PROGRESS_KEY, DLI_PROGRESS_VERSION, &self.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::RamFlash;
+
+    /// 2026-10-19T00:00:00Z.
+    const MIDNIGHT: u64 = 1_792_368_000_000;
+
+    fn at(hours: u32, minutes: u32) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    /// Lights on at full intensity from 06:00 to 18:00, without ramps, for 34.56 mol/m².
+    fn twelve_hours() -> Photoperiod {
+        Photoperiod::daily(at(6, 0), 12, 100.0, 0, 0)
+    }
+
+    fn tracker(dli: f64, max_stretch: u32) -> DliTracker {
+        let mut tracker = DliTracker::new(SF2000_PPFD);
+        tracker.target = Some(DliTarget { dli, max_stretch });
+        tracker
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn light_is_accumulated() {
+        let mut tracker = DliTracker::new(SF2000_PPFD);
+
+        // An hour at full intensity, then an hour at half
+        assert!(tracker.accumulate(100.0, 3600, at(7, 0)).is_none());
+        assert!(close(tracker.today(), 2.88));
+        assert!(tracker.accumulate(50.0, 3600, at(8, 0)).is_none());
+        assert!(close(tracker.today(), 4.32));
+
+        // Intensities out of range are clamped
+        assert!(tracker.accumulate(150.0, 3600, at(9, 0)).is_none());
+        assert!(close(tracker.today(), 7.2));
+        assert!(tracker.accumulate(-10.0, 3600, at(10, 0)).is_none());
+        assert!(close(tracker.today(), 7.2));
+        assert!(tracker.previous_day().is_none());
+    }
+
+    #[test]
+    fn a_new_day_splits_the_interval() {
+        let mut tracker = DliTracker::new(SF2000_PPFD);
+        tracker.day_start = at(6, 0);
+
+        assert!(tracker.accumulate(100.0, 3600, at(5, 0)).is_none());
+
+        // Half an hour before the day starts, half an hour after
+        let Some(completed) = tracker.accumulate(100.0, 3600, at(6, 30)) else {
+            panic!("no new day");
+        };
+        assert!(close(completed, 4.32));
+        assert!(tracker.previous_day() == Some(completed));
+        assert!(close(tracker.today(), 1.44));
+
+        // The rest of the day carries on
+        assert!(tracker.accumulate(100.0, 1800, at(7, 0)).is_none());
+        assert!(close(tracker.today(), 2.88));
+    }
+
+    #[test]
+    fn the_photoperiod_is_followed_without_a_target() {
+        let photoperiod = twelve_hours();
+        let tracker = DliTracker::new(SF2000_PPFD);
+
+        assert!(tracker.adapt(&photoperiod, at(12, 0)) == 100.0);
+        assert!(tracker.adapt(&photoperiod, at(18, 30)) == 0.0);
+    }
+
+    #[test]
+    fn the_photoperiod_is_dimmed_or_brightened_to_the_target() {
+        let photoperiod = twelve_hours();
+
+        // Half of the photoperiod's integral is reached at half intensity
+        assert!(close(tracker(17.28, 0).adapt(&photoperiod, at(6, 0)), 50.0));
+
+        // More than it delivers is capped at full intensity
+        assert!(tracker(50.0, 0).adapt(&photoperiod, at(6, 0)) == 100.0);
+
+        // Light already received is taken off what's still needed
+        let mut tracker = tracker(17.28, 0);
+        assert!(tracker.accumulate(50.0, 6 * 3600, at(12, 0)).is_none());
+        assert!(close(tracker.adapt(&photoperiod, at(12, 0)), 50.0));
+
+        // The lights are turned off once the target is reached
+        assert!(tracker.accumulate(50.0, 6 * 3600, at(17, 0)).is_none());
+        assert!(tracker.adapt(&photoperiod, at(17, 0)) == 0.0);
+    }
+
+    #[test]
+    fn the_photoperiod_is_stretched_to_reach_the_target() {
+        let photoperiod = twelve_hours();
+        let mut tracker = tracker(40.0, 3600);
+        assert!(tracker.accumulate(100.0, 12 * 3600, at(18, 0)).is_none());
+
+        // Kept on past the photoperiod, for up to the longest stretch
+        assert!(tracker.adapt(&photoperiod, at(18, 30)) == 100.0);
+        assert!(tracker.adapt(&photoperiod, at(19, 30)) == 0.0);
+
+        // Not stretched before the photoperiod starts
+        assert!(tracker.adapt(&photoperiod, at(5, 0)) == 0.0);
+    }
+
+    #[test]
+    fn stretches_stop_at_the_start_of_a_new_day() {
+        let photoperiod = Photoperiod::daily(at(12, 0), 12, 100.0, 0, 0);
+        let mut tracker = tracker(40.0, 3600);
+        tracker.day_start = at(0, 15);
+
+        assert!(tracker.adapt(&photoperiod, at(0, 10)) == 100.0);
+        assert!(tracker.adapt(&photoperiod, at(0, 20)) == 0.0);
+    }
+
+    #[test]
+    fn days_are_numbered_from_the_local_day_start() {
+        let mut tracker = DliTracker::new(SF2000_PPFD);
+        let midnight = Timestamp::from_millis(MIDNIGHT);
+        let day = tracker.day_number(midnight, 0);
+
+        assert!(tracker.day_number(midnight.add_millis(86_399_999), 0) == day);
+        assert!(tracker.day_number(midnight.add_millis(86_400_000), 0) == day + 1);
+
+        // Two hours ahead of UTC, local midnight is at 22:00 UTC
+        assert!(tracker.day_number(midnight.add_millis(22 * 3_600_000), 7200) == day + 1);
+
+        tracker.day_start = at(6, 0);
+        assert!(tracker.day_number(midnight.add_millis(5 * 3_600_000), 0) == day - 1);
+        assert!(tracker.day_number(midnight.add_millis(6 * 3_600_000), 0) == day);
+    }
+
+    #[test]
+    fn a_reboot_carries_on_with_the_day() {
+        let photoperiod = twelve_hours();
+        let mut before = tracker(17.28, 0);
+        assert!(before.accumulate(100.0, 3 * 3600, at(9, 0)).is_none());
+        let intensity = before.adapt(&photoperiod, at(9, 0));
+
+        let mut store = RecordStore::new(RamFlash::<{ 2 * 1024 }, 1024, 16>::new())
+            .ok()
+            .unwrap();
+        assert!(before.progress(20_000).save(&mut store).is_ok());
+        let Some(progress) = DliProgress::load(&mut store) else {
+            panic!("no progress");
+        };
+        assert!(progress == before.progress(20_000));
+
+        // The same day picks up where it was
+        let mut rebooted = tracker(17.28, 0);
+        rebooted.restore(progress, 20_000);
+        assert!(close(rebooted.today(), before.today()));
+        assert!(close(rebooted.adapt(&photoperiod, at(9, 0)), intensity));
+
+        // The next day only keeps it as the previous day's integral
+        let mut rebooted = tracker(17.28, 0);
+        rebooted.restore(progress, 20_001);
+        assert!(rebooted.today() == 0.0);
+        assert!(rebooted.previous_day() == Some(progress.today));
+
+        // Anything older is ignored
+        let mut rebooted = tracker(17.28, 0);
+        rebooted.restore(progress, 20_002);
+        assert!(rebooted.today() == 0.0);
+        assert!(rebooted.previous_day().is_none());
+    }
+
+    #[test]
+    fn malformed_progress_is_rejected() {
+        let progress = DliProgress {
+            day: -3,
+            today: 1.5,
+            previous_day: None,
+        };
+        let data = progress.encode();
+
+        assert!(DliProgress::decode(&data) == Some(progress));
+        assert!(DliProgress::decode(&data[1..]).is_none());
+
+        let mut negative = data;
+        negative[8..16].copy_from_slice(&(-1.0f64).to_le_bytes());
+        assert!(DliProgress::decode(&negative).is_none());
+    }
+}
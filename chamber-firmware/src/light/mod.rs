@@ -0,0 +1,9 @@
+mod derating;
+mod dimmer;
+mod dli;
+mod photoperiod;
+
+pub use derating::*;
+pub use dimmer::*;
+pub use dli::*;
+pub use photoperiod::*;
@@ -0,0 +1,300 @@
+/// Number of seconds in a day.
+pub const SECONDS_PER_DAY: u32 = 86_400;
+
+/// Maximum number of segments in a [`Photoperiod`].
+pub const MAX_PHOTOPERIOD_SEGMENTS: usize = 8;
+
+/// Time of day, in seconds since midnight.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, defmt::Format)]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    pub const MIDNIGHT: Self = Self(0);
+
+    /// Creates a time of day, returning `None` if it's out of range.
+    pub const fn new(hours: u32, minutes: u32, seconds: u32) -> Option<Self> {
+        if hours < 24 && minutes < 60 && seconds < 60 {
+            Some(Self(hours * 3600 + minutes * 60 + seconds))
+        } else {
+            None
+        }
+    }
+
+    /// Creates a time of day from seconds since midnight, wrapping past midnight.
+    pub const fn from_seconds(seconds: u32) -> Self {
+        Self(seconds % SECONDS_PER_DAY)
+    }
+
+    /// Creates the local time of day from seconds since midnight UTC.
+    ///
+    /// `utc_offset` is the local time's offset from UTC in seconds, positive east of Greenwich.
+    pub const fn from_utc(seconds: u32, utc_offset: i32) -> Self {
+        Self((seconds as i64 + utc_offset as i64).rem_euclid(SECONDS_PER_DAY as i64) as u32)
+    }
+
+    /// Returns the number of seconds since midnight.
+    pub const fn seconds(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the time of day `seconds` later, wrapping past midnight.
+    pub const fn add_seconds(&self, seconds: u32) -> Self {
+        Self(((self.0 as u64 + seconds as u64) % SECONDS_PER_DAY as u64) as u32)
+    }
+
+    /// Returns the number of seconds from `earlier` until this time, wrapping past midnight.
+    pub const fn seconds_since(&self, earlier: TimeOfDay) -> u32 {
+        (self.0 + SECONDS_PER_DAY - earlier.0) % SECONDS_PER_DAY
+    }
+}
+
+/// A period of the day during which the lights are on.
+///
+/// Segments whose off time is earlier than their on time run past midnight.
+/// Segments with equal on and off times run all day.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct PhotoperiodSegment {
+    pub on: TimeOfDay,
+    pub off: TimeOfDay,
+    /// Intensity in % once the sunrise ramp finishes.
+    pub intensity: f64,
+}
+
+impl PhotoperiodSegment {
+    /// Returns the length of the segment in seconds.
+    pub fn duration(&self) -> u32 {
+        match self.off.seconds_since(self.on) {
+            0 => SECONDS_PER_DAY,
+            duration => duration,
+        }
+    }
+}
+
+/// A daily light schedule, with sunrise and sunset ramps.
+///
+/// The intensity is computed from the time of day alone, so the lights come
+/// back at the right intensity after a reboot, even if an on or off time was
+/// missed. Times are local, see [`TimeOfDay::from_utc`].
+#[derive(Clone)]
+pub struct Photoperiod {
+    segments: heapless::Vec<PhotoperiodSegment, MAX_PHOTOPERIOD_SEGMENTS>,
+    /// Length of the ramp up at the start of each segment, in seconds.
+    pub sunrise: u32,
+    /// Length of the ramp down at the end of each segment, in seconds.
+    pub sunset: u32,
+}
+
+impl Photoperiod {
+    /// Creates a schedule from any number of segments.
+    ///
+    /// Returns `None` if there are more than [`MAX_PHOTOPERIOD_SEGMENTS`] segments.
+    pub fn new(segments: &[PhotoperiodSegment], sunrise: u32, sunset: u32) -> Option<Self> {
+        Some(Self {
+            segments: heapless::Vec::from_slice(segments).ok()?,
+            sunrise,
+            sunset,
+        })
+    }
+
+    /// Creates a schedule with the lights on for `hours` a day from `on`.
+    ///
+    /// The lights stay off if `hours` is 0, and on all day from 24 hours.
+    pub fn daily(on: TimeOfDay, hours: u32, intensity: f64, sunrise: u32, sunset: u32) -> Self {
+        let mut segments = heapless::Vec::new();
+
+        // A segment that turns off when it turns on runs all day
+        if hours > 0 {
+            let _ = segments.push(PhotoperiodSegment {
+                on,
+                off: on.add_seconds(hours.min(24) * 3600),
+                intensity,
+            });
+        }
+
+        Self {
+            segments,
+            sunrise,
+            sunset,
+        }
+    }
+
+    /// Creates an 18/6 vegetative schedule, on from 06:00 until midnight.
+    pub fn vegetative(intensity: f64, sunrise: u32, sunset: u32) -> Self {
+        Self::daily(
+            TimeOfDay::from_seconds(6 * 3600),
+            18,
+            intensity,
+            sunrise,
+            sunset,
+        )
+    }
+
+    /// Creates a 12/12 flowering schedule, on from 06:00 until 18:00.
+    pub fn flowering(intensity: f64, sunrise: u32, sunset: u32) -> Self {
+        Self::daily(
+            TimeOfDay::from_seconds(6 * 3600),
+            12,
+            intensity,
+            sunrise,
+            sunset,
+        )
+    }
+
+    /// Returns the segments of the schedule.
+    pub fn segments(&self) -> &[PhotoperiodSegment] {
+        &self.segments
+    }
+
+    /// Returns the intensity in % the lights should have at `time`.
+    ///
+    /// Where segments overlap, the brightest wins.
+    pub fn intensity_at(&self, time: TimeOfDay) -> f64 {
+        self.segments
+            .iter()
+            .map(|segment| self.segment_intensity(segment, time))
+            .fold(0.0, f64::max)
+    }
+
+    fn segment_intensity(&self, segment: &PhotoperiodSegment, time: TimeOfDay) -> f64 {
+        let duration = segment.duration();
+        let elapsed = time.seconds_since(segment.on);
+
+        if duration == SECONDS_PER_DAY {
+            // Lights that never turn off don't ramp
+            return segment.intensity;
+        }
+        if elapsed >= duration {
+            return 0.0;
+        }
+
+        let remaining = duration - elapsed;
+
+        // Ramps that overlap in a short segment meet in the middle
+        let rising = ramp_fraction(elapsed, self.sunrise);
+        let setting = ramp_fraction(remaining, self.sunset);
+
+        segment.intensity * rising.min(setting)
+    }
+}
+
+impl Default for Photoperiod {
+    fn default() -> Self {
+        Self::vegetative(100.0, 1800, 1800)
+    }
+}
+
+/// Returns how far through a ramp of `length` seconds `elapsed` seconds are, between 0 and 1.
+fn ramp_fraction(elapsed: u32, length: u32) -> f64 {
+    if elapsed >= length {
+        1.0
+    } else {
+        f64::from(elapsed) / f64::from(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: u32, minutes: u32) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    fn segment(on: TimeOfDay, off: TimeOfDay, intensity: f64) -> PhotoperiodSegment {
+        PhotoperiodSegment { on, off, intensity }
+    }
+
+    #[test]
+    fn times_of_day_wrap() {
+        assert!(TimeOfDay::new(24, 0, 0).is_none());
+        assert_eq!(at(23, 0).add_seconds(2 * 3600).seconds(), 3600);
+        assert_eq!(at(1, 0).seconds_since(at(23, 0)), 2 * 3600);
+
+        // UTC-5 is 19:00 the day before at midnight UTC
+        assert!(TimeOfDay::from_utc(0, -5 * 3600) == at(19, 0));
+        assert!(TimeOfDay::from_utc(23 * 3600, 2 * 3600) == at(1, 0));
+    }
+
+    #[test]
+    fn ramps_up_and_down() {
+        // 06:00 to 18:00 at 80%, with a 30 minute sunrise and an hour's sunset
+        let photoperiod = Photoperiod::flowering(80.0, 1800, 3600);
+
+        assert_eq!(photoperiod.intensity_at(at(5, 59)), 0.0);
+        assert_eq!(photoperiod.intensity_at(at(6, 0)), 0.0);
+        assert_eq!(photoperiod.intensity_at(at(6, 15)), 40.0);
+        assert_eq!(photoperiod.intensity_at(at(6, 30)), 80.0);
+        assert_eq!(photoperiod.intensity_at(at(12, 0)), 80.0);
+        assert_eq!(photoperiod.intensity_at(at(17, 0)), 80.0);
+        assert_eq!(photoperiod.intensity_at(at(17, 30)), 40.0);
+        assert_eq!(photoperiod.intensity_at(at(18, 0)), 0.0);
+        assert_eq!(photoperiod.intensity_at(at(23, 0)), 0.0);
+    }
+
+    #[test]
+    fn short_segments_ramp_to_the_middle() {
+        // An hour on, with hour long ramps that meet half way
+        let photoperiod =
+            Photoperiod::new(&[segment(at(12, 0), at(13, 0), 100.0)], 3600, 3600).unwrap();
+
+        assert_eq!(photoperiod.intensity_at(at(12, 15)), 25.0);
+        assert_eq!(photoperiod.intensity_at(at(12, 30)), 50.0);
+        assert_eq!(photoperiod.intensity_at(at(12, 45)), 25.0);
+    }
+
+    #[test]
+    fn segments_cross_midnight() {
+        // 18/6 from 18:00 until 12:00 the next day
+        let photoperiod = Photoperiod::daily(at(18, 0), 18, 100.0, 3600, 3600);
+
+        assert_eq!(photoperiod.intensity_at(at(17, 0)), 0.0);
+        assert_eq!(photoperiod.intensity_at(at(18, 30)), 50.0);
+        assert_eq!(photoperiod.intensity_at(at(23, 59)), 100.0);
+        assert_eq!(photoperiod.intensity_at(TimeOfDay::MIDNIGHT), 100.0);
+        assert_eq!(photoperiod.intensity_at(at(3, 0)), 100.0);
+        assert_eq!(photoperiod.intensity_at(at(11, 30)), 50.0);
+        assert_eq!(photoperiod.intensity_at(at(12, 0)), 0.0);
+    }
+
+    #[test]
+    fn whole_days_and_dark_days() {
+        let always = Photoperiod::daily(at(6, 0), 24, 60.0, 1800, 1800);
+        let never = Photoperiod::daily(at(6, 0), 0, 60.0, 1800, 1800);
+
+        for hour in 0..24 {
+            assert_eq!(always.intensity_at(at(hour, 0)), 60.0);
+            assert_eq!(never.intensity_at(at(hour, 0)), 0.0);
+        }
+    }
+
+    #[test]
+    fn brightest_segment_wins() {
+        let photoperiod = Photoperiod::new(
+            &[
+                segment(at(6, 0), at(18, 0), 50.0),
+                segment(at(10, 0), at(14, 0), 90.0),
+            ],
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(photoperiod.intensity_at(at(8, 0)), 50.0);
+        assert_eq!(photoperiod.intensity_at(at(12, 0)), 90.0);
+        assert_eq!(photoperiod.intensity_at(at(16, 0)), 50.0);
+        assert!(Photoperiod::new(&[segment(at(6, 0), at(7, 0), 1.0); 9], 0, 0).is_none());
+    }
+
+    #[test]
+    fn missed_transitions_are_caught_up_after_a_reboot() {
+        // 06:00 until midnight, so a fresh schedule is all a rebooted chamber has
+        let boot = || Photoperiod::vegetative(100.0, 1800, 1800);
+
+        // Booting after the on time and the sunrise, part way through the
+        // sunset, and after the off time
+        assert_eq!(boot().intensity_at(at(7, 0)), 100.0);
+        assert_eq!(boot().intensity_at(at(6, 15)), 50.0);
+        assert_eq!(boot().intensity_at(at(23, 45)), 50.0);
+        assert_eq!(boot().intensity_at(at(0, 30)), 0.0);
+    }
+}
@@ -0,0 +1,69 @@
+/// Size of a block of an SD card, the unit it's read and written in.
+pub const BLOCK_SIZE: usize = 512;
+
+/// A block of an SD card.
+pub type Block = [u8; BLOCK_SIZE];
+
+/// Ways in which accessing a block device can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BlockError {
+    /// The block is past the end of the device.
+    OutOfRange,
+    /// The device reported an error, or isn't there.
+    Device,
+}
+
+/// Storage that's read and written in blocks of [`BLOCK_SIZE`] bytes, such as an SD card.
+pub trait BlockDevice {
+    /// Returns the number of blocks on the device.
+    fn block_count(&self) -> u32;
+
+    /// Reads the block at index `block`.
+    fn read(&mut self, block: u32, buffer: &mut Block) -> Result<(), BlockError>;
+
+    /// Writes the block at index `block`.
+    fn write(&mut self, block: u32, data: &Block) -> Result<(), BlockError>;
+}
+
+/// A block device in RAM, such as for running the CSV log on a host.
+pub struct RamBlockDevice<'a> {
+    blocks: &'a mut [Block],
+}
+
+impl<'a> RamBlockDevice<'a> {
+    /// Creates a device that stores its blocks in `blocks`.
+    pub fn new(blocks: &'a mut [Block]) -> Self {
+        Self { blocks }
+    }
+
+    /// Returns the device's blocks, such as to inspect a volume written to it.
+    pub fn blocks(&self) -> &[Block] {
+        self.blocks
+    }
+}
+
+impl BlockDevice for RamBlockDevice<'_> {
+    fn block_count(&self) -> u32 {
+        self.blocks.len() as u32
+    }
+
+    fn read(&mut self, block: u32, buffer: &mut Block) -> Result<(), BlockError> {
+        let data = self
+            .blocks
+            .get(block as usize)
+            .ok_or(BlockError::OutOfRange)?;
+
+        buffer.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn write(&mut self, block: u32, data: &Block) -> Result<(), BlockError> {
+        let target = self
+            .blocks
+            .get_mut(block as usize)
+            .ok_or(BlockError::OutOfRange)?;
+
+        target.copy_from_slice(data);
+        Ok(())
+    }
+}
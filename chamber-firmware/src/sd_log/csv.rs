@@ -0,0 +1,320 @@
+use core::fmt::Write;
+
+use heapless::LinearMap;
+
+use super::{BlockDevice, FatError, FatVolume, File, ShortName};
+use crate::atlas::DeviceType;
+use crate::clock::DateTime;
+use crate::storage::LogRecord;
+
+/// First line of each CSV file.
+pub const CSV_HEADER: &str = "time,value,stable,synced\r\n";
+
+/// Largest number of sensors whose files are kept open.
+pub const MAX_CSV_SENSORS: usize = 8;
+
+/// A sensor's file for a day.
+struct DailyFile {
+    /// Day of the file, in days since the Unix epoch.
+    day: u64,
+    file: File,
+}
+
+/// Returns the name of the directory holding a day's files, such as `20261019`.
+pub fn day_dir_name(date: DateTime) -> heapless::String<12> {
+    let mut name = heapless::String::new();
+    let _ = write!(name, "{:04}{:02}{:02}", date.year, date.month, date.day);
+
+    name
+}
+
+/// Returns the name of a sensor's file, such as `HUM6F.CSV` for humidity at address 0x6F.
+pub fn sensor_file_name(device_type: DeviceType, address: u8) -> heapless::String<12> {
+    let mut name = heapless::String::new();
+    let _ = write!(name, "{}{:02X}.CSV", device_type.code(), address);
+
+    name
+}
+
+/// Formats a record as a line of CSV, with its time in ISO 8601 UTC.
+pub fn csv_line(record: &LogRecord) -> heapless::String<80> {
+    let date = record.at.date_time();
+    let mut line = heapless::String::new();
+
+    let _ = write!(
+        line,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z,{},{},{}\r\n",
+        date.year,
+        date.month,
+        date.day,
+        date.hour,
+        date.minute,
+        date.second,
+        date.millisecond,
+        record.value,
+        u8::from(record.stable),
+        u8::from(record.synced)
+    );
+
+    line
+}
+
+/// Readings in CSV files on a FAT32 volume, one per sensor per day.
+///
+/// Each UTC day has a directory, such as `20261019`, holding a file per
+/// sensor, such as `HUM6F.CSV`. A sensor's first record of a day creates its
+/// file, starting with [`CSV_HEADER`], and the previous day's file is left
+/// as it is. A file that already exists, such as after a reboot, is appended
+/// to.
+pub struct CsvLog<D> {
+    volume: FatVolume<D>,
+    /// Each sensor's current file, by address.
+    files: LinearMap<u8, DailyFile, MAX_CSV_SENSORS>,
+}
+
+impl<D: BlockDevice> CsvLog<D> {
+    pub fn new(volume: FatVolume<D>) -> Self {
+        Self {
+            volume,
+            files: LinearMap::new(),
+        }
+    }
+
+    /// Returns the volume the files are on.
+    pub fn into_inner(self) -> FatVolume<D> {
+        self.volume
+    }
+
+    /// Appends a sensor's record to its file for the record's day.
+    pub fn log(&mut self, device_type: DeviceType, record: &LogRecord) -> Result<(), FatError> {
+        let date = record.at.date_time();
+        let day = record.at.days();
+
+        let mut file = match self.files.get(&record.address) {
+            Some(daily) if daily.day == day => daily.file,
+            _ => self.open(device_type, record.address, date)?,
+        };
+
+        self.volume
+            .append(&mut file, csv_line(record).as_bytes(), date)?;
+
+        // Files of sensors that have gone quiet are dropped to make room
+        if self.files.len() == MAX_CSV_SENSORS && !self.files.contains_key(&record.address) {
+            self.files.clear();
+        }
+        let _ = self.files.insert(record.address, DailyFile { day, file });
+
+        Ok(())
+    }
+
+    /// Opens a sensor's file for the day of `date`, creating it with its header if needed.
+    fn open(
+        &mut self,
+        device_type: DeviceType,
+        address: u8,
+        date: DateTime,
+    ) -> Result<File, FatError> {
+        let dir_name = ShortName::new(&day_dir_name(date)).ok_or(FatError::InvalidName)?;
+        let file_name =
+            ShortName::new(&sensor_file_name(device_type, address)).ok_or(FatError::InvalidName)?;
+
+        let root = self.volume.root();
+        let dir = self.volume.open_dir(root, &dir_name, date)?;
+        let mut file = self.volume.open_file(dir, &file_name, date)?;
+
+        if file.size() == 0 {
+            self.volume.append(&mut file, CSV_HEADER.as_bytes(), date)?;
+        }
+
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::clock::Timestamp;
+    use crate::sd_log::fat::tests::{format, read_file};
+    use crate::sd_log::{Block, RamBlockDevice};
+
+    fn at(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Timestamp {
+        DateTime::new(year, month, day, hour, minute, second)
+            .and_then(|date| date.timestamp())
+            .unwrap()
+    }
+
+    fn record(at: Timestamp, address: u8, value: f32) -> LogRecord {
+        LogRecord {
+            at,
+            address,
+            value,
+            stable: true,
+            synced: false,
+        }
+    }
+
+    fn open(blocks: &mut [Block]) -> CsvLog<RamBlockDevice<'_>> {
+        let Ok(volume) = FatVolume::mount(RamBlockDevice::new(blocks)) else {
+            panic!("couldn't mount the volume");
+        };
+        CsvLog::new(volume)
+    }
+
+    /// Joins the header and `lines` the way they're expected in a file.
+    fn file_of(lines: &[&str]) -> Vec<u8> {
+        let mut file = Vec::from(CSV_HEADER.as_bytes());
+        for line in lines {
+            file.extend_from_slice(line.as_bytes());
+        }
+        file
+    }
+
+    #[test]
+    fn names() {
+        let date = DateTime::new(2026, 10, 9, 23, 59, 59).unwrap();
+
+        assert_eq!(day_dir_name(date).as_str(), "20261009");
+        assert_eq!(
+            sensor_file_name(DeviceType::Humidity, 0x6F).as_str(),
+            "HUM6F.CSV"
+        );
+        assert_eq!(
+            sensor_file_name(DeviceType::Oxygen, 0x6C).as_str(),
+            "O26C.CSV"
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let mut reading = record(at(2026, 10, 19, 12, 34, 56).add_millis(789), 0x6F, 21.5);
+        assert_eq!(
+            csv_line(&reading).as_str(),
+            "2026-10-19T12:34:56.789Z,21.5,1,0\r\n"
+        );
+
+        reading.stable = false;
+        reading.synced = true;
+        assert_eq!(
+            csv_line(&reading).as_str(),
+            "2026-10-19T12:34:56.789Z,21.5,0,1\r\n"
+        );
+    }
+
+    #[test]
+    fn files_per_sensor_start_with_the_header() {
+        let mut blocks = format(256);
+        let morning = at(2026, 10, 19, 8, 0, 0);
+
+        let mut log = open(&mut blocks);
+        for minute in 0..3 {
+            let now = morning.add_millis(minute * 60_000);
+            assert!(log
+                .log(DeviceType::Humidity, &record(now, 0x6F, 60.0))
+                .is_ok());
+            assert!(log
+                .log(DeviceType::Oxygen, &record(now, 0x6C, 20.9))
+                .is_ok());
+        }
+
+        assert_eq!(
+            read_file(&blocks, &["20261019", "HUM6F.CSV"]).unwrap(),
+            file_of(&[
+                "2026-10-19T08:00:00.000Z,60,1,0\r\n",
+                "2026-10-19T08:01:00.000Z,60,1,0\r\n",
+                "2026-10-19T08:02:00.000Z,60,1,0\r\n",
+            ])
+        );
+        assert_eq!(
+            read_file(&blocks, &["20261019", "O26C.CSV"]).unwrap(),
+            file_of(&[
+                "2026-10-19T08:00:00.000Z,20.9,1,0\r\n",
+                "2026-10-19T08:01:00.000Z,20.9,1,0\r\n",
+                "2026-10-19T08:02:00.000Z,20.9,1,0\r\n",
+            ])
+        );
+    }
+
+    #[test]
+    fn new_day_starts_new_files() {
+        let mut blocks = format(256);
+        let before_midnight = at(2026, 10, 19, 23, 59, 59);
+
+        let mut log = open(&mut blocks);
+        assert!(log
+            .log(DeviceType::Humidity, &record(before_midnight, 0x6F, 61.0))
+            .is_ok());
+        assert!(log
+            .log(
+                DeviceType::Humidity,
+                &record(before_midnight.add_millis(1000), 0x6F, 62.0)
+            )
+            .is_ok());
+
+        assert_eq!(
+            read_file(&blocks, &["20261019", "HUM6F.CSV"]).unwrap(),
+            file_of(&["2026-10-19T23:59:59.000Z,61,1,0\r\n"])
+        );
+        assert_eq!(
+            read_file(&blocks, &["20261020", "HUM6F.CSV"]).unwrap(),
+            file_of(&["2026-10-20T00:00:00.000Z,62,1,0\r\n"])
+        );
+    }
+
+    #[test]
+    fn reopened_files_are_appended_to() {
+        let mut blocks = format(256);
+        let morning = at(2026, 10, 19, 8, 0, 0);
+
+        let mut log = open(&mut blocks);
+        assert!(log
+            .log(DeviceType::Humidity, &record(morning, 0x6F, 60.0))
+            .is_ok());
+
+        // Such as after a reboot
+        let mut log = open(&mut blocks);
+        assert!(log
+            .log(
+                DeviceType::Humidity,
+                &record(morning.add_millis(60_000), 0x6F, 61.0)
+            )
+            .is_ok());
+
+        assert_eq!(
+            read_file(&blocks, &["20261019", "HUM6F.CSV"]).unwrap(),
+            file_of(&[
+                "2026-10-19T08:00:00.000Z,60,1,0\r\n",
+                "2026-10-19T08:01:00.000Z,61,1,0\r\n",
+            ])
+        );
+    }
+
+    #[test]
+    fn more_sensors_than_open_files() {
+        let mut blocks = format(256);
+        let morning = at(2026, 10, 19, 8, 0, 0);
+        let addresses = 0x60..0x60 + MAX_CSV_SENSORS as u8 + 2;
+
+        let mut log = open(&mut blocks);
+        for minute in 0..2 {
+            let now = morning.add_millis(minute * 60_000);
+            for address in addresses.clone() {
+                assert!(log
+                    .log(DeviceType::Humidity, &record(now, address, 60.0))
+                    .is_ok());
+            }
+        }
+
+        for address in addresses {
+            let file_name = sensor_file_name(DeviceType::Humidity, address);
+            assert_eq!(
+                read_file(&blocks, &["20261019", &file_name]).unwrap(),
+                file_of(&[
+                    "2026-10-19T08:00:00.000Z,60,1,0\r\n",
+                    "2026-10-19T08:01:00.000Z,60,1,0\r\n",
+                ])
+            );
+        }
+    }
+}
@@ -0,0 +1,916 @@
+use super::{Block, BlockDevice, BlockError, BLOCK_SIZE};
+use crate::clock::DateTime;
+
+/// Signature at the end of a boot sector or master boot record.
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Offset of the partition table in the master boot record.
+const PARTITION_TABLE: usize = 446;
+
+/// Partition types of FAT32 volumes, addressed by CHS and LBA.
+const FAT32_PARTITION_TYPES: [u8; 2] = [0x0B, 0x0C];
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+/// Value of a free count or next free cluster that tells the OS to work it out.
+const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// Bits of a FAT entry that hold the cluster. The top four are reserved.
+const FAT_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+/// FAT entry that ends a cluster chain.
+const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+
+/// Smallest FAT entry that ends a cluster chain.
+const MIN_END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+
+/// Number of the first cluster of the data region.
+const FIRST_CLUSTER: u32 = 2;
+
+const DIR_ENTRY_LEN: usize = 32;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// First byte of the entry after the last one in use in a directory.
+const ENTRY_END: u8 = 0x00;
+
+/// First byte of a deleted entry.
+const ENTRY_DELETED: u8 = 0xE5;
+
+/// Ways in which accessing a FAT32 volume can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum FatError {
+    Block(BlockError),
+    /// There's no FAT32 volume on the device, or it doesn't have 512-byte sectors.
+    NotFat32,
+    /// There are no free clusters left.
+    Full,
+    /// A cluster chain or directory entry doesn't make sense.
+    Corrupt,
+    /// The name isn't a valid 8.3 name.
+    InvalidName,
+    /// A file was opened as a directory, or a directory as a file.
+    WrongKind,
+}
+
+impl From<BlockError> for FatError {
+    fn from(error: BlockError) -> Self {
+        FatError::Block(error)
+    }
+}
+
+/// An 8.3 name as it's stored in a directory entry, such as `HUM6F   CSV`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ShortName([u8; 11]);
+
+impl ShortName {
+    /// Converts a name such as `hum6f.csv` to upper case.
+    ///
+    /// Returns `None` unless the name has up to 8 characters, optionally
+    /// followed by a dot and up to 3 more, all letters, digits, `_` or `-`.
+    pub fn new(name: &str) -> Option<Self> {
+        let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+        let valid = |part: &str, max_len: usize| {
+            part.len() <= max_len
+                && part
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')
+        };
+
+        if base.is_empty() || !valid(base, 8) || !valid(extension, 3) {
+            return None;
+        }
+
+        let mut short_name = [b' '; 11];
+        short_name[..base.len()].copy_from_slice(base.as_bytes());
+        short_name[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+        short_name.make_ascii_uppercase();
+
+        Some(Self(short_name))
+    }
+}
+
+/// A directory on a [`FatVolume`].
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Dir {
+    /// First cluster of the directory.
+    cluster: u32,
+}
+
+/// A file on a [`FatVolume`], open for appending.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct File {
+    /// Block holding the file's directory entry.
+    entry_block: u32,
+    /// Offset of the file's directory entry in its block.
+    entry_offset: usize,
+    /// First cluster of the file, or 0 if it's empty.
+    first_cluster: u32,
+    /// Last cluster of the file, or 0 if it's empty.
+    last_cluster: u32,
+    size: u32,
+}
+
+impl File {
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Where a name was, or can be, found in a directory.
+enum Lookup {
+    Found {
+        block: u32,
+        offset: usize,
+        entry: [u8; DIR_ENTRY_LEN],
+    },
+    /// The name isn't in the directory, but it has a free entry.
+    Free { block: u32, offset: usize },
+    /// The name isn't in the directory, and it has no free entries.
+    Full { last_cluster: u32 },
+}
+
+/// A FAT32 volume, on its own or in the first FAT32 partition of a device.
+///
+/// Only what's needed to append to files is supported: creating directories
+/// and files with 8.3 names, and appending to files. Files are never
+/// deleted or truncated, and long file names are left alone.
+///
+/// The free cluster count in the FSInfo sector is marked unknown when the
+/// volume is mounted, so the OS counts the free clusters itself.
+pub struct FatVolume<D> {
+    device: D,
+    /// First block of the first FAT.
+    fat_start: u32,
+    /// Size of each FAT in blocks.
+    fat_size: u32,
+    fat_count: u8,
+    /// First block of cluster 2.
+    data_start: u32,
+    blocks_per_cluster: u32,
+    root_cluster: u32,
+    /// Number of clusters in the data region.
+    cluster_count: u32,
+    /// Cluster to start looking for a free cluster from.
+    next_free: u32,
+}
+
+impl<D: BlockDevice> FatVolume<D> {
+    /// Mounts the volume on `device`.
+    pub fn mount(mut device: D) -> Result<Self, FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        device.read(0, &mut block)?;
+
+        // A card is either formatted whole, or partitioned
+        let volume_start = if is_fat32_boot_sector(&block) {
+            0
+        } else if block[510..] == BOOT_SIGNATURE {
+            let partition = (0..4)
+                .map(|index| &block[PARTITION_TABLE + index * 16..][..16])
+                .find(|entry| FAT32_PARTITION_TYPES.contains(&entry[4]))
+                .ok_or(FatError::NotFat32)?;
+            let start = read_u32(partition, 8);
+
+            device.read(start, &mut block)?;
+
+            if !is_fat32_boot_sector(&block) {
+                return Err(FatError::NotFat32);
+            }
+
+            start
+        } else {
+            return Err(FatError::NotFat32);
+        };
+
+        let blocks_per_cluster = u32::from(block[13]);
+        let reserved = u32::from(read_u16(&block, 14));
+        let fat_count = block[16];
+        let total_blocks = match read_u16(&block, 19) {
+            0 => read_u32(&block, 32),
+            total => u32::from(total),
+        };
+        let fat_size = read_u32(&block, 36);
+        let root_cluster = read_u32(&block, 44);
+        let fs_info = read_u16(&block, 48);
+
+        let system_blocks = reserved + u32::from(fat_count) * fat_size;
+        let cluster_count = (total_blocks.saturating_sub(system_blocks) / blocks_per_cluster)
+            .min(fat_size * (BLOCK_SIZE as u32 / 4) - FIRST_CLUSTER);
+
+        let mut volume = Self {
+            device,
+            fat_start: volume_start + reserved,
+            fat_size,
+            fat_count,
+            data_start: volume_start + system_blocks,
+            blocks_per_cluster,
+            root_cluster,
+            cluster_count,
+            next_free: FIRST_CLUSTER,
+        };
+
+        if !volume.is_cluster(root_cluster) {
+            return Err(FatError::NotFat32);
+        }
+
+        if fs_info != 0 && fs_info != 0xFFFF {
+            volume.invalidate_fs_info(volume_start + u32::from(fs_info))?;
+        }
+
+        Ok(volume)
+    }
+
+    /// Returns the device the volume is on.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> Dir {
+        Dir {
+            cluster: self.root_cluster,
+        }
+    }
+
+    /// Opens the directory `name` in `parent`, creating it if it doesn't exist.
+    pub fn open_dir(
+        &mut self,
+        parent: Dir,
+        name: &ShortName,
+        now: DateTime,
+    ) -> Result<Dir, FatError> {
+        if let Lookup::Found { entry, .. } = self.lookup(parent, name)? {
+            if entry[11] & ATTR_DIRECTORY == 0 {
+                return Err(FatError::WrongKind);
+            }
+
+            // `..` entries point at cluster 0 for the root
+            return match entry_cluster(&entry) {
+                0 => Ok(self.root()),
+                cluster if self.is_cluster(cluster) => Ok(Dir { cluster }),
+                _ => Err(FatError::Corrupt),
+            };
+        }
+
+        // The directory's cluster is set up before it's linked into its parent
+        let cluster = self.allocate_cluster(None)?;
+        self.zero_cluster(cluster)?;
+
+        let parent_cluster = if parent == self.root() {
+            0
+        } else {
+            parent.cluster
+        };
+
+        let mut block = [0; BLOCK_SIZE];
+        block[..DIR_ENTRY_LEN].copy_from_slice(&new_entry(
+            *b".          ",
+            ATTR_DIRECTORY,
+            cluster,
+            now,
+        ));
+        block[DIR_ENTRY_LEN..2 * DIR_ENTRY_LEN].copy_from_slice(&new_entry(
+            *b"..         ",
+            ATTR_DIRECTORY,
+            parent_cluster,
+            now,
+        ));
+        self.device.write(self.cluster_block(cluster), &block)?;
+
+        self.insert_entry(parent, new_entry(name.0, ATTR_DIRECTORY, cluster, now))?;
+
+        Ok(Dir { cluster })
+    }
+
+    /// Opens the file `name` in `dir` for appending, creating it if it doesn't exist.
+    pub fn open_file(
+        &mut self,
+        dir: Dir,
+        name: &ShortName,
+        now: DateTime,
+    ) -> Result<File, FatError> {
+        match self.lookup(dir, name)? {
+            Lookup::Found {
+                block,
+                offset,
+                entry,
+            } => {
+                if entry[11] & ATTR_DIRECTORY != 0 {
+                    return Err(FatError::WrongKind);
+                }
+
+                let first_cluster = entry_cluster(&entry);
+                let mut last_cluster = first_cluster;
+
+                if first_cluster != 0 {
+                    if !self.is_cluster(first_cluster) {
+                        return Err(FatError::Corrupt);
+                    }
+
+                    // A chain can't be longer than the number of clusters
+                    let mut remaining = self.cluster_count;
+
+                    while let Some(next) = self.next_cluster(last_cluster)? {
+                        remaining = remaining.checked_sub(1).ok_or(FatError::Corrupt)?;
+                        last_cluster = next;
+                    }
+                }
+
+                Ok(File {
+                    entry_block: block,
+                    entry_offset: offset,
+                    first_cluster,
+                    last_cluster,
+                    size: read_u32(&entry, 28),
+                })
+            }
+            _ => {
+                let (entry_block, entry_offset) =
+                    self.insert_entry(dir, new_entry(name.0, ATTR_ARCHIVE, 0, now))?;
+
+                Ok(File {
+                    entry_block,
+                    entry_offset,
+                    first_cluster: 0,
+                    last_cluster: 0,
+                    size: 0,
+                })
+            }
+        }
+    }
+
+    /// Appends `data` to `file`, then updates its size and modification time.
+    pub fn append(&mut self, file: &mut File, data: &[u8], now: DateTime) -> Result<(), FatError> {
+        let cluster_size = self.blocks_per_cluster * BLOCK_SIZE as u32;
+        let mut data = data;
+
+        while !data.is_empty() {
+            let offset_in_cluster = file.size % cluster_size;
+
+            if file.first_cluster == 0 {
+                let cluster = self.allocate_cluster(None)?;
+                file.first_cluster = cluster;
+                file.last_cluster = cluster;
+            } else if offset_in_cluster == 0 && file.size > 0 {
+                file.last_cluster = self.allocate_cluster(Some(file.last_cluster))?;
+            }
+
+            let block_index =
+                self.cluster_block(file.last_cluster) + offset_in_cluster / BLOCK_SIZE as u32;
+            let offset = file.size as usize % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - offset).min(data.len());
+
+            // Past the end of the file, the block holds nothing worth keeping
+            let mut block = [0; BLOCK_SIZE];
+            if offset > 0 {
+                self.device.read(block_index, &mut block)?;
+            }
+
+            block[offset..offset + len].copy_from_slice(&data[..len]);
+            self.device.write(block_index, &block)?;
+
+            file.size += len as u32;
+            data = &data[len..];
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        self.device.read(file.entry_block, &mut block)?;
+
+        let entry = &mut block[file.entry_offset..file.entry_offset + DIR_ENTRY_LEN];
+        let (date, time) = fat_date_time(now);
+        entry[18..20].copy_from_slice(&date.to_le_bytes());
+        entry[20..22].copy_from_slice(&((file.first_cluster >> 16) as u16).to_le_bytes());
+        entry[22..24].copy_from_slice(&time.to_le_bytes());
+        entry[24..26].copy_from_slice(&date.to_le_bytes());
+        entry[26..28].copy_from_slice(&(file.first_cluster as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&file.size.to_le_bytes());
+
+        self.device.write(file.entry_block, &block)?;
+
+        Ok(())
+    }
+
+    fn is_cluster(&self, cluster: u32) -> bool {
+        (FIRST_CLUSTER..FIRST_CLUSTER + self.cluster_count).contains(&cluster)
+    }
+
+    fn cluster_block(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - FIRST_CLUSTER) * self.blocks_per_cluster
+    }
+
+    /// Returns the cluster after `cluster` in its chain, or `None` if it's the last.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        self.device
+            .read(self.fat_start + cluster / 128, &mut block)?;
+
+        match read_u32(&block, (cluster % 128) as usize * 4) & FAT_ENTRY_MASK {
+            next if next >= MIN_END_OF_CHAIN => Ok(None),
+            next if self.is_cluster(next) => Ok(Some(next)),
+            _ => Err(FatError::Corrupt),
+        }
+    }
+
+    /// Sets the FAT entry of `cluster` in every copy of the FAT.
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        let offset = (cluster % 128) as usize * 4;
+
+        for fat in 0..u32::from(self.fat_count) {
+            let block_index = self.fat_start + fat * self.fat_size + cluster / 128;
+            self.device.read(block_index, &mut block)?;
+
+            let reserved = read_u32(&block, offset) & !FAT_ENTRY_MASK;
+            block[offset..offset + 4].copy_from_slice(&(reserved | value).to_le_bytes());
+
+            self.device.write(block_index, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes a free cluster, linking it after `previous` if there is one.
+    fn allocate_cluster(&mut self, previous: Option<u32>) -> Result<u32, FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        let mut loaded = None;
+
+        for index in 0..self.cluster_count {
+            let cluster =
+                FIRST_CLUSTER + (self.next_free - FIRST_CLUSTER + index) % self.cluster_count;
+            let block_index = self.fat_start + cluster / 128;
+
+            if loaded != Some(block_index) {
+                self.device.read(block_index, &mut block)?;
+                loaded = Some(block_index);
+            }
+
+            if read_u32(&block, (cluster % 128) as usize * 4) & FAT_ENTRY_MASK != 0 {
+                continue;
+            }
+
+            // The chain is ended before it's linked, so it's never left dangling
+            self.set_fat_entry(cluster, END_OF_CHAIN)?;
+            if let Some(previous) = previous {
+                self.set_fat_entry(previous, cluster)?;
+            }
+
+            self.next_free = if cluster + 1 < FIRST_CLUSTER + self.cluster_count {
+                cluster + 1
+            } else {
+                FIRST_CLUSTER
+            };
+
+            return Ok(cluster);
+        }
+
+        Err(FatError::Full)
+    }
+
+    fn zero_cluster(&mut self, cluster: u32) -> Result<(), FatError> {
+        let block = [0; BLOCK_SIZE];
+        let start = self.cluster_block(cluster);
+
+        for block_index in start..start + self.blocks_per_cluster {
+            self.device.write(block_index, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// Looks for `name` in `dir`, noting the first free entry in case it isn't there.
+    fn lookup(&mut self, dir: Dir, name: &ShortName) -> Result<Lookup, FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        let mut free = None;
+        let mut cluster = dir.cluster;
+        let mut remaining = self.cluster_count;
+
+        loop {
+            let start = self.cluster_block(cluster);
+
+            for block_index in start..start + self.blocks_per_cluster {
+                self.device.read(block_index, &mut block)?;
+
+                for offset in (0..BLOCK_SIZE).step_by(DIR_ENTRY_LEN) {
+                    let entry = &block[offset..offset + DIR_ENTRY_LEN];
+                    let attributes = entry[11];
+
+                    match entry[0] {
+                        ENTRY_END => {
+                            let (block, offset) = free.unwrap_or((block_index, offset));
+                            return Ok(Lookup::Free { block, offset });
+                        }
+                        ENTRY_DELETED => {
+                            free.get_or_insert((block_index, offset));
+                        }
+                        _ if attributes == ATTR_LONG_NAME || attributes & ATTR_VOLUME_ID != 0 => {}
+                        _ if entry[..11] == name.0 => {
+                            let mut found = [0; DIR_ENTRY_LEN];
+                            found.copy_from_slice(entry);
+
+                            return Ok(Lookup::Found {
+                                block: block_index,
+                                offset,
+                                entry: found,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            match self.next_cluster(cluster)? {
+                Some(next) => {
+                    remaining = remaining.checked_sub(1).ok_or(FatError::Corrupt)?;
+                    cluster = next;
+                }
+                None => {
+                    return Ok(match free {
+                        Some((block, offset)) => Lookup::Free { block, offset },
+                        None => Lookup::Full {
+                            last_cluster: cluster,
+                        },
+                    })
+                }
+            }
+        }
+    }
+
+    /// Adds an entry to `dir`, growing it if it's full.
+    ///
+    /// Returns the block and offset the entry was written to.
+    fn insert_entry(
+        &mut self,
+        dir: Dir,
+        entry: [u8; DIR_ENTRY_LEN],
+    ) -> Result<(u32, usize), FatError> {
+        let name = ShortName(entry[..11].try_into().unwrap_or([b' '; 11]));
+
+        let (block_index, offset) = match self.lookup(dir, &name)? {
+            Lookup::Found { block, offset, .. } | Lookup::Free { block, offset } => (block, offset),
+            Lookup::Full { last_cluster } => {
+                let cluster = self.allocate_cluster(Some(last_cluster))?;
+                self.zero_cluster(cluster)?;
+
+                (self.cluster_block(cluster), 0)
+            }
+        };
+
+        let mut block = [0; BLOCK_SIZE];
+        self.device.read(block_index, &mut block)?;
+        block[offset..offset + DIR_ENTRY_LEN].copy_from_slice(&entry);
+        self.device.write(block_index, &block)?;
+
+        Ok((block_index, offset))
+    }
+
+    /// Marks the FSInfo sector's free cluster count and next free cluster as unknown.
+    fn invalidate_fs_info(&mut self, block_index: u32) -> Result<(), FatError> {
+        let mut block = [0; BLOCK_SIZE];
+        self.device.read(block_index, &mut block)?;
+
+        if read_u32(&block, 0) != FS_INFO_LEAD_SIGNATURE
+            || read_u32(&block, 484) != FS_INFO_STRUCT_SIGNATURE
+        {
+            return Ok(());
+        }
+
+        block[488..492].copy_from_slice(&FS_INFO_UNKNOWN.to_le_bytes());
+        block[492..496].copy_from_slice(&FS_INFO_UNKNOWN.to_le_bytes());
+        self.device.write(block_index, &block)?;
+
+        Ok(())
+    }
+}
+
+/// Returns whether `block` is the boot sector of a FAT32 volume with 512-byte sectors.
+fn is_fat32_boot_sector(block: &Block) -> bool {
+    let blocks_per_cluster = block[13];
+
+    block[510..] == BOOT_SIGNATURE
+        && usize::from(read_u16(block, 11)) == BLOCK_SIZE
+        && blocks_per_cluster.is_power_of_two()
+        && read_u16(block, 14) > 0
+        && block[16] > 0
+        // FAT12 and FAT16 have a fixed root directory and a 16-bit FAT size
+        && read_u16(block, 17) == 0
+        && read_u16(block, 22) == 0
+        && read_u32(block, 36) > 0
+}
+
+/// Returns a directory entry for a new file or directory.
+fn new_entry(name: [u8; 11], attributes: u8, cluster: u32, now: DateTime) -> [u8; DIR_ENTRY_LEN] {
+    let mut entry = [0; DIR_ENTRY_LEN];
+    let (date, time) = fat_date_time(now);
+
+    entry[..11].copy_from_slice(&name);
+    entry[11] = attributes;
+    entry[14..16].copy_from_slice(&time.to_le_bytes());
+    entry[16..18].copy_from_slice(&date.to_le_bytes());
+    entry[18..20].copy_from_slice(&date.to_le_bytes());
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[22..24].copy_from_slice(&time.to_le_bytes());
+    entry[24..26].copy_from_slice(&date.to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+
+    entry
+}
+
+fn entry_cluster(entry: &[u8; DIR_ENTRY_LEN]) -> u32 {
+    (u32::from(read_u16(entry, 20)) << 16) | u32::from(read_u16(entry, 26))
+}
+
+/// Returns the date and time of a directory entry, which count from 1980 in 2 s steps.
+fn fat_date_time(now: DateTime) -> (u16, u16) {
+    let date =
+        (now.year.saturating_sub(1980) << 9) | (u16::from(now.month) << 5) | u16::from(now.day);
+    let time =
+        (u16::from(now.hour) << 11) | (u16::from(now.minute) << 5) | u16::from(now.second / 2);
+
+    (date, time)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// A formatter and reader of FAT32 volumes, independent of [`FatVolume`], to check what it writes.
+#[cfg(test)]
+pub(super) mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::sd_log::RamBlockDevice;
+
+    const RESERVED_BLOCKS: u32 = 32;
+
+    /// Formats a FAT32 volume of `len` blocks, with one block per cluster.
+    pub fn format(len: u32) -> Vec<Block> {
+        let mut blocks = std::vec![[0; BLOCK_SIZE]; len as usize];
+        let fat_size = (len - RESERVED_BLOCKS).div_ceil(128);
+
+        let boot = &mut blocks[0];
+        boot[..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"CHAMBER ");
+        boot[11..13].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        boot[13] = 1;
+        boot[14..16].copy_from_slice(&(RESERVED_BLOCKS as u16).to_le_bytes());
+        boot[16] = 2;
+        boot[21] = 0xF8;
+        boot[32..36].copy_from_slice(&len.to_le_bytes());
+        boot[36..40].copy_from_slice(&fat_size.to_le_bytes());
+        boot[44..48].copy_from_slice(&FIRST_CLUSTER.to_le_bytes());
+        boot[48..50].copy_from_slice(&1u16.to_le_bytes());
+        boot[510..].copy_from_slice(&BOOT_SIGNATURE);
+
+        let fs_info = &mut blocks[1];
+        fs_info[..4].copy_from_slice(&FS_INFO_LEAD_SIGNATURE.to_le_bytes());
+        fs_info[484..488].copy_from_slice(&FS_INFO_STRUCT_SIGNATURE.to_le_bytes());
+        fs_info[488..492]
+            .copy_from_slice(&(len - RESERVED_BLOCKS - 2 * fat_size - 1).to_le_bytes());
+        fs_info[492..496].copy_from_slice(&3u32.to_le_bytes());
+        fs_info[510..].copy_from_slice(&BOOT_SIGNATURE);
+
+        // The media type, a reserved entry and the root directory's cluster
+        for fat in 0..2 {
+            let block = &mut blocks[(RESERVED_BLOCKS + fat * fat_size) as usize];
+            block[..4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+            block[4..8].copy_from_slice(&END_OF_CHAIN.to_le_bytes());
+            block[8..12].copy_from_slice(&END_OF_CHAIN.to_le_bytes());
+        }
+
+        blocks
+    }
+
+    /// Reads the file at `path` from a volume made by [`format`], or `None` if it isn't there.
+    pub fn read_file(blocks: &[Block], path: &[&str]) -> Option<Vec<u8>> {
+        let boot = &blocks[0];
+        let fat_start = usize::from(read_u16(boot, 14));
+        let data_start = fat_start + usize::from(boot[16]) * read_u32(boot, 36) as usize;
+
+        let read_chain = |first: u32| {
+            let mut data = Vec::new();
+            let mut cluster = first;
+
+            while (FIRST_CLUSTER..MIN_END_OF_CHAIN).contains(&cluster) {
+                data.extend_from_slice(&blocks[data_start + (cluster - FIRST_CLUSTER) as usize]);
+                cluster = read_u32(
+                    &blocks[fat_start + cluster as usize / 128],
+                    (cluster % 128) as usize * 4,
+                );
+            }
+
+            data
+        };
+
+        let mut cluster = read_u32(boot, 44);
+        let mut size = 0;
+
+        for name in path {
+            let name = ShortName::new(name)?;
+            let dir = read_chain(cluster);
+            let entry: &[u8; DIR_ENTRY_LEN] = dir
+                .as_chunks::<DIR_ENTRY_LEN>()
+                .0
+                .iter()
+                .take_while(|entry| entry[0] != ENTRY_END)
+                .find(|entry| entry[..11] == name.0)?;
+
+            cluster = entry_cluster(entry);
+            size = read_u32(entry, 28) as usize;
+        }
+
+        let mut data = read_chain(cluster);
+        data.truncate(size);
+        Some(data)
+    }
+
+    fn mount(blocks: &mut [Block]) -> FatVolume<RamBlockDevice<'_>> {
+        let Ok(volume) = FatVolume::mount(RamBlockDevice::new(blocks)) else {
+            panic!("couldn't mount the volume");
+        };
+        volume
+    }
+
+    fn name(name: &str) -> ShortName {
+        ShortName::new(name).unwrap()
+    }
+
+    fn now() -> DateTime {
+        DateTime::new(2026, 10, 19, 12, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn short_names() {
+        assert!(name("hum6f.csv") == name("HUM6F.CSV"));
+        assert!(name("20261019").0 == *b"20261019   ");
+        assert!(name("A_B-C.X").0 == *b"A_B-C   X  ");
+
+        assert!(ShortName::new("").is_none());
+        assert!(ShortName::new(".CSV").is_none());
+        assert!(ShortName::new("TOOLONGNAME").is_none());
+        assert!(ShortName::new("FILE.LONG").is_none());
+        assert!(ShortName::new("A B.CSV").is_none());
+        assert!(ShortName::new("A.B.C").is_none());
+    }
+
+    #[test]
+    fn mounts_a_whole_device() {
+        let mut blocks = format(256);
+        mount(&mut blocks);
+
+        // The OS is left to count the free clusters
+        assert_eq!(blocks[1][488..496], [0xFF; 8]);
+    }
+
+    #[test]
+    fn mounts_the_first_fat32_partition() {
+        let volume = format(256);
+        let mut blocks = std::vec![[0; BLOCK_SIZE]; 8];
+        blocks.extend(volume);
+
+        let partition = &mut blocks[0][PARTITION_TABLE + 16..PARTITION_TABLE + 32];
+        partition[4] = FAT32_PARTITION_TYPES[1];
+        partition[8..12].copy_from_slice(&8u32.to_le_bytes());
+        partition[12..16].copy_from_slice(&256u32.to_le_bytes());
+        blocks[0][510..].copy_from_slice(&BOOT_SIGNATURE);
+
+        let mut volume = mount(&mut blocks);
+        let mut file = volume
+            .open_file(volume.root(), &name("A.TXT"), now())
+            .ok()
+            .unwrap();
+        assert!(volume.append(&mut file, b"partitioned", now()).is_ok());
+
+        assert_eq!(read_file(&blocks[8..], &["A.TXT"]).unwrap(), b"partitioned");
+    }
+
+    #[test]
+    fn rejects_other_volumes() {
+        let mut blank = std::vec![[0; BLOCK_SIZE]; 64];
+        assert!(FatVolume::mount(RamBlockDevice::new(&mut blank))
+            .is_err_and(|error| error == FatError::NotFat32));
+
+        // FAT16 has a 16-bit FAT size
+        let mut fat16 = format(64);
+        fat16[0][22..24].copy_from_slice(&1u16.to_le_bytes());
+        assert!(FatVolume::mount(RamBlockDevice::new(&mut fat16))
+            .is_err_and(|error| error == FatError::NotFat32));
+    }
+
+    #[test]
+    fn appends_across_clusters() {
+        let mut blocks = format(256);
+        let data: Vec<u8> = (0..1500).map(|index| index as u8).collect();
+
+        let mut volume = mount(&mut blocks);
+        let dir = volume
+            .open_dir(volume.root(), &name("LOGS"), now())
+            .ok()
+            .unwrap();
+        let mut file = volume
+            .open_file(dir, &name("DATA.BIN"), now())
+            .ok()
+            .unwrap();
+        for chunk in data[..1000].chunks(100) {
+            assert!(volume.append(&mut file, chunk, now()).is_ok());
+        }
+
+        // A remounted volume carries on where the file ends
+        let mut volume = mount(&mut blocks);
+        let dir = volume
+            .open_dir(volume.root(), &name("LOGS"), now())
+            .ok()
+            .unwrap();
+        let mut file = volume
+            .open_file(dir, &name("DATA.BIN"), now())
+            .ok()
+            .unwrap();
+        assert_eq!(file.size(), 1000);
+        assert!(volume.append(&mut file, &data[1000..], now()).is_ok());
+
+        assert_eq!(read_file(&blocks, &["LOGS", "DATA.BIN"]).unwrap(), data);
+    }
+
+    #[test]
+    fn directories_grow() {
+        let mut blocks = format(256);
+        let mut volume = mount(&mut blocks);
+        let dir = volume
+            .open_dir(volume.root(), &name("MANY"), now())
+            .ok()
+            .unwrap();
+
+        // A cluster holds 16 entries, two of them `.` and `..`
+        for index in 0..40 {
+            let file_name = std::format!("F{index}.TXT");
+            let mut file = volume
+                .open_file(dir, &name(&file_name), now())
+                .ok()
+                .unwrap();
+            assert!(volume
+                .append(&mut file, file_name.as_bytes(), now())
+                .is_ok());
+        }
+
+        for index in 0..40 {
+            let file_name = std::format!("F{index}.TXT");
+            assert_eq!(
+                read_file(&blocks, &["MANY", &file_name]).unwrap(),
+                file_name.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn files_and_directories_are_kept_apart() {
+        let mut blocks = format(256);
+        let mut volume = mount(&mut blocks);
+        let root = volume.root();
+
+        let dir = volume.open_dir(root, &name("DIR"), now()).ok().unwrap();
+        volume.open_file(root, &name("FILE"), now()).ok().unwrap();
+
+        assert!(volume
+            .open_file(root, &name("DIR"), now())
+            .is_err_and(|error| error == FatError::WrongKind));
+        assert!(volume
+            .open_dir(root, &name("FILE"), now())
+            .is_err_and(|error| error == FatError::WrongKind));
+
+        assert!(volume
+            .open_dir(root, &name("DIR"), now())
+            .is_ok_and(|found| found == dir));
+    }
+
+    #[test]
+    fn full_volume() {
+        // 30 clusters, one of them the root directory's
+        let mut blocks = format(64);
+        let mut volume = mount(&mut blocks);
+        let mut file = volume
+            .open_file(volume.root(), &name("BIG"), now())
+            .ok()
+            .unwrap();
+
+        assert!(volume
+            .append(&mut file, &[0xA5; 29 * BLOCK_SIZE], now())
+            .is_ok());
+        assert!(volume
+            .append(&mut file, &[0xA5], now())
+            .is_err_and(|error| error == FatError::Full));
+    }
+}
@@ -0,0 +1,8 @@
+mod block;
+mod csv;
+mod fat;
+mod sdmmc;
+
+pub use block::*;
+pub use csv::*;
+pub use fat::*;
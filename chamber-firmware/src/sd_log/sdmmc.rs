@@ -0,0 +1,22 @@
+use stm32h7xx_hal::sdmmc::{SdCard, Sdmmc};
+use stm32h7xx_hal::stm32::SDMMC1;
+
+use super::{Block, BlockDevice, BlockError, BLOCK_SIZE};
+
+// The driver only supports high capacity cards, which are addressed in blocks
+impl BlockDevice for Sdmmc<SDMMC1, SdCard> {
+    fn block_count(&self) -> u32 {
+        self.card()
+            .map_or(0, |card| (card.size() / BLOCK_SIZE as u64) as u32)
+    }
+
+    fn read(&mut self, block: u32, buffer: &mut Block) -> Result<(), BlockError> {
+        self.read_block(block, buffer)
+            .map_err(|_| BlockError::Device)
+    }
+
+    fn write(&mut self, block: u32, data: &Block) -> Result<(), BlockError> {
+        self.write_block(block, data)
+            .map_err(|_| BlockError::Device)
+    }
+}
@@ -1,6 +1,320 @@
-use crate::atlas::{AtlasSensor, PendingOperation};
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use crate::atlas::{
+    AtlasSensor, DetectedDevice, DeviceHealth, DeviceType, FaultTracker, PendingAction,
+    ReadingFilter,
+};
+use crate::clock::Timestamp;
+
+/// Number of sample intervals after which a sensor's last reading is out of date.
+pub const STALE_AFTER_SAMPLES: u32 = 3;
+
+/// How the firmware reaches a sensor's device.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorLink {
+    /// The shared I2C bus, at the sensor's address.
+    I2c,
+    /// The dedicated UART port, which only has room for a single device.
+    ///
+    /// The sensor's address still identifies it to the rest of the firmware.
+    Uart,
+}
+
+/// A sensor along with the state the firmware keeps for it.
+pub struct AtlasSensorSlot {
+    pub sensor: &'static mut dyn AtlasSensor,
+    pub link: SensorLink,
+    /// Filter applied to the first value of the sensor's readings.
+    pub filter: ReadingFilter,
+    pub faults: FaultTracker,
+    pub health: DeviceHealth,
+    /// Time between samples of the sensor.
+    pub sample_interval: Duration<u32, 1, 1000>,
+    /// Whether the next reading is discarded, because the sensor just woke up.
+    pub discard_next: bool,
+    /// When the sensor's reading was last written to the data log.
+    pub logged_at: Option<Timestamp>,
+    /// When the sensor last gave a reading the filter accepted.
+    pub read_at: Option<Instant<u32, 1, 1000>>,
+}
+
+impl AtlasSensorSlot {
+    pub fn new(sensor: &'static mut dyn AtlasSensor) -> Self {
+        let filter = ReadingFilter::new(sensor.filter_config());
+        let sample_interval = sensor.sample_interval();
+
+        Self {
+            sensor,
+            link: SensorLink::I2c,
+            filter,
+            faults: FaultTracker::new(),
+            health: DeviceHealth::new(),
+            sample_interval,
+            discard_next: false,
+            logged_at: None,
+            read_at: None,
+        }
+    }
+
+    /// Returns whether the sensor's last reading can be relied on at `now`.
+    ///
+    /// It can't once the sensor is faulted, or after it's missed
+    /// [`STALE_AFTER_SAMPLES`] samples.
+    pub fn is_current(&self, now: Instant<u32, 1, 1000>) -> bool {
+        let max_age = self.sample_interval * STALE_AFTER_SAMPLES;
+
+        !self.faults.is_faulted()
+            && self.read_at.is_some_and(|read_at| {
+                now.checked_duration_since(read_at)
+                    .is_some_and(|age| age <= max_age)
+            })
+    }
+
+    /// Sets how the sensor's device is reached, for devices that aren't on the I2C bus.
+    pub fn with_link(mut self, link: SensorLink) -> Self {
+        self.link = link;
+        self
+    }
+}
 
 pub struct AtlasScientificSensors<const SIZE: usize> {
-    pub sensors: [&'static mut dyn AtlasSensor; SIZE],
-    pub current_operation: Option<PendingOperation>,
+    pub sensors: heapless::Vec<AtlasSensorSlot, SIZE>,
+}
+
+impl<const SIZE: usize> AtlasScientificSensors<SIZE> {
+    /// Builds the sensor set from the candidate sensors that were detected on the bus.
+    ///
+    /// Candidates are matched to the detected device of their type at their
+    /// address. Failing that, they're moved to a detected device of their type
+    /// that no other sensor uses. Candidates matched to the same device as an
+    /// earlier candidate are dropped.
+    ///
+    /// A warning is logged for each `expected` device type that wasn't detected
+    /// as often as expected, each detected device with unsupported firmware, and
+    /// each detected device without a candidate driver.
+    pub fn from_detected(
+        candidates: impl IntoIterator<Item = &'static mut dyn AtlasSensor>,
+        detected: &[DetectedDevice],
+        expected: &[DeviceType],
+    ) -> Self {
+        let mut sensors: heapless::Vec<AtlasSensorSlot, SIZE> = heapless::Vec::new();
+
+        for (index, device_type) in expected.iter().enumerate() {
+            // Each type is only checked once
+            if expected[..index].contains(device_type) {
+                continue;
+            }
+
+            let expected_count = expected.iter().filter(|t| *t == device_type).count();
+            let detected_count = detected
+                .iter()
+                .filter(|device| device.info.device_type == *device_type)
+                .count();
+
+            if detected_count == 0 {
+                defmt::warn!(
+                    "[atlas_sensors] Expected {} sensor wasn't detected.",
+                    device_type
+                );
+            } else if detected_count < expected_count {
+                // Boards of the same type leave the factory at the same address
+                defmt::warn!(
+                    "[atlas_sensors] Expected {} {} sensors, but only {} were detected. They may share an address.",
+                    expected_count,
+                    device_type,
+                    detected_count
+                );
+            }
+        }
+
+        for candidate in candidates {
+            let matches_type =
+                |device: &&DetectedDevice| device.info.device_type == candidate.device_type();
+
+            let device = detected
+                .iter()
+                .filter(matches_type)
+                .find(|device| u32::from(device.address) == candidate.address())
+                .or_else(|| {
+                    detected.iter().filter(matches_type).find(|device| {
+                        !sensors
+                            .iter()
+                            .any(|slot| slot.sensor.address() == u32::from(device.address))
+                    })
+                });
+
+            let Some(device) = device else {
+                continue;
+            };
+
+            // Conflicts are checked at the address the sensor ends up using
+            if let Some(slot) = sensors
+                .iter()
+                .find(|slot| slot.sensor.address() == u32::from(device.address))
+            {
+                defmt::warn!(
+                    "[atlas_sensors] {} and {} sensors both use address {}, ignoring the {} sensor.",
+                    slot.sensor.device_type(),
+                    candidate.device_type(),
+                    device.address,
+                    candidate.device_type()
+                );
+                continue;
+            }
+
+            if !device.info.is_supported() {
+                defmt::warn!(
+                    "[atlas_sensors] {} sensor at {} has unsupported firmware {}, {} or newer is required.",
+                    device.info.device_type,
+                    device.address,
+                    device.info.firmware,
+                    device.info.device_type.minimum_firmware()
+                );
+            }
+
+            if u32::from(device.address) != candidate.address() {
+                defmt::warn!(
+                    "[atlas_sensors] {} sensor detected at {}, but its driver uses {}. Using {}.",
+                    device.info.device_type,
+                    device.address,
+                    candidate.address(),
+                    device.address
+                );
+
+                candidate.set_address(u32::from(device.address));
+            }
+
+            if sensors.push(AtlasSensorSlot::new(candidate)).is_err() {
+                defmt::warn!(
+                    "[atlas_sensors] No room for {} sensor, at most {} sensors are supported.",
+                    device.info.device_type,
+                    SIZE
+                );
+            }
+        }
+
+        for device in detected {
+            if !sensors
+                .iter()
+                .any(|slot| slot.sensor.address() == u32::from(device.address))
+            {
+                defmt::warn!(
+                    "[atlas_sensors] No driver for {} device at {}.",
+                    device.info.device_type,
+                    device.address
+                );
+            }
+        }
+
+        Self { sensors }
+    }
+
+    /// Adds the sensor of the device on the UART port.
+    ///
+    /// Returns `false` if there's no room for it, or another sensor already uses its address.
+    pub fn add_uart_sensor(&mut self, sensor: &'static mut dyn AtlasSensor) -> bool {
+        if let Some(slot) = self
+            .sensors
+            .iter()
+            .find(|slot| slot.sensor.address() == sensor.address())
+        {
+            defmt::warn!(
+                "[atlas_sensors] {} and {} sensors both use address {}, ignoring the {} sensor on the UART port.",
+                slot.sensor.device_type(),
+                sensor.device_type(),
+                sensor.address(),
+                sensor.device_type()
+            );
+            return false;
+        }
+
+        let device_type = sensor.device_type();
+        let slot = AtlasSensorSlot::new(sensor).with_link(SensorLink::Uart);
+
+        if self.sensors.push(slot).is_err() {
+            defmt::warn!(
+                "[atlas_sensors] No room for {} sensor, at most {} sensors are supported.",
+                device_type,
+                SIZE
+            );
+            return false;
+        }
+
+        true
+    }
+
+    /// Sets the time between samples of the sensor at `address`.
+    ///
+    /// Returns `false` if there's no sensor at that address.
+    pub fn set_sample_interval(&mut self, address: u32, interval: Duration<u32, 1, 1000>) -> bool {
+        match self
+            .sensors
+            .iter_mut()
+            .find(|slot| slot.sensor.address() == address)
+        {
+            Some(slot) => {
+                slot.sample_interval = interval;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the index of the sensor whose pending action is due soonest.
+    ///
+    /// Actions without a deadline, such as startup commands, come first.
+    pub fn next_due(&self) -> Option<usize> {
+        self.sensors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, slot)| slot.sensor.pending_action().deadline())
+            .map(|(index, _)| index)
+    }
+
+    /// Returns whether every sensor has been set up, or has faulted trying.
+    pub fn initialised(&self) -> bool {
+        self.sensors.iter().all(|slot| {
+            slot.faults.is_faulted()
+                || !matches!(slot.sensor.pending_action(), PendingAction::Startup { .. })
+        })
+    }
+
+    /// Returns the number of sensors that have faulted since they last worked.
+    pub fn faulted(&self) -> usize {
+        self.sensors
+            .iter()
+            .filter(|slot| slot.faults.is_faulted())
+            .count()
+    }
+
+    /// Returns the latest solution temperature in °C, if a sensor with a current reading measures it.
+    ///
+    /// This is forwarded to temperature compensated sensors when they're
+    /// sampled. See [`AtlasSensorSlot::is_current`].
+    pub fn solution_temperature(&self, now: Instant<u32, 1, 1000>) -> Option<f64> {
+        self.sensors
+            .iter()
+            .filter(|slot| slot.is_current(now))
+            .find_map(|slot| slot.sensor.solution_temperature())
+    }
+
+    /// Returns the latest chamber air temperature in °C, if a sensor with a current reading measures it.
+    ///
+    /// See [`AtlasSensorSlot::is_current`].
+    pub fn air_temperature(&self, now: Instant<u32, 1, 1000>) -> Option<f64> {
+        self.sensors
+            .iter()
+            .filter(|slot| slot.is_current(now))
+            .find_map(|slot| slot.sensor.air_temperature())
+    }
+
+    /// Returns the latest vapour pressure deficit in kPa, if a sensor with a current reading gives it.
+    ///
+    /// See [`AtlasSensorSlot::is_current`].
+    pub fn vapour_pressure_deficit(&self, now: Instant<u32, 1, 1000>) -> Option<f64> {
+        self.sensors
+            .iter()
+            .filter(|slot| slot.is_current(now))
+            .find_map(|slot| slot.sensor.vapour_pressure_deficit())
+    }
 }
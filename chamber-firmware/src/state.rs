@@ -1 +1,266 @@
-pub struct DeviceState {}
+use heapless::HistoryBuffer;
+use rtic_monotonics::systick::fugit::Instant;
+
+use crate::xbee::message::MessageType;
+
+/// Length of an encoded [`StateReport`].
+pub const STATE_REPORT_LEN: usize = 7;
+
+/// Number of transitions kept by a [`DeviceStateMachine`].
+pub const STATE_LOG_SIZE: usize = 16;
+
+/// Stage of the device's lifecycle.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DeviceState {
+    /// The firmware is setting up the hardware.
+    Booting,
+    /// The hardware is being checked.
+    SelfTest,
+    /// The sensors are being set up.
+    SensorInit,
+    /// Waiting for the radio to join the coordinator's network.
+    JoiningRadio,
+    /// Everything is working.
+    Operational,
+    /// Some sensors are faulted, the rest of the chamber keeps running.
+    Degraded,
+    /// A grower is calibrating or servicing the chamber.
+    Maintenance,
+    /// The device can't run, and needs attention.
+    Fault,
+}
+
+impl DeviceState {
+    /// Returns the code the state is reported to the coordinator with.
+    pub const fn code(&self) -> u8 {
+        match self {
+            DeviceState::Booting => 0,
+            DeviceState::SelfTest => 1,
+            DeviceState::SensorInit => 2,
+            DeviceState::JoiningRadio => 3,
+            DeviceState::Operational => 4,
+            DeviceState::Degraded => 5,
+            DeviceState::Maintenance => 6,
+            DeviceState::Fault => 7,
+        }
+    }
+
+    /// Returns how the status LED blinks in the state.
+    pub const fn blink_pattern(&self) -> BlinkPattern {
+        match self {
+            DeviceState::Booting | DeviceState::SelfTest | DeviceState::SensorInit => {
+                BlinkPattern(&[250, 250])
+            }
+            DeviceState::JoiningRadio => BlinkPattern(&[500, 500]),
+            // A short heartbeat
+            DeviceState::Operational => BlinkPattern(&[50, 1950]),
+            // A double heartbeat
+            DeviceState::Degraded => BlinkPattern(&[50, 150, 50, 1750]),
+            DeviceState::Maintenance => BlinkPattern(&[1000, 0]),
+            DeviceState::Fault => BlinkPattern(&[100, 100]),
+        }
+    }
+}
+
+/// Times the status LED spends on and off in turn, in ms, starting on.
+#[derive(Clone, Copy)]
+pub struct BlinkPattern(pub &'static [u32]);
+
+/// Something that happened to the device, which may change its state.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DeviceEvent {
+    /// The hardware is set up.
+    Booted,
+    SelfTestPassed,
+    SelfTestFailed,
+    /// Every sensor has been set up, or has faulted.
+    SensorsInitialised,
+    RadioJoined,
+    RadioLost,
+    /// The number of faulted sensors changed.
+    SensorsFaulted {
+        count: u8,
+    },
+    MaintenanceStarted,
+    MaintenanceFinished,
+    /// Something went wrong that the device can't recover from.
+    Fatal,
+}
+
+/// A change of the device's state.
+#[derive(Clone, Copy)]
+pub struct StateTransition {
+    pub from: DeviceState,
+    pub to: DeviceState,
+    pub event: DeviceEvent,
+    pub at: Instant<u32, 1, 1000>,
+}
+
+/// Tracks the device's state through its lifecycle.
+///
+/// The device boots, tests itself, sets up its sensors and joins the radio
+/// network before it's operational. It's degraded while some sensors are
+/// faulted, and goes back to joining the network if the radio drops out.
+/// Maintenance can only start once the device is running. Fault is final,
+/// and the device has to be restarted.
+///
+/// Every transition is logged. Events that don't apply to the current state
+/// are logged and ignored.
+pub struct DeviceStateMachine {
+    state: DeviceState,
+    since: Instant<u32, 1, 1000>,
+    faulted_sensors: u8,
+    radio_joined: bool,
+    log: HistoryBuffer<StateTransition, STATE_LOG_SIZE>,
+}
+
+impl DeviceStateMachine {
+    /// Creates the state machine, booting since `now`.
+    pub const fn new(now: Instant<u32, 1, 1000>) -> Self {
+        Self {
+            state: DeviceState::Booting,
+            since: now,
+            faulted_sensors: 0,
+            radio_joined: false,
+            log: HistoryBuffer::new(),
+        }
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    /// Returns when the current state was entered.
+    pub fn since(&self) -> Instant<u32, 1, 1000> {
+        self.since
+    }
+
+    /// Returns the number of faulted sensors.
+    pub fn faulted_sensors(&self) -> u8 {
+        self.faulted_sensors
+    }
+
+    /// Returns the recorded transitions, oldest first.
+    pub fn transitions(&self) -> impl Iterator<Item = &StateTransition> {
+        self.log.oldest_ordered()
+    }
+
+    /// Handles an event at `now`, returning the transition if the state changed.
+    pub fn handle(
+        &mut self,
+        event: DeviceEvent,
+        now: Instant<u32, 1, 1000>,
+    ) -> Option<StateTransition> {
+        match event {
+            DeviceEvent::SensorsFaulted { count } => self.faulted_sensors = count,
+            DeviceEvent::RadioJoined => self.radio_joined = true,
+            DeviceEvent::RadioLost => self.radio_joined = false,
+            _ => {}
+        }
+
+        let Some(next) = self.next_state(event) else {
+            defmt::warn!("[device_state] Ignoring {} while in {}.", event, self.state);
+            return None;
+        };
+
+        if next == self.state {
+            return None;
+        }
+
+        let transition = StateTransition {
+            from: self.state,
+            to: next,
+            event,
+            at: now,
+        };
+
+        defmt::info!(
+            "[device_state] {} -> {} on {}.",
+            transition.from,
+            transition.to,
+            transition.event
+        );
+
+        self.state = next;
+        self.since = now;
+        self.log.write(transition);
+
+        Some(transition)
+    }
+
+    /// Returns the state `event` leads to, or `None` if it doesn't apply to the current state.
+    fn next_state(&self, event: DeviceEvent) -> Option<DeviceState> {
+        use DeviceEvent as Event;
+        use DeviceState as State;
+
+        // The state to run in, depending on the radio and whether any sensors are faulted
+        let running = if !self.radio_joined {
+            State::JoiningRadio
+        } else if self.faulted_sensors > 0 {
+            State::Degraded
+        } else {
+            State::Operational
+        };
+
+        Some(match (self.state, event) {
+            (State::Fault, _) => return None,
+            (_, Event::Fatal) => State::Fault,
+
+            (State::Booting, Event::Booted) => State::SelfTest,
+            (State::SelfTest, Event::SelfTestPassed) => State::SensorInit,
+            (State::SelfTest, Event::SelfTestFailed) => State::Fault,
+            (State::SensorInit, Event::SensorsInitialised) => running,
+            (State::JoiningRadio, Event::RadioJoined) => running,
+
+            (State::Operational | State::Degraded, Event::SensorsFaulted { .. }) => running,
+            (State::Operational | State::Degraded, Event::RadioLost) => State::JoiningRadio,
+            (State::Operational | State::Degraded, Event::MaintenanceStarted) => State::Maintenance,
+            (State::Maintenance, Event::MaintenanceFinished) => running,
+
+            // Faults and the radio are tracked in every state, and acted on once running
+            (_, Event::SensorsFaulted { .. } | Event::RadioJoined | Event::RadioLost) => self.state,
+
+            _ => return None,
+        })
+    }
+
+    /// Returns the report of the current state for the coordinator, at `now`.
+    pub fn report(&self, now: Instant<u32, 1, 1000>) -> StateReport {
+        StateReport {
+            state: self.state,
+            faulted_sensors: self.faulted_sensors,
+            seconds_in_state: now
+                .checked_duration_since(self.since)
+                .map_or(0, |elapsed| elapsed.to_secs()),
+        }
+    }
+}
+
+/// The device's state, as reported to the coordinator.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct StateReport {
+    pub state: DeviceState,
+    pub faulted_sensors: u8,
+    /// Time since the state was entered, in seconds.
+    pub seconds_in_state: u32,
+}
+
+impl StateReport {
+    /// Encodes the report as `S<state><faulted sensors><seconds in state>`.
+    ///
+    /// The time is big-endian.
+    pub fn encode(&self) -> [u8; STATE_REPORT_LEN] {
+        let seconds = self.seconds_in_state.to_be_bytes();
+
+        [
+            MessageType::StateReport as u8,
+            self.state.code(),
+            self.faulted_sensors,
+            seconds[0],
+            seconds[1],
+            seconds[2],
+            seconds[3],
+        ]
+    }
+}
@@ -0,0 +1,23 @@
+/// Reversed polynomial of CRC-32 (IEEE 802.3), as used by zlib and Ethernet.
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// Returns the CRC-32 of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Extends `crc`, the CRC-32 of earlier data, with `data`.
+///
+/// This lets the CRC of data that isn't contiguous be computed in parts.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    // Bitwise, as a table would take a kilobyte of flash
+    !data.iter().fold(!crc, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
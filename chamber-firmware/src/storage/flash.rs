@@ -0,0 +1,218 @@
+use stm32h7xx_hal::flash::LockedFlashBank;
+
+/// Size of a sector of the STM32H7B0's internal flash, the smallest area that can be erased.
+pub const INTERNAL_SECTOR_SIZE: usize = 0x2000;
+
+/// Size of a flash word of the STM32H7B0's internal flash, the smallest area that can be written.
+pub const INTERNAL_WRITE_SIZE: usize = 16;
+
+/// Ways in which accessing flash can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum FlashError {
+    /// The access goes past the end of the flash.
+    OutOfBounds,
+    /// The access isn't aligned to the write or erase size.
+    Misaligned,
+    /// Part of the area written wasn't erased first.
+    NotErased,
+    /// The flash controller reported an error.
+    Hardware,
+}
+
+/// NOR flash, which is erased in sectors to all ones, and written in words.
+///
+/// Offsets are from the start of the flash. Writes must be aligned to
+/// [`Flash::WRITE_SIZE`], and may only write words that are still erased.
+/// Erases must be aligned to [`Flash::ERASE_SIZE`].
+pub trait Flash {
+    /// Size of the smallest area that can be erased, in bytes.
+    const ERASE_SIZE: usize;
+    /// Size of the smallest area that can be written, in bytes.
+    const WRITE_SIZE: usize;
+
+    /// Returns the size of the flash in bytes.
+    fn capacity(&self) -> usize;
+
+    /// Reads `buffer.len()` bytes from `offset`.
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), FlashError>;
+
+    /// Writes `data` to `offset`, padding the last word with ones.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError>;
+
+    /// Erases `len` bytes from `offset`.
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), FlashError>;
+}
+
+/// Checks an access of `len` bytes at `offset` fits in `capacity` and is aligned to `alignment`.
+fn check_access(
+    offset: usize,
+    len: usize,
+    capacity: usize,
+    alignment: usize,
+) -> Result<(), FlashError> {
+    if offset.checked_add(len).is_none_or(|end| end > capacity) {
+        return Err(FlashError::OutOfBounds);
+    }
+    if !offset.is_multiple_of(alignment) {
+        return Err(FlashError::Misaligned);
+    }
+
+    Ok(())
+}
+
+/// Flash emulated in RAM, such as for running storage code on a host.
+///
+/// It behaves like NOR flash: writes fail unless the words written are
+/// erased, so code that works against it works against real flash.
+pub struct RamFlash<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> {
+    data: [u8; SIZE],
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
+    RamFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    /// Creates erased flash.
+    pub const fn new() -> Self {
+        Self { data: [0xFF; SIZE] }
+    }
+
+    /// Returns the contents of the flash, such as to corrupt them on purpose.
+    pub fn data_mut(&mut self) -> &mut [u8; SIZE] {
+        &mut self.data
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> Default
+    for RamFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> Flash
+    for RamFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    const ERASE_SIZE: usize = ERASE_SIZE;
+    const WRITE_SIZE: usize = WRITE_SIZE;
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), FlashError> {
+        check_access(offset, buffer.len(), SIZE, 1)?;
+
+        buffer.copy_from_slice(&self.data[offset..offset + buffer.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        let len = data.len().next_multiple_of(WRITE_SIZE);
+        check_access(offset, len, SIZE, WRITE_SIZE)?;
+
+        let target = &mut self.data[offset..offset + len];
+
+        if target.iter().any(|byte| *byte != 0xFF) {
+            return Err(FlashError::NotErased);
+        }
+
+        target[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), FlashError> {
+        check_access(offset, len, SIZE, ERASE_SIZE)?;
+
+        if !len.is_multiple_of(ERASE_SIZE) {
+            return Err(FlashError::Misaligned);
+        }
+
+        self.data[offset..offset + len].fill(0xFF);
+        Ok(())
+    }
+}
+
+/// A region of whole sectors of the STM32H7B0's internal flash.
+///
+/// The region must be left out of the firmware image, or erasing it erases
+/// the firmware.
+pub struct InternalFlash {
+    bank: LockedFlashBank,
+    /// Offset of the region from the start of the bank.
+    start: usize,
+    len: usize,
+}
+
+impl InternalFlash {
+    /// Takes over `len` bytes of the bank from `start`.
+    ///
+    /// Returns `None` if the region isn't made of whole sectors in the bank.
+    pub fn new(bank: LockedFlashBank, start: usize, len: usize) -> Option<Self> {
+        let aligned =
+            start.is_multiple_of(INTERNAL_SECTOR_SIZE) && len.is_multiple_of(INTERNAL_SECTOR_SIZE);
+        let in_bank = start.checked_add(len).is_some_and(|end| end <= bank.len());
+
+        (aligned && in_bank && len > 0).then_some(Self { bank, start, len })
+    }
+}
+
+impl Flash for InternalFlash {
+    const ERASE_SIZE: usize = INTERNAL_SECTOR_SIZE;
+    const WRITE_SIZE: usize = INTERNAL_WRITE_SIZE;
+
+    fn capacity(&self) -> usize {
+        self.len
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), FlashError> {
+        check_access(offset, buffer.len(), self.len, 1)?;
+
+        let start = self.start + offset;
+        buffer.copy_from_slice(&self.bank.read_all()[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        let len = data.len().next_multiple_of(INTERNAL_WRITE_SIZE);
+        check_access(offset, len, self.len, INTERNAL_WRITE_SIZE)?;
+
+        let start = self.start + offset;
+
+        // Programming a word twice corrupts its ECC
+        if self.bank.read_all()[start..start + len]
+            .iter()
+            .any(|byte| *byte != 0xFF)
+        {
+            return Err(FlashError::NotErased);
+        }
+
+        self.bank
+            .unlocked()
+            .program(start, data.iter())
+            .map_err(|_| FlashError::Hardware)
+    }
+
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), FlashError> {
+        check_access(offset, len, self.len, INTERNAL_SECTOR_SIZE)?;
+
+        if !len.is_multiple_of(INTERNAL_SECTOR_SIZE) {
+            return Err(FlashError::Misaligned);
+        }
+
+        let mut unlocked = self.bank.unlocked();
+
+        for sector_offset in
+            (self.start + offset..self.start + offset + len).step_by(INTERNAL_SECTOR_SIZE)
+        {
+            let sector = u8::try_from(sector_offset / INTERNAL_SECTOR_SIZE)
+                .map_err(|_| FlashError::OutOfBounds)?;
+
+            unlocked
+                .erase_sector(sector)
+                .map_err(|_| FlashError::Hardware)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,490 @@
+use super::{crc32, Flash, FlashError, StoreError};
+use crate::clock::Timestamp;
+
+/// Marks a sector that holds log records.
+const SECTOR_MAGIC: u32 = 0x444C_4731;
+
+/// Length of a sector header: magic, sequence and CRC, padded to a record.
+const SECTOR_HEADER_LEN: usize = LOG_RECORD_LEN;
+
+/// Length of an encoded [`LogRecord`], one flash word of the internal flash.
+pub const LOG_RECORD_LEN: usize = 16;
+
+/// Largest number of sectors a [`DataLog`] uses.
+pub const MAX_LOG_SECTORS: usize = 16;
+
+/// Set in a record's flags if the sensor's readings had settled.
+pub const LOG_FLAG_STABLE: u8 = 1 << 0;
+
+/// Set in a record's flags if the clock was synchronised when it was taken.
+pub const LOG_FLAG_SYNCED: u8 = 1 << 1;
+
+/// A logged measurement of a sensor.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct LogRecord {
+    pub at: Timestamp,
+    /// I2C address of the sensor.
+    pub address: u8,
+    pub value: f32,
+    /// Whether the sensor's readings had settled.
+    pub stable: bool,
+    /// Whether the clock had been synchronised with the coordinator.
+    pub synced: bool,
+}
+
+impl LogRecord {
+    /// Returns the record's [`LOG_FLAG_STABLE`] and [`LOG_FLAG_SYNCED`] flags.
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+
+        if self.stable {
+            flags |= LOG_FLAG_STABLE;
+        }
+        if self.synced {
+            flags |= LOG_FLAG_SYNCED;
+        }
+
+        flags
+    }
+
+    /// Encodes the record with its CRC, little-endian.
+    ///
+    /// The layout is the time in whole seconds (u32) and milliseconds (u16),
+    /// the address, the flags, the value (f32) and the CRC-32 of the rest.
+    pub fn encode(&self) -> [u8; LOG_RECORD_LEN] {
+        let mut data = [0; LOG_RECORD_LEN];
+
+        data[0..4].copy_from_slice(&(self.at.seconds() as u32).to_le_bytes());
+        data[4..6].copy_from_slice(&self.at.subsec_millis().to_le_bytes());
+        data[6] = self.address;
+        data[7] = self.flags();
+        data[8..12].copy_from_slice(&self.value.to_le_bytes());
+
+        let crc = crc32(&data[..12]);
+        data[12..].copy_from_slice(&crc.to_le_bytes());
+
+        data
+    }
+
+    /// Decodes a record encoded by [`LogRecord::encode`].
+    ///
+    /// Returns `None` if its CRC doesn't match, such as when it was torn by a power loss.
+    pub fn decode(data: &[u8; LOG_RECORD_LEN]) -> Option<Self> {
+        let word = |index: usize| {
+            [
+                data[index],
+                data[index + 1],
+                data[index + 2],
+                data[index + 3],
+            ]
+        };
+
+        if u32::from_le_bytes(word(12)) != crc32(&data[..12]) {
+            return None;
+        }
+
+        let seconds = u64::from(u32::from_le_bytes(word(0)));
+        let millis = u64::from(u16::from_le_bytes([data[4], data[5]]));
+
+        Some(Self {
+            at: Timestamp::from_seconds(seconds)?.add_millis(millis),
+            address: data[6],
+            value: f32::from_le_bytes(word(8)),
+            stable: data[7] & LOG_FLAG_STABLE != 0,
+            synced: data[7] & LOG_FLAG_SYNCED != 0,
+        })
+    }
+}
+
+/// Position of the next record to read from a [`DataLog`].
+#[derive(Clone, Copy, defmt::Format)]
+pub struct LogCursor {
+    /// Sequence number of the sector being read.
+    sequence: u32,
+    /// Offset of the next record from the start of the sector.
+    offset: usize,
+}
+
+/// Measurement records in a ring of flash sectors, overwriting the oldest once full.
+///
+/// Records are appended to the active sector. Once it's full, the sector
+/// after it, which holds the oldest records, is erased and becomes the active
+/// sector. Each sector's header holds a sequence number, so the order of the
+/// sectors is known after a reboot.
+///
+/// Each record carries a CRC, so a record torn by a power loss is skipped
+/// when the log is read, and the next record is written after it. A power
+/// loss while starting a sector leaves it without a valid header, so its
+/// records are ignored and it's erased again.
+pub struct DataLog<F> {
+    flash: F,
+    /// Sequence number of each sector, or `None` if it has no valid header.
+    sequences: heapless::Vec<Option<u32>, MAX_LOG_SECTORS>,
+    /// Index of the sector records are appended to, if any sector was written.
+    active: Option<usize>,
+    /// Offset in the active sector at which the next record is written.
+    end: usize,
+    /// Number of torn records found when the log was opened.
+    torn: usize,
+}
+
+impl<F: Flash> DataLog<F> {
+    /// Opens the log in `flash`, using up to [`MAX_LOG_SECTORS`] of its sectors.
+    pub fn new(flash: F) -> Result<Self, StoreError> {
+        if !LOG_RECORD_LEN.is_multiple_of(F::WRITE_SIZE) {
+            return Err(StoreError::Flash(FlashError::Misaligned));
+        }
+
+        let sector_count = (flash.capacity() / F::ERASE_SIZE).min(MAX_LOG_SECTORS);
+
+        if sector_count < 2 {
+            return Err(StoreError::TooSmall);
+        }
+
+        let mut log = Self {
+            flash,
+            sequences: heapless::Vec::new(),
+            active: None,
+            end: 0,
+            torn: 0,
+        };
+
+        for sector in 0..sector_count {
+            let sequence = log.sector_sequence(sector)?;
+            let _ = log.sequences.push(sequence);
+
+            if sequence.is_some_and(|sequence| {
+                log.active
+                    .is_none_or(|active| log.sequences[active] < Some(sequence))
+            }) {
+                log.active = Some(sector);
+            }
+        }
+
+        for sector in 0..sector_count {
+            if log.sequences[sector].is_none() {
+                continue;
+            }
+
+            let mut end = SECTOR_HEADER_LEN;
+
+            for offset in (SECTOR_HEADER_LEN..F::ERASE_SIZE).step_by(LOG_RECORD_LEN) {
+                match log.read_slot(sector, offset)? {
+                    Slot::Erased => {}
+                    Slot::Torn => {
+                        log.torn += 1;
+                        end = offset + LOG_RECORD_LEN;
+                    }
+                    Slot::Record(_) => end = offset + LOG_RECORD_LEN,
+                }
+            }
+
+            if log.active == Some(sector) {
+                log.end = end;
+            }
+        }
+
+        Ok(log)
+    }
+
+    /// Returns the flash the log is stored in.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Returns the number of torn records found when the log was opened.
+    pub fn torn(&self) -> usize {
+        self.torn
+    }
+
+    /// Appends a record, erasing the oldest records if the log is full.
+    pub fn append(&mut self, record: &LogRecord) -> Result<(), StoreError> {
+        let sector = match self.active {
+            Some(sector) if self.end + LOG_RECORD_LEN <= F::ERASE_SIZE => sector,
+            _ => self.start_sector()?,
+        };
+
+        self.flash
+            .write(sector * F::ERASE_SIZE + self.end, &record.encode())?;
+        self.end += LOG_RECORD_LEN;
+
+        Ok(())
+    }
+
+    /// Returns a cursor at the oldest record.
+    pub fn cursor(&self) -> LogCursor {
+        LogCursor {
+            sequence: self.sequences.iter().flatten().min().copied().unwrap_or(0),
+            offset: SECTOR_HEADER_LEN,
+        }
+    }
+
+    /// Returns a cursor after the newest record, to read only records appended later.
+    pub fn end_cursor(&self) -> LogCursor {
+        match self.active {
+            Some(sector) => LogCursor {
+                sequence: self.sequences[sector].unwrap_or(0),
+                offset: self.end,
+            },
+            None => self.cursor(),
+        }
+    }
+
+    /// Reads the record at `cursor`, oldest first, and moves the cursor past it.
+    ///
+    /// Returns `None` once there are no more records. The cursor can be kept
+    /// to read records appended later. If the records at the cursor were
+    /// overwritten, it's moved to the oldest record.
+    pub fn next(&mut self, cursor: &mut LogCursor) -> Result<Option<LogRecord>, StoreError> {
+        loop {
+            let Some((sector, sequence)) = self
+                .sequences
+                .iter()
+                .enumerate()
+                .filter_map(|(sector, sequence)| Some((sector, (*sequence)?)))
+                .filter(|(_, sequence)| *sequence >= cursor.sequence)
+                .min_by_key(|(_, sequence)| *sequence)
+            else {
+                return Ok(None);
+            };
+
+            if sequence != cursor.sequence {
+                *cursor = LogCursor {
+                    sequence,
+                    offset: SECTOR_HEADER_LEN,
+                };
+            }
+
+            let active = self.active == Some(sector);
+            let end = if active { self.end } else { F::ERASE_SIZE };
+
+            if cursor.offset + LOG_RECORD_LEN > end {
+                if active {
+                    return Ok(None);
+                }
+
+                *cursor = LogCursor {
+                    sequence: sequence.wrapping_add(1),
+                    offset: SECTOR_HEADER_LEN,
+                };
+                continue;
+            }
+
+            let slot = self.read_slot(sector, cursor.offset)?;
+            cursor.offset += LOG_RECORD_LEN;
+
+            if let Slot::Record(record) = slot {
+                return Ok(Some(record));
+            }
+        }
+    }
+
+    /// Calls `found` with each record taken from `from` until `to`, oldest first.
+    ///
+    /// Returns the number of records found.
+    pub fn for_each_between(
+        &mut self,
+        from: Timestamp,
+        to: Timestamp,
+        mut found: impl FnMut(&LogRecord),
+    ) -> Result<usize, StoreError> {
+        let mut cursor = self.cursor();
+        let mut count = 0;
+
+        // The clock can be set back, so every record is checked
+        while let Some(record) = self.next(&mut cursor)? {
+            if (from..to).contains(&record.at) {
+                found(&record);
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Erases the sector after the active one, and makes it the active sector.
+    fn start_sector(&mut self) -> Result<usize, StoreError> {
+        let (sector, sequence) = match self.active {
+            Some(active) => (
+                (active + 1) % self.sequences.len(),
+                self.sequences[active].map_or(0, |sequence| sequence.wrapping_add(1)),
+            ),
+            None => (0, 0),
+        };
+
+        // The sector's records are gone as soon as it starts being erased
+        self.sequences[sector] = None;
+        self.flash.erase(sector * F::ERASE_SIZE, F::ERASE_SIZE)?;
+
+        let mut header = [0xFF; SECTOR_HEADER_LEN];
+        header[..4].copy_from_slice(&SECTOR_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        let crc = crc32(&header[..8]);
+        header[8..12].copy_from_slice(&crc.to_le_bytes());
+
+        self.flash.write(sector * F::ERASE_SIZE, &header)?;
+
+        self.sequences[sector] = Some(sequence);
+        self.active = Some(sector);
+        self.end = SECTOR_HEADER_LEN;
+
+        Ok(sector)
+    }
+
+    /// Returns the sequence number of a sector, or `None` if it has no valid header.
+    fn sector_sequence(&mut self, sector: usize) -> Result<Option<u32>, StoreError> {
+        let mut header = [0; SECTOR_HEADER_LEN];
+        self.flash.read(sector * F::ERASE_SIZE, &mut header)?;
+
+        let word = |index: usize| {
+            u32::from_le_bytes([
+                header[index],
+                header[index + 1],
+                header[index + 2],
+                header[index + 3],
+            ])
+        };
+
+        let valid = word(0) == SECTOR_MAGIC && word(8) == crc32(&header[..8]);
+        Ok(valid.then(|| word(4)))
+    }
+
+    fn read_slot(&mut self, sector: usize, offset: usize) -> Result<Slot, StoreError> {
+        let mut data = [0; LOG_RECORD_LEN];
+        self.flash
+            .read(sector * F::ERASE_SIZE + offset, &mut data)?;
+
+        if data.iter().all(|byte| *byte == 0xFF) {
+            return Ok(Slot::Erased);
+        }
+
+        Ok(LogRecord::decode(&data).map_or(Slot::Torn, Slot::Record))
+    }
+}
+
+/// What a record's slot in a sector holds.
+enum Slot {
+    Erased,
+    /// A record whose CRC doesn't match.
+    Torn,
+    Record(LogRecord),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::RamFlash;
+
+    /// Three sectors, each with room for seven records after its header.
+    type TestFlash = RamFlash<384, 128, 16>;
+
+    fn open(flash: TestFlash) -> DataLog<TestFlash> {
+        let Ok(log) = DataLog::new(flash) else {
+            panic!("couldn't open the log");
+        };
+        log
+    }
+
+    fn record(seconds: u64) -> LogRecord {
+        LogRecord {
+            at: Timestamp::from_seconds(seconds).unwrap(),
+            address: 0x6F,
+            value: seconds as f32,
+            stable: true,
+            synced: false,
+        }
+    }
+
+    fn append(log: &mut DataLog<TestFlash>, seconds: u64) {
+        assert!(
+            log.append(&record(seconds)).is_ok(),
+            "couldn't append {seconds}"
+        );
+    }
+
+    /// Returns the times of the records in the log, oldest first.
+    fn times(log: &mut DataLog<TestFlash>) -> std::vec::Vec<u64> {
+        let mut cursor = log.cursor();
+        let mut times = std::vec::Vec::new();
+
+        while let Ok(Some(record)) = log.next(&mut cursor) {
+            times.push(record.at.seconds());
+        }
+
+        times
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut original = record(1_700_000_000);
+        original.at = original.at.add_millis(250);
+        original.synced = true;
+
+        let decoded = LogRecord::decode(&original.encode()).unwrap();
+
+        assert!(decoded.at == original.at);
+        assert_eq!(decoded.address, 0x6F);
+        assert_eq!(decoded.value, original.value);
+        assert_eq!(decoded.flags(), LOG_FLAG_STABLE | LOG_FLAG_SYNCED);
+    }
+
+    #[test]
+    fn reads_records_after_reopening() {
+        let mut log = open(TestFlash::new());
+        for seconds in 0..10 {
+            append(&mut log, seconds);
+        }
+
+        let mut log = open(log.into_inner());
+        assert_eq!(times(&mut log), (0..10).collect::<std::vec::Vec<_>>());
+        assert_eq!(log.torn(), 0);
+    }
+
+    #[test]
+    fn overwrites_the_oldest_records() {
+        let mut log = open(TestFlash::new());
+        for seconds in 0..30 {
+            append(&mut log, seconds);
+        }
+
+        // Starting a sector erases the oldest seven records, twice over
+        let mut log = open(log.into_inner());
+        assert_eq!(times(&mut log), (14..30).collect::<std::vec::Vec<_>>());
+    }
+
+    #[test]
+    fn torn_record_is_skipped() {
+        let mut log = open(TestFlash::new());
+        for seconds in 0..3 {
+            append(&mut log, seconds);
+        }
+
+        // A power loss partway through the second record
+        let mut flash = log.into_inner();
+        flash.data_mut()[SECTOR_HEADER_LEN + LOG_RECORD_LEN + 10] ^= 0xFF;
+
+        let mut log = open(flash);
+        assert_eq!(log.torn(), 1);
+
+        append(&mut log, 3);
+        assert_eq!(times(&mut log), [0, 2, 3]);
+    }
+
+    #[test]
+    fn records_between_times() {
+        let mut log = open(TestFlash::new());
+        for seconds in 0..10 {
+            append(&mut log, seconds);
+        }
+
+        let mut found = std::vec::Vec::new();
+        let count = log.for_each_between(
+            Timestamp::from_seconds(3).unwrap(),
+            Timestamp::from_seconds(6).unwrap(),
+            |record| found.push(record.at.seconds()),
+        );
+
+        assert!(count.is_ok_and(|count| count == 3));
+        assert_eq!(found, [3, 4, 5]);
+    }
+}
@@ -0,0 +1,11 @@
+mod crc;
+mod flash;
+mod log;
+mod partition;
+mod records;
+
+pub use crc::*;
+pub use flash::*;
+pub use log::*;
+pub use partition::*;
+pub use records::*;
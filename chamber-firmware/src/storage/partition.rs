@@ -0,0 +1,68 @@
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use super::{Flash, FlashError};
+
+/// Flash shared by several [`Partition`]s.
+pub type SharedFlash<F> = Mutex<RefCell<F>>;
+
+/// A region of whole erase sectors of flash that's shared with other partitions.
+///
+/// This lets stores that each own their flash, such as the configuration and
+/// the data log, use the one bank of internal flash. Each access borrows the
+/// flash in a critical section. Erasing and writing the bank stalls the CPU
+/// anyway, since the firmware runs from it.
+pub struct Partition<F: 'static> {
+    flash: &'static SharedFlash<F>,
+    /// Offset of the partition from the start of the flash.
+    start: usize,
+    len: usize,
+}
+
+impl<F: Flash> Partition<F> {
+    /// Takes over `len` bytes of `flash` from `start`.
+    ///
+    /// Returns `None` if the partition isn't made of whole sectors in the flash.
+    pub fn new(flash: &'static SharedFlash<F>, start: usize, len: usize) -> Option<Self> {
+        let capacity = cortex_m::interrupt::free(|cs| flash.borrow(cs).borrow().capacity());
+        let aligned = start.is_multiple_of(F::ERASE_SIZE) && len.is_multiple_of(F::ERASE_SIZE);
+        let in_flash = start.checked_add(len).is_some_and(|end| end <= capacity);
+
+        (aligned && in_flash && len > 0).then_some(Self { flash, start, len })
+    }
+
+    fn check_access(&self, offset: usize, len: usize) -> Result<usize, FlashError> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(self.start + offset),
+            _ => Err(FlashError::OutOfBounds),
+        }
+    }
+}
+
+impl<F: Flash> Flash for Partition<F> {
+    const ERASE_SIZE: usize = F::ERASE_SIZE;
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+
+    fn capacity(&self) -> usize {
+        self.len
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), FlashError> {
+        let offset = self.check_access(offset, buffer.len())?;
+
+        cortex_m::interrupt::free(|cs| self.flash.borrow(cs).borrow_mut().read(offset, buffer))
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        let offset = self.check_access(offset, data.len().next_multiple_of(F::WRITE_SIZE))?;
+
+        cortex_m::interrupt::free(|cs| self.flash.borrow(cs).borrow_mut().write(offset, data))
+    }
+
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), FlashError> {
+        let offset = self.check_access(offset, len)?;
+
+        cortex_m::interrupt::free(|cs| self.flash.borrow(cs).borrow_mut().erase(offset, len))
+    }
+}
@@ -0,0 +1,565 @@
+use heapless::LinearMap;
+
+use super::{crc32, crc32_update, Flash, FlashError};
+
+/// Marks a page that holds records.
+const PAGE_MAGIC: u32 = 0x5243_5331;
+
+/// Length of a page header: magic, sequence and CRC.
+const PAGE_HEADER_LEN: usize = 12;
+
+/// Length of a record header: key, version, data length and CRC.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// Largest amount of data in a record, in bytes.
+pub const MAX_RECORD_LEN: usize = 512;
+
+/// Largest number of keys a [`RecordStore`] holds.
+pub const MAX_RECORD_KEYS: usize = 16;
+
+/// Ways in which storing or fetching a record can fail.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum StoreError {
+    Flash(FlashError),
+    /// The flash is too small to hold two pages.
+    TooSmall,
+    /// The record's data is longer than [`MAX_RECORD_LEN`], or the buffer it's read into.
+    TooLarge,
+    /// The latest records of every key don't fit in a page.
+    Full,
+}
+
+impl From<FlashError> for StoreError {
+    fn from(error: FlashError) -> Self {
+        StoreError::Flash(error)
+    }
+}
+
+/// Where a valid record is in a page.
+#[derive(Clone, Copy)]
+struct RecordLocation {
+    /// Offset of the record header from the start of the page.
+    offset: usize,
+    version: u8,
+    len: usize,
+}
+
+/// Key/value records appended through flash, spreading the wear over its sectors.
+///
+/// The flash is split into pages of one erase sector each. Records are
+/// appended to the active page, and a newer record of a key replaces the
+/// older ones. Once the active page is full, the latest record of each key
+/// is copied to the next page, round-robin, which becomes the active page.
+/// Each sector is only erased once per trip through the flash.
+///
+/// Each record carries a CRC, so a record torn by a power loss is skipped
+/// and the previous record of its key is used instead. A page's header is
+/// only written once its records have all been copied, along with the
+/// record that didn't fit in the old page, so a power loss while moving to a
+/// new page leaves the old page active.
+pub struct RecordStore<F> {
+    flash: F,
+    /// Index and sequence number of the active page, if any page was written.
+    active: Option<(usize, u32)>,
+    /// Offset in the active page at which the next record is written.
+    end: usize,
+}
+
+impl<F: Flash> RecordStore<F> {
+    /// Opens the records in `flash`, which is used whole.
+    pub fn new(flash: F) -> Result<Self, StoreError> {
+        if flash.capacity() / F::ERASE_SIZE < 2 {
+            return Err(StoreError::TooSmall);
+        }
+
+        let mut store = Self {
+            flash,
+            active: None,
+            end: 0,
+        };
+
+        for page in 0..store.page_count() {
+            if let Some(sequence) = store.page_sequence(page)? {
+                if store.active.is_none_or(|(_, active)| sequence > active) {
+                    store.active = Some((page, sequence));
+                }
+            }
+        }
+
+        if let Some((page, _)) = store.active {
+            store.end = store.scan(page, |_, _| {})?;
+        }
+
+        Ok(store)
+    }
+
+    /// Returns the flash the records are stored in.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Reads the latest record of `key` into `buffer`.
+    ///
+    /// Returns the record's version and length, or `None` if there's no valid
+    /// record of the key.
+    pub fn fetch(&mut self, key: u8, buffer: &mut [u8]) -> Result<Option<(u8, usize)>, StoreError> {
+        let Some((page, _)) = self.active else {
+            return Ok(None);
+        };
+
+        let mut latest = None;
+        self.scan(page, |record_key, location| {
+            if record_key == key {
+                latest = Some(location);
+            }
+        })?;
+
+        let Some(location) = latest else {
+            return Ok(None);
+        };
+
+        let data = buffer.get_mut(..location.len).ok_or(StoreError::TooLarge)?;
+        self.flash.read(
+            self.page_offset(page) + location.offset + RECORD_HEADER_LEN,
+            data,
+        )?;
+
+        Ok(Some((location.version, location.len)))
+    }
+
+    /// Stores `data` as the latest record of `key`, at `version` of its layout.
+    pub fn store(&mut self, key: u8, version: u8, data: &[u8]) -> Result<(), StoreError> {
+        if data.len() > MAX_RECORD_LEN {
+            return Err(StoreError::TooLarge);
+        }
+
+        let mut record: heapless::Vec<u8, { RECORD_HEADER_LEN + MAX_RECORD_LEN }> =
+            heapless::Vec::new();
+        let mut header = [key, version, 0, 0];
+        header[2..4].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        let crc = crc32_update(crc32(&header), data);
+
+        let _ = record.extend_from_slice(&header);
+        let _ = record.extend_from_slice(&crc.to_le_bytes());
+        let _ = record.extend_from_slice(data);
+
+        match self.active {
+            Some((page, _)) if self.end + self.record_len(data.len()) <= F::ERASE_SIZE => {
+                self.flash
+                    .write(self.page_offset(page) + self.end, &record)?;
+                self.end += self.record_len(data.len());
+
+                Ok(())
+            }
+            Some((page, sequence)) => self.move_to_next_page(page, sequence, key, &record),
+            None => self.move_to_next_page(self.page_count() - 1, 0, key, &record),
+        }
+    }
+
+    fn page_count(&self) -> usize {
+        self.flash.capacity() / F::ERASE_SIZE
+    }
+
+    fn page_offset(&self, page: usize) -> usize {
+        page * F::ERASE_SIZE
+    }
+
+    /// Returns the offset of the first record in a page.
+    fn first_record(&self) -> usize {
+        PAGE_HEADER_LEN.next_multiple_of(F::WRITE_SIZE)
+    }
+
+    /// Returns the space a record with `len` bytes of data takes up.
+    fn record_len(&self, len: usize) -> usize {
+        (RECORD_HEADER_LEN + len).next_multiple_of(F::WRITE_SIZE)
+    }
+
+    /// Returns the sequence number of a page, or `None` if it has no valid header.
+    fn page_sequence(&mut self, page: usize) -> Result<Option<u32>, StoreError> {
+        let mut header = [0; PAGE_HEADER_LEN];
+        self.flash.read(self.page_offset(page), &mut header)?;
+
+        let word = |index: usize| {
+            u32::from_le_bytes([
+                header[index],
+                header[index + 1],
+                header[index + 2],
+                header[index + 3],
+            ])
+        };
+
+        let valid = word(0) == PAGE_MAGIC && word(8) == crc32(&header[..8]);
+        Ok(valid.then(|| word(4)))
+    }
+
+    /// Calls `found` with the key and location of each valid record in a page, oldest first.
+    ///
+    /// Returns the offset after the last record. A record that's too corrupt
+    /// to skip over ends the page.
+    fn scan(
+        &mut self,
+        page: usize,
+        mut found: impl FnMut(u8, RecordLocation),
+    ) -> Result<usize, StoreError> {
+        let page_offset = self.page_offset(page);
+        let mut offset = self.first_record();
+
+        while offset + RECORD_HEADER_LEN <= F::ERASE_SIZE {
+            let mut header = [0; RECORD_HEADER_LEN];
+            self.flash.read(page_offset + offset, &mut header)?;
+
+            // Erased flash follows the last record
+            if header.iter().all(|byte| *byte == 0xFF) {
+                break;
+            }
+
+            let len = usize::from(u16::from_le_bytes([header[2], header[3]]));
+            let record_len = self.record_len(len);
+
+            if len > MAX_RECORD_LEN || offset + record_len > F::ERASE_SIZE {
+                return Ok(F::ERASE_SIZE);
+            }
+
+            let mut crc = crc32(&header[..4]);
+            let mut chunk = [0; 32];
+            let mut read = 0;
+
+            while read < len {
+                let chunk = &mut chunk[..(len - read).min(32)];
+                self.flash
+                    .read(page_offset + offset + RECORD_HEADER_LEN + read, chunk)?;
+
+                crc = crc32_update(crc, chunk);
+                read += chunk.len();
+            }
+
+            // Torn records are skipped
+            if crc.to_le_bytes() == header[4..8] {
+                found(
+                    header[0],
+                    RecordLocation {
+                        offset,
+                        version: header[1],
+                        len,
+                    },
+                );
+            }
+
+            offset += record_len;
+        }
+
+        Ok(offset)
+    }
+
+    /// Moves the latest records of every key except `key` to the page after
+    /// `page`, followed by `record`, the new record of `key`.
+    fn move_to_next_page(
+        &mut self,
+        page: usize,
+        sequence: u32,
+        key: u8,
+        record: &[u8],
+    ) -> Result<(), StoreError> {
+        let next = (page + 1) % self.page_count();
+
+        let mut latest: LinearMap<u8, RecordLocation, MAX_RECORD_KEYS> = LinearMap::new();
+
+        if self.active.is_some() {
+            let mut overflow = false;
+
+            self.scan(page, |record_key, location| {
+                if record_key != key && latest.insert(record_key, location).is_err() {
+                    overflow = true;
+                }
+            })?;
+
+            if overflow {
+                return Err(StoreError::Full);
+            }
+        }
+
+        // Nothing is erased unless the records all fit
+        let len = self.first_record()
+            + latest
+                .values()
+                .map(|location| self.record_len(location.len))
+                .sum::<usize>()
+            + record.len().next_multiple_of(F::WRITE_SIZE);
+
+        if len > F::ERASE_SIZE {
+            return Err(StoreError::Full);
+        }
+
+        let next_offset = self.page_offset(next);
+        self.flash.erase(next_offset, F::ERASE_SIZE)?;
+
+        let mut end = self.first_record();
+        let mut copied = [0; RECORD_HEADER_LEN + MAX_RECORD_LEN];
+
+        for location in latest.values() {
+            let copied = &mut copied[..RECORD_HEADER_LEN + location.len];
+
+            self.flash
+                .read(self.page_offset(page) + location.offset, copied)?;
+            self.flash.write(next_offset + end, copied)?;
+
+            end += self.record_len(location.len);
+        }
+
+        self.flash.write(next_offset + end, record)?;
+        end += record.len().next_multiple_of(F::WRITE_SIZE);
+
+        // The page only becomes active once its records are all written
+        let sequence = sequence.wrapping_add(1);
+        let mut header = [0; PAGE_HEADER_LEN];
+        header[..4].copy_from_slice(&PAGE_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        let crc = crc32(&header[..8]);
+        header[8..].copy_from_slice(&crc.to_le_bytes());
+
+        self.flash.write(next_offset, &header)?;
+
+        self.active = Some((next, sequence));
+        self.end = end;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::RamFlash;
+
+    /// Four pages, each with room for seven records of up to 24 bytes.
+    type TestFlash = RamFlash<1024, 256, 16>;
+
+    /// Flash that loses power partway through one of its writes or erases.
+    ///
+    /// The interrupted operation is left half done, and every later one fails.
+    struct PowerCutFlash {
+        flash: TestFlash,
+        /// Number of operations that finish before the power is cut.
+        operations_left: usize,
+    }
+
+    impl PowerCutFlash {
+        /// Returns whether the power is still on for the next operation.
+        fn operate(&mut self) -> bool {
+            let powered = self.operations_left > 0;
+            self.operations_left = self.operations_left.saturating_sub(1);
+            powered
+        }
+    }
+
+    impl Flash for PowerCutFlash {
+        const ERASE_SIZE: usize = TestFlash::ERASE_SIZE;
+        const WRITE_SIZE: usize = TestFlash::WRITE_SIZE;
+
+        fn capacity(&self) -> usize {
+            self.flash.capacity()
+        }
+
+        fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), FlashError> {
+            self.flash.read(offset, buffer)
+        }
+
+        fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+            if self.operate() {
+                return self.flash.write(offset, data);
+            }
+
+            // Only the whole words in the first half make it
+            let torn = data.len() / 2 / Self::WRITE_SIZE * Self::WRITE_SIZE;
+            let _ = self.flash.write(offset, &data[..torn]);
+            Err(FlashError::Hardware)
+        }
+
+        fn erase(&mut self, offset: usize, len: usize) -> Result<(), FlashError> {
+            if self.operate() {
+                return self.flash.erase(offset, len);
+            }
+
+            // Only the first half of the sector is erased
+            self.flash.data_mut()[offset..offset + len / 2].fill(0xFF);
+            Err(FlashError::Hardware)
+        }
+    }
+
+    fn open<F: Flash>(flash: F) -> RecordStore<F> {
+        let Ok(store) = RecordStore::new(flash) else {
+            panic!("couldn't open the store");
+        };
+        store
+    }
+
+    /// Returns the version and data of the latest record of `key`.
+    fn fetch<F: Flash>(store: &mut RecordStore<F>, key: u8) -> Option<(u8, heapless::Vec<u8, 32>)> {
+        let mut buffer = [0; 32];
+        let Ok(record) = store.fetch(key, &mut buffer) else {
+            panic!("couldn't fetch record {}", key);
+        };
+
+        record.map(|(version, len)| (version, heapless::Vec::from_slice(&buffer[..len]).unwrap()))
+    }
+
+    fn store<F: Flash>(store: &mut RecordStore<F>, key: u8, data: &[u8]) {
+        assert!(
+            store.store(key, 1, data).is_ok(),
+            "couldn't store record {key}"
+        );
+    }
+
+    #[test]
+    fn empty_store() {
+        let mut store = open(TestFlash::new());
+
+        assert_eq!(fetch(&mut store, 1), None);
+    }
+
+    #[test]
+    fn too_small() {
+        assert!(RecordStore::new(RamFlash::<256, 256, 16>::new())
+            .is_err_and(|error| error == StoreError::TooSmall));
+    }
+
+    #[test]
+    fn latest_record_wins() {
+        let mut records = open(TestFlash::new());
+        store(&mut records, 1, b"first");
+        store(&mut records, 2, b"other data");
+        assert!(records.store(1, 2, b"second").is_ok());
+
+        // Both before and after reopening the store
+        for _ in 0..2 {
+            assert_eq!(
+                fetch(&mut records, 1).unwrap(),
+                (2, heapless::Vec::from_slice(b"second").unwrap())
+            );
+            assert_eq!(&fetch(&mut records, 2).unwrap().1, b"other data");
+            assert_eq!(fetch(&mut records, 3), None);
+
+            records = open(records.into_inner());
+        }
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut records = open(TestFlash::new());
+        store(&mut records, 1, b"longer than the buffer");
+
+        assert!(records
+            .fetch(1, &mut [0; 4])
+            .is_err_and(|error| error == StoreError::TooLarge));
+    }
+
+    #[test]
+    fn torn_record_is_skipped() {
+        let mut records = open(TestFlash::new());
+        store(&mut records, 1, b"old");
+        store(&mut records, 1, b"new");
+
+        let mut flash = records.into_inner();
+        let data = flash.data_mut();
+        let torn = data.windows(3).position(|window| window == b"new").unwrap();
+        data[torn + 2] = 0xFF;
+
+        let mut records = open(flash);
+        assert_eq!(&fetch(&mut records, 1).unwrap().1, b"old");
+
+        // The torn record's space isn't reused
+        store(&mut records, 1, b"newer");
+        let mut records = open(records.into_inner());
+        assert_eq!(&fetch(&mut records, 1).unwrap().1, b"newer");
+    }
+
+    #[test]
+    fn rolls_over_pages() {
+        let mut records = open(TestFlash::new());
+
+        // Enough records to go round the pages several times
+        for count in 0u32..100 {
+            store(&mut records, (count % 3) as u8, &count.to_le_bytes());
+
+            if count % 7 == 0 {
+                records = open(records.into_inner());
+            }
+        }
+
+        let mut records = open(records.into_inner());
+        assert_eq!(&fetch(&mut records, 0).unwrap().1, &99u32.to_le_bytes());
+        assert_eq!(&fetch(&mut records, 1).unwrap().1, &97u32.to_le_bytes());
+        assert_eq!(&fetch(&mut records, 2).unwrap().1, &98u32.to_le_bytes());
+    }
+
+    #[test]
+    fn full_page_is_left_alone() {
+        let mut records = open(TestFlash::new());
+        store(&mut records, 1, b"kept");
+
+        // A record larger than a page can never be stored
+        assert!(records
+            .store(2, 1, &[0; 250])
+            .is_err_and(|error| error == StoreError::Full));
+
+        let mut records = open(records.into_inner());
+        assert_eq!(&fetch(&mut records, 1).unwrap().1, b"kept");
+        assert_eq!(fetch(&mut records, 2), None);
+    }
+
+    #[test]
+    fn power_loss_while_moving_to_next_page() {
+        // Erasing the next page, copying two records, writing the new record and the page header
+        const MOVE_OPERATIONS: usize = 5;
+
+        for cut in 0..=MOVE_OPERATIONS {
+            let mut records = open(TestFlash::new());
+            store(&mut records, 1, b"old config");
+            store(&mut records, 2, b"other data");
+            for count in 0u8..5 {
+                store(&mut records, 3, &[count; 10]);
+            }
+
+            // The page is full, so the next record moves to the next page
+            let mut records = open(PowerCutFlash {
+                flash: records.into_inner(),
+                operations_left: cut,
+            });
+            let stored = records.store(1, 1, b"new config").is_ok();
+            assert_eq!(stored, cut == MOVE_OPERATIONS);
+
+            let mut records = open(records.into_inner().flash);
+            let expected: &[u8] = if stored { b"new config" } else { b"old config" };
+            assert_eq!(
+                &fetch(&mut records, 1).unwrap().1,
+                expected,
+                "power cut after {cut} operations"
+            );
+            assert_eq!(&fetch(&mut records, 2).unwrap().1, b"other data");
+            assert_eq!(&fetch(&mut records, 3).unwrap().1, &[4; 10]);
+
+            // The store carries on after the power comes back
+            store(&mut records, 1, b"newer");
+            let mut records = open(records.into_inner());
+            assert_eq!(&fetch(&mut records, 1).unwrap().1, b"newer");
+            assert_eq!(&fetch(&mut records, 2).unwrap().1, b"other data");
+        }
+    }
+
+    #[test]
+    fn power_loss_while_appending() {
+        let mut records = open(TestFlash::new());
+        store(&mut records, 1, b"old config");
+
+        let mut records = open(PowerCutFlash {
+            flash: records.into_inner(),
+            operations_left: 0,
+        });
+        assert!(records
+            .store(1, 1, b"new config, long enough to tear")
+            .is_err());
+
+        let mut records = open(records.into_inner().flash);
+        assert_eq!(&fetch(&mut records, 1).unwrap().1, b"old config");
+    }
+}
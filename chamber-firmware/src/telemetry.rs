@@ -0,0 +1,73 @@
+use crate::xbee::message::MessageType;
+
+/// Length of an encoded [`TelemetryReport`].
+pub const TELEMETRY_REPORT_LEN: usize = 18;
+
+/// Values derived from the sensors' readings and the grow light, as reported to the coordinator.
+///
+/// The raw readings are in the data log, these are the values the grower
+/// steers the chamber by.
+#[derive(Clone, Copy, Default, defmt::Format)]
+pub struct TelemetryReport {
+    /// Chamber air temperature in °C.
+    pub air_temperature: Option<f64>,
+    /// Vapour pressure deficit in kPa.
+    pub vapour_pressure_deficit: Option<f64>,
+    /// Daily light integral so far today, in mol/m².
+    pub dli_today: Option<f64>,
+    /// Daily light integral of the last complete day, in mol/m².
+    pub dli_previous_day: Option<f64>,
+    /// Whether the clock had been synchronised with the coordinator when the values were taken.
+    ///
+    /// Until then, the days the light integrals are totalled over may be off.
+    pub synced: bool,
+}
+
+impl TelemetryReport {
+    /// Encodes the report as `R` followed by each value, in the order they're declared.
+    ///
+    /// Values are big-endian f32, NaN if unknown, and `synced` is 1 or 0.
+    pub fn encode(&self) -> [u8; TELEMETRY_REPORT_LEN] {
+        let mut encoded = [0; TELEMETRY_REPORT_LEN];
+        encoded[0] = MessageType::TelemetryReport as u8;
+
+        for (field, value) in encoded[1..17].as_chunks_mut::<4>().0.iter_mut().zip([
+            self.air_temperature,
+            self.vapour_pressure_deficit,
+            self.dli_today,
+            self.dli_previous_day,
+        ]) {
+            *field = value.map_or(f32::NAN, |value| value as f32).to_be_bytes();
+        }
+        encoded[17] = u8::from(self.synced);
+
+        encoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_report() {
+        let report = TelemetryReport {
+            air_temperature: Some(24.5),
+            vapour_pressure_deficit: None,
+            dli_today: Some(12.0),
+            dli_previous_day: None,
+            synced: true,
+        };
+        let encoded = report.encode();
+
+        assert_eq!(encoded[0], b'R');
+        assert_eq!(encoded[1..5], 24.5f32.to_be_bytes());
+        assert!(f32::from_be_bytes(encoded[5..9].try_into().unwrap()).is_nan());
+        assert_eq!(encoded[9..13], 12.0f32.to_be_bytes());
+        assert!(f32::from_be_bytes(encoded[13..17].try_into().unwrap()).is_nan());
+        assert_eq!(encoded[17], 1);
+
+        let unsynced = TelemetryReport::default().encode();
+        assert_eq!(unsynced[17], 0);
+    }
+}
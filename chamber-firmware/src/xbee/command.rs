@@ -0,0 +1,532 @@
+use crate::atlas::{
+    CalibrationPoint, CalibrationProcedure, CommandBuffer, DeviceType, Dispense, StabilityCriteria,
+    MAX_CALIBRATION_POINTS, MAX_STABILITY_WINDOW,
+};
+use crate::clock::{self, Timestamp};
+use crate::light::{
+    DliTarget, Photoperiod, PhotoperiodSegment, TimeOfDay, MAX_PHOTOPERIOD_SEGMENTS,
+};
+
+use super::message::MessageType;
+
+/// Largest offset of local time from UTC that's accepted, in seconds, such as UTC+14:00.
+pub const MAX_UTC_OFFSET: i32 = 14 * 3600;
+
+/// Smallest offset of local time from UTC that's accepted, in seconds, such as UTC-12:00.
+pub const MIN_UTC_OFFSET: i32 = -12 * 3600;
+
+/// Instruction logged for each point of a calibration started over the radio.
+///
+/// The coordinator tells the operator what each point needs.
+pub const REMOTE_CALIBRATION_INSTRUCTION: &str =
+    "Prepare the point as the coordinator describes, then confirm.";
+
+/// Length of an encoded photoperiod segment.
+const SEGMENT_LEN: usize = 8;
+
+/// Length the device type's code is padded to in a calibration command.
+const DEVICE_CODE_LEN: usize = 3;
+
+/// A change the coordinator asks a chamber to make.
+///
+/// Each command is a message of its own type. Values are big-endian, and
+/// intensities are in %.
+pub enum Command {
+    /// Replaces the photoperiod, in the form
+    /// `P<sunrise s: u32><sunset s: u32>` followed by each segment as
+    /// `<on min: u16><off min: u16><intensity: f32>`.
+    SetPhotoperiod(Photoperiod),
+    /// Sets the offset of local time from UTC, in the form `U<offset s: i32>`.
+    SetUtcOffset(i32),
+    /// Sets the daily light integral the photoperiod is adapted to, in the
+    /// form `I<dli mol/m²: f32><max stretch s: u32>`. A NaN DLI clears it.
+    SetDliTarget(Option<DliTarget>),
+    /// Sets the grow light's intensity until the photoperiod's next update, in the form `G<intensity: f32>`.
+    SetGrowLight(f64),
+    /// Sets the real-time clock, in the form `C<ms since the Unix epoch: u64>`.
+    SetTime(Timestamp),
+    /// Starts maintenance if 1, or finishes it if 0, in the form `M<0|1>`.
+    SetMaintenance(bool),
+    /// Starts a dispense, in the form `N<mode: u8><amount: f32><minutes: u32>`.
+    ///
+    /// The mode is 0 for a volume, 1 for a volume over the minutes, 2 for a
+    /// flow rate for the minutes, or until stopped if they're 0, and 3 to run
+    /// until stopped.
+    Dispense(Dispense),
+    /// Stops the pump, in the form `X`.
+    StopPump,
+    /// Starts calibrating a sensor, in the form
+    /// `K<address: u8><device code: 3 bytes, 0 padded><expected points: u8><window: u8><max deviation: f32>`
+    /// followed by each point's calibration command as `<length: u8><command>`.
+    ///
+    /// The expected points are what `Cal,?` reports once it's done.
+    StartCalibration(CalibrationRequest),
+    /// Confirms the operator has prepared the current calibration point, in the form `Y`.
+    ConfirmCalibration,
+    /// Cancels the running calibration, in the form `Z`.
+    CancelCalibration,
+    /// Restores a sensor's stored calibration, in the form `B<address: u8>`.
+    RestoreCalibration(u8),
+}
+
+/// A calibration the coordinator asks for, see [`Command::StartCalibration`].
+pub struct CalibrationRequest {
+    pub address: u8,
+    pub device_type: DeviceType,
+    /// Each point's calibration command, such as `Cal,low,500`.
+    pub commands: heapless::Vec<CommandBuffer, MAX_CALIBRATION_POINTS>,
+    pub criteria: StabilityCriteria,
+    /// What `Cal,?` reports once every point is calibrated.
+    pub expected_points: u8,
+}
+
+impl CalibrationRequest {
+    /// Returns the procedure that carries out the calibration.
+    ///
+    /// Each point's instruction is [`REMOTE_CALIBRATION_INSTRUCTION`].
+    pub fn procedure(self) -> CalibrationProcedure {
+        let points = self
+            .commands
+            .into_iter()
+            .map(|command| CalibrationPoint {
+                instruction: REMOTE_CALIBRATION_INSTRUCTION,
+                command,
+            })
+            .collect();
+
+        CalibrationProcedure::new(
+            u32::from(self.address),
+            self.device_type,
+            points,
+            self.criteria,
+            self.expected_points,
+        )
+    }
+}
+
+impl Command {
+    /// Decodes a command, returning `None` if it's malformed, out of range, or not a command.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (_, body) = data.split_first()?;
+        let mut body = BodyReader(body);
+
+        let command = match MessageType::of(data)? {
+            MessageType::SetPhotoperiod => {
+                let sunrise = body.u32()?;
+                let sunset = body.u32()?;
+
+                if body.0.len() % SEGMENT_LEN != 0 {
+                    return None;
+                }
+
+                let mut segments: heapless::Vec<PhotoperiodSegment, MAX_PHOTOPERIOD_SEGMENTS> =
+                    heapless::Vec::new();
+                while !body.0.is_empty() {
+                    let segment = PhotoperiodSegment {
+                        on: time_of_day(body.u16()?)?,
+                        off: time_of_day(body.u16()?)?,
+                        intensity: intensity(body.f32()?)?,
+                    };
+                    segments.push(segment).ok()?;
+                }
+
+                Command::SetPhotoperiod(Photoperiod::new(&segments, sunrise, sunset)?)
+            }
+            MessageType::SetUtcOffset => {
+                let offset = body.i32()?;
+
+                if !(MIN_UTC_OFFSET..=MAX_UTC_OFFSET).contains(&offset) {
+                    return None;
+                }
+
+                Command::SetUtcOffset(offset)
+            }
+            MessageType::SetDliTarget => {
+                let dli = body.f32()?;
+                let max_stretch = body.u32()?;
+
+                if dli.is_nan() {
+                    Command::SetDliTarget(None)
+                } else if dli.is_finite() && dli > 0.0 {
+                    Command::SetDliTarget(Some(DliTarget {
+                        dli: f64::from(dli),
+                        max_stretch,
+                    }))
+                } else {
+                    return None;
+                }
+            }
+            MessageType::SetGrowLight => Command::SetGrowLight(intensity(body.f32()?)?),
+            MessageType::SetTime => {
+                let time = Timestamp::from_millis(body.u64()?);
+
+                if !clock::can_hold(time) {
+                    return None;
+                }
+
+                Command::SetTime(time)
+            }
+            MessageType::SetMaintenance => match body.u8()? {
+                0 => Command::SetMaintenance(false),
+                1 => Command::SetMaintenance(true),
+                _ => return None,
+            },
+            MessageType::Dispense => {
+                let mode = body.u8()?;
+                let amount = body.f32()?;
+                let minutes = body.u32()?;
+
+                if !amount.is_finite() {
+                    return None;
+                }
+
+                let amount = f64::from(amount);
+                Command::Dispense(match (mode, minutes) {
+                    (0, _) => Dispense::Volume(amount),
+                    (1, 1..) => Dispense::VolumeOverTime {
+                        volume: amount,
+                        minutes,
+                    },
+                    (2, 0) => Dispense::ConstantFlow {
+                        rate: amount,
+                        minutes: None,
+                    },
+                    (2, _) => Dispense::ConstantFlow {
+                        rate: amount,
+                        minutes: Some(minutes),
+                    },
+                    (3, _) => Dispense::Continuous,
+                    _ => return None,
+                })
+            }
+            MessageType::StopPump => Command::StopPump,
+            MessageType::StartCalibration => {
+                let address = body.u8()?;
+                let code = body.take::<DEVICE_CODE_LEN>()?;
+                let code = core::str::from_utf8(&code).ok()?.trim_end_matches('\0');
+                let device_type: DeviceType = code.parse().ok()?;
+                let expected_points = body.u8()?;
+                let criteria = StabilityCriteria {
+                    window: usize::from(body.u8()?),
+                    max_deviation: f64::from(body.f32()?),
+                };
+
+                if !(2..=MAX_STABILITY_WINDOW).contains(&criteria.window)
+                    || criteria.max_deviation.is_nan()
+                    || criteria.max_deviation < 0.0
+                {
+                    return None;
+                }
+
+                let mut commands = heapless::Vec::new();
+                while !body.0.is_empty() {
+                    let len = usize::from(body.u8()?);
+                    let (command, rest) = body.0.split_at_checked(len)?;
+                    body.0 = rest;
+
+                    if command.is_empty() || !command.is_ascii() {
+                        return None;
+                    }
+
+                    commands
+                        .push(CommandBuffer::from_slice(command).ok()?)
+                        .ok()?;
+                }
+
+                Command::StartCalibration(CalibrationRequest {
+                    address,
+                    device_type,
+                    commands,
+                    criteria,
+                    expected_points,
+                })
+            }
+            MessageType::ConfirmCalibration => Command::ConfirmCalibration,
+            MessageType::CancelCalibration => Command::CancelCalibration,
+            MessageType::RestoreCalibration => Command::RestoreCalibration(body.u8()?),
+            _ => return None,
+        };
+
+        body.0.is_empty().then_some(command)
+    }
+}
+
+/// Returns the time of day `minutes` after midnight, or `None` if it's past the end of the day.
+fn time_of_day(minutes: u16) -> Option<TimeOfDay> {
+    TimeOfDay::new(u32::from(minutes / 60), u32::from(minutes % 60), 0)
+}
+
+/// Returns an intensity in %, or `None` if it's out of range.
+fn intensity(value: f32) -> Option<f64> {
+    (0.0..=100.0).contains(&value).then_some(f64::from(value))
+}
+
+/// Reads big-endian values from the start of a command's body.
+struct BodyReader<'a>(&'a [u8]);
+
+impl BodyReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (value, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*value)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take().map(u8::from_be_bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_be_bytes)
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take().map(i32::from_be_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_be_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_be_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::atlas::CalibrationState;
+
+    fn message(message_type: MessageType, body: &[&[u8]]) -> Vec<u8> {
+        let mut data = Vec::from([message_type as u8]);
+        for value in body {
+            data.extend_from_slice(value);
+        }
+        data
+    }
+
+    #[test]
+    fn set_photoperiod() {
+        // 06:00 to 00:00 at 75%, and 01:30 to 02:00 at 20%
+        let data = message(
+            MessageType::SetPhotoperiod,
+            &[
+                &900u32.to_be_bytes(),
+                &600u32.to_be_bytes(),
+                &360u16.to_be_bytes(),
+                &0u16.to_be_bytes(),
+                &75f32.to_be_bytes(),
+                &90u16.to_be_bytes(),
+                &120u16.to_be_bytes(),
+                &20f32.to_be_bytes(),
+            ],
+        );
+
+        let Some(Command::SetPhotoperiod(photoperiod)) = Command::decode(&data) else {
+            panic!("expected a photoperiod");
+        };
+        let [first, second] = photoperiod.segments() else {
+            panic!("expected two segments");
+        };
+
+        assert_eq!(photoperiod.sunrise, 900);
+        assert_eq!(photoperiod.sunset, 600);
+        assert_eq!((first.on.seconds(), first.off.seconds()), (6 * 3600, 0));
+        assert_eq!(first.intensity, 75.0);
+        assert_eq!((second.on.seconds(), second.off.seconds()), (5400, 7200));
+        assert_eq!(second.intensity, 20.0);
+
+        // No segments turns the lights off
+        let off = message(MessageType::SetPhotoperiod, &[&[0; 8]]);
+        assert!(matches!(
+            Command::decode(&off),
+            Some(Command::SetPhotoperiod(photoperiod)) if photoperiod.segments().is_empty()
+        ));
+    }
+
+    #[test]
+    fn malformed_photoperiods() {
+        let segment = |on: u16, intensity: f32| {
+            let mut data = Vec::from(on.to_be_bytes());
+            data.extend_from_slice(&0u16.to_be_bytes());
+            data.extend_from_slice(&intensity.to_be_bytes());
+            data
+        };
+        let photoperiod = |segments: &[Vec<u8>]| {
+            let mut data = message(MessageType::SetPhotoperiod, &[&[0; 8]]);
+            for segment in segments {
+                data.extend_from_slice(segment);
+            }
+            data
+        };
+
+        assert!(Command::decode(&photoperiod(&[segment(1440, 50.0)])).is_none());
+        assert!(Command::decode(&photoperiod(&[segment(0, 101.0)])).is_none());
+        assert!(Command::decode(&photoperiod(&[segment(0, f32::NAN)])).is_none());
+        assert!(Command::decode(&photoperiod(&[segment(0, 50.0)])[..15]).is_none());
+        assert!(Command::decode(&photoperiod(&std::vec![segment(0, 50.0); 9])).is_none());
+    }
+
+    #[test]
+    fn light_settings() {
+        let offset = message(MessageType::SetUtcOffset, &[&(-5i32 * 3600).to_be_bytes()]);
+        assert!(matches!(
+            Command::decode(&offset),
+            Some(Command::SetUtcOffset(-18_000))
+        ));
+        let too_far = message(MessageType::SetUtcOffset, &[&(15i32 * 3600).to_be_bytes()]);
+        assert!(Command::decode(&too_far).is_none());
+
+        let target = message(
+            MessageType::SetDliTarget,
+            &[&35f32.to_be_bytes(), &3600u32.to_be_bytes()],
+        );
+        assert!(matches!(
+            Command::decode(&target),
+            Some(Command::SetDliTarget(Some(DliTarget {
+                dli: 35.0,
+                max_stretch: 3600
+            })))
+        ));
+        let cleared = message(
+            MessageType::SetDliTarget,
+            &[&f32::NAN.to_be_bytes(), &0u32.to_be_bytes()],
+        );
+        assert!(matches!(
+            Command::decode(&cleared),
+            Some(Command::SetDliTarget(None))
+        ));
+        let negative = message(
+            MessageType::SetDliTarget,
+            &[&(-1f32).to_be_bytes(), &0u32.to_be_bytes()],
+        );
+        assert!(Command::decode(&negative).is_none());
+
+        let light = message(MessageType::SetGrowLight, &[&40f32.to_be_bytes()]);
+        assert!(matches!(
+            Command::decode(&light),
+            Some(Command::SetGrowLight(40.0))
+        ));
+        let too_bright = message(MessageType::SetGrowLight, &[&100.5f32.to_be_bytes()]);
+        assert!(Command::decode(&too_bright).is_none());
+    }
+
+    #[test]
+    fn set_time() {
+        // 2026-10-19T12:00:00Z
+        let time = message(MessageType::SetTime, &[&1_792_411_200_000u64.to_be_bytes()]);
+        assert!(matches!(
+            Command::decode(&time),
+            Some(Command::SetTime(time)) if time.millis() == 1_792_411_200_000
+        ));
+
+        // 2100-01-01T00:00:00Z is past what the RTC holds
+        let too_late = message(MessageType::SetTime, &[&4_102_444_800_000u64.to_be_bytes()]);
+        assert!(Command::decode(&too_late).is_none());
+    }
+
+    #[test]
+    fn maintenance_and_pump() {
+        assert!(matches!(
+            Command::decode(&[b'M', 1]),
+            Some(Command::SetMaintenance(true))
+        ));
+        assert!(matches!(
+            Command::decode(&[b'M', 0]),
+            Some(Command::SetMaintenance(false))
+        ));
+        assert!(Command::decode(&[b'M', 2]).is_none());
+        assert!(matches!(Command::decode(b"X"), Some(Command::StopPump)));
+
+        let dispense = |mode: u8, amount: f32, minutes: u32| {
+            let data = message(
+                MessageType::Dispense,
+                &[&[mode], &amount.to_be_bytes(), &minutes.to_be_bytes()],
+            );
+            match Command::decode(&data) {
+                Some(Command::Dispense(dispense)) => dispense.command(),
+                _ => None,
+            }
+        };
+
+        assert_eq!(dispense(0, 12.5, 0).unwrap(), b"D,12.50");
+        assert_eq!(dispense(1, 10.0, 5).unwrap(), b"D,10.00,5");
+        assert_eq!(dispense(2, 1.5, 0).unwrap(), b"DC,1.50,*");
+        assert_eq!(dispense(2, 1.5, 3).unwrap(), b"DC,1.50,3");
+        assert_eq!(dispense(3, 0.0, 0).unwrap(), b"D,*");
+
+        // A volume over no time, an unknown mode and no amount
+        assert!(dispense(1, 10.0, 0).is_none());
+        assert!(dispense(4, 1.0, 0).is_none());
+        assert!(dispense(0, f32::INFINITY, 0).is_none());
+    }
+
+    #[test]
+    fn calibration() {
+        let data = message(
+            MessageType::StartCalibration,
+            &[
+                &[0x64],
+                b"EC\0",
+                &[2, 5],
+                &0.5f32.to_be_bytes(),
+                &[11],
+                b"Cal,low,500",
+                &[14],
+                b"Cal,high,80000",
+            ],
+        );
+
+        let Some(Command::StartCalibration(request)) = Command::decode(&data) else {
+            panic!("expected a calibration");
+        };
+        assert_eq!(request.commands[1], b"Cal,high,80000");
+
+        let procedure = request.procedure();
+        assert_eq!(procedure.address, 0x64);
+        assert!(procedure.device_type == DeviceType::Conductivity);
+        assert!(matches!(
+            procedure.state,
+            CalibrationState::AwaitingOperator { point: 0 }
+        ));
+        assert_eq!(
+            procedure.instruction(),
+            Some(REMOTE_CALIBRATION_INSTRUCTION)
+        );
+
+        // A command cut short, and a window too short to judge stability by
+        assert!(Command::decode(&data[..data.len() - 1]).is_none());
+        let mut short_window = data.clone();
+        short_window[6] = 1;
+        assert!(Command::decode(&short_window).is_none());
+
+        assert!(matches!(
+            Command::decode(b"Y"),
+            Some(Command::ConfirmCalibration)
+        ));
+        assert!(matches!(
+            Command::decode(b"Z"),
+            Some(Command::CancelCalibration)
+        ));
+        assert!(matches!(
+            Command::decode(&[b'B', 0x64]),
+            Some(Command::RestoreCalibration(0x64))
+        ));
+    }
+
+    #[test]
+    fn not_commands() {
+        assert!(Command::decode(&[]).is_none());
+        assert!(Command::decode(b"?").is_none());
+        // Reports and responses aren't commands
+        assert!(Command::decode(b"S").is_none());
+        // Trailing bytes
+        assert!(Command::decode(b"XX").is_none());
+    }
+}
@@ -1,49 +1,187 @@
-pub struct Frame<'b, T: FrameData<'b>> {
-    id: Option<u8>,
-    destination: u64,
-    destination_small: u16,
-    broadcast_radius: Option<u8>,
-    data: &'b T,
-}
+/// Byte every API frame starts with.
+pub const START_DELIMITER: u8 = 0x7E;
+
+/// Largest API frame the firmware sends or receives, including its delimiter, length and checksum.
+pub const MAX_FRAME_LEN: usize = 128;
+
+/// Frame type of a [`LocalATCommandRequest`].
+pub const LOCAL_AT_COMMAND_REQUEST: u8 = 0x08;
+
+/// Frame type of a [`LocalATCommandResponse`].
+pub const LOCAL_AT_COMMAND_RESPONSE: u8 = 0x88;
+
+/// Frame type of a [`ModemStatus`].
+pub const MODEM_STATUS: u8 = 0x8A;
+
+/// Frame type of a [`TransmitRequest`].
+pub const TRANSMIT_REQUEST: u8 = 0x10;
+
+/// Frame type of a [`TransmitStatus`].
+pub const TRANSMIT_STATUS: u8 = 0x8B;
+
+/// Frame type of a [`ReceivePacket`].
+pub const RECEIVE_PACKET: u8 = 0x90;
 
-impl<'b, T: FrameData<'b>> Frame<'b, T> {
-    pub fn write(self, buffer: &mut [u8]) {
-        let mut offset = 0;
+/// AT command reading whether the radio has joined a network, which it has if the value is 0.
+pub const ASSOCIATION_INDICATION: [char; 2] = ['A', 'I'];
 
-        buffer[offset] = 0x7E;
-        offset += 1;
+/// AT command setting the 64-bit PAN ID of the network to join, or 0 to join any network.
+pub const PAN_ID: [char; 2] = ['I', 'D'];
 
-        // Placeholder for length, we'll circle back for that one
-        offset += 2;
+/// 16-bit address used when the destination's isn't known.
+pub const UNKNOWN_SMALL_ADDRESS: u16 = 0xFFFE;
+
+/// Returns the checksum of a frame's data, from its type up to the checksum.
+pub fn checksum(frame_data: &[u8]) -> u8 {
+    0xFF - frame_data
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// An API frame to write to the radio.
+pub struct Frame<T> {
+    /// Frame ID, for frame types that have one.
+    ///
+    /// An ID of 0 asks the radio not to answer the frame.
+    pub id: Option<u8>,
+    pub data: T,
+}
+
+impl<'b, T: FrameData<'b>> Frame<T> {
+    /// Writes the frame to `buffer`, returning its length.
+    ///
+    /// Returns `None` if the frame doesn't fit in `buffer`.
+    pub fn write(&self, buffer: &mut [u8]) -> Option<usize> {
+        // The length covers the frame's data, from its type up to the checksum
+        let mut length = 0;
 
         // Frame type
-        buffer[offset] = self.data.frame_type();
-        offset += 1;
+        *buffer.get_mut(3)? = self.data.frame_type();
+        length += 1;
 
         // Frame ID
-        buffer[offset] = self.id.unwrap_or(0);
-        offset += 1;
+        if let Some(id) = self.id {
+            *buffer.get_mut(3 + length)? = id;
+            length += 1;
+        }
 
-        // Destination Address
-        let dest_array = self.destination.to_be_bytes();
-        buffer[offset..dest_array.len()].copy_from_slice(&dest_array);
-        offset += dest_array.len();
+        length += self.data.write(buffer.get_mut(3 + length..)?)?;
 
-        // 16-Bit Destination Address
-        let dest_array = self.destination_small.to_be_bytes();
-        buffer[offset..dest_array.len()].copy_from_slice(&dest_array);
-        offset += dest_array.len();
+        let [length_high, length_low] = u16::try_from(length).ok()?.to_be_bytes();
+        buffer[..3].copy_from_slice(&[START_DELIMITER, length_high, length_low]);
+        *buffer.get_mut(3 + length)? = checksum(&buffer[3..3 + length]);
 
-        // Broadcast Radius
-        buffer[offset] = self.broadcast_radius.unwrap_or(0);
-        offset += 1;
+        Some(length + 4)
     }
 }
 
 pub trait FrameData<'b>: Sized {
     fn frame_type(&self) -> u8;
-    fn write(self, buffer: &'b mut [u8]) -> usize;
-    fn read(self, buffer: &'b [u8]) -> Option<Self>;
+
+    /// Writes the data following the frame's type and ID, returning its length.
+    ///
+    /// Returns `None` if it doesn't fit in `buffer`.
+    fn write(&self, buffer: &mut [u8]) -> Option<usize>;
+
+    /// Reads the data following the frame's type, starting with its ID if it has one.
+    fn read(buffer: &'b [u8]) -> Option<Self>;
+}
+
+/// Reassembles API frames from the bytes the radio sends.
+///
+/// Bytes outside a frame are skipped, as are frames that are too long or
+/// whose checksum doesn't match.
+pub struct FrameReader {
+    buffer: heapless::Vec<u8, MAX_FRAME_LEN>,
+    /// Whether the buffer holds a frame that was already returned.
+    complete: bool,
+}
+
+impl FrameReader {
+    pub const fn new() -> Self {
+        Self {
+            buffer: heapless::Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Adds a byte from the radio.
+    ///
+    /// Returns the frame's data, from its type up to the checksum, once the byte completes a frame.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if self.complete {
+            self.buffer.clear();
+            self.complete = false;
+        }
+
+        // A frame can only start with the delimiter
+        if self.buffer.is_empty() && byte != START_DELIMITER {
+            return None;
+        }
+
+        if self.buffer.push(byte).is_err() {
+            self.buffer.clear();
+            return None;
+        }
+
+        let [_, length_high, length_low, ..] = self.buffer[..] else {
+            return None;
+        };
+
+        let length = usize::from(u16::from_be_bytes([length_high, length_low]));
+
+        if length + 4 > MAX_FRAME_LEN {
+            defmt::warn!("[xbee] Dropping frame of {} bytes, it's too long.", length);
+            self.buffer.clear();
+            return None;
+        }
+
+        if self.buffer.len() < length + 4 {
+            return None;
+        }
+
+        if checksum(&self.buffer[3..3 + length]) != self.buffer[3 + length] {
+            defmt::warn!("[xbee] Dropping frame with a bad checksum.");
+            self.buffer.clear();
+            return None;
+        }
+
+        self.complete = true;
+        Some(&self.buffer[3..3 + length])
+    }
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A frame received from the radio, of a type the firmware handles.
+pub enum ReceivedFrame<'a> {
+    LocalATCommandResponse(LocalATCommandResponse<'a>),
+    ModemStatus(ModemStatus),
+    TransmitStatus(TransmitStatus),
+    ReceivePacket(ReceivePacket<'a>),
+}
+
+impl<'a> ReceivedFrame<'a> {
+    /// Decodes a frame's data, from its type up to the checksum.
+    ///
+    /// Returns `None` if it's malformed or of a type the firmware doesn't handle.
+    pub fn decode(frame_data: &'a [u8]) -> Option<Self> {
+        let (&frame_type, data) = frame_data.split_first()?;
+
+        match frame_type {
+            LOCAL_AT_COMMAND_RESPONSE => {
+                LocalATCommandResponse::read(data).map(Self::LocalATCommandResponse)
+            }
+            MODEM_STATUS => ModemStatus::read(data).map(Self::ModemStatus),
+            TRANSMIT_STATUS => TransmitStatus::read(data).map(Self::TransmitStatus),
+            RECEIVE_PACKET => ReceivePacket::read(data).map(Self::ReceivePacket),
+            _ => None,
+        }
+    }
 }
 
 pub struct LocalATCommandRequest<'a> {
@@ -51,36 +189,45 @@ pub struct LocalATCommandRequest<'a> {
     value: &'a [u8],
 }
 
+impl<'a> LocalATCommandRequest<'a> {
+    /// Creates a request to run `command`, which reads its value if `value` is empty.
+    pub fn new(command: [char; 2], value: &'a [u8]) -> Self {
+        Self { command, value }
+    }
+}
+
 impl FrameData<'_> for LocalATCommandRequest<'_> {
     fn frame_type(&self) -> u8 {
-        0x08
+        LOCAL_AT_COMMAND_REQUEST
     }
 
-    fn write(self, buffer: &mut [u8]) -> usize {
-        let mut offset = 0;
+    fn write(&self, buffer: &mut [u8]) -> Option<usize> {
+        let length = 2 + self.value.len();
+        let buffer = buffer.get_mut(..length)?;
 
         // AT Command
-        buffer[offset..1].copy_from_slice(&self.command.map(|element| element as u8));
-        offset += 1;
+        buffer[..2].copy_from_slice(&self.command.map(|element| element as u8));
 
         // Parameter Value
-        buffer[offset..self.value.len()].copy_from_slice(self.value);
-        offset += self.value.len();
+        buffer[2..].copy_from_slice(self.value);
 
-        offset
+        Some(length)
     }
 
-    fn read(self, _buffer: &[u8]) -> Option<Self> {
+    fn read(_buffer: &[u8]) -> Option<Self> {
         todo!()
     }
 }
 
 pub struct LocalATCommandResponse<'a> {
-    command: [char; 2],
-    status: LocalATCommandResponseStatus,
-    data: &'a [u8],
+    /// Frame ID of the request.
+    pub id: u8,
+    pub command: [char; 2],
+    pub status: LocalATCommandResponseStatus,
+    pub data: &'a [u8],
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
 #[repr(u8)]
 pub enum LocalATCommandResponseStatus {
     Ok = 0,
@@ -91,30 +238,17 @@ pub enum LocalATCommandResponseStatus {
 
 impl<'a, 'b: 'a> FrameData<'b> for LocalATCommandResponse<'a> {
     fn frame_type(&self) -> u8 {
-        0x88
+        LOCAL_AT_COMMAND_RESPONSE
     }
 
-    fn write(self, _buffer: &mut [u8]) -> usize {
+    fn write(&self, _buffer: &mut [u8]) -> Option<usize> {
         todo!()
     }
 
-    fn read(self, buffer: &'b [u8]) -> Option<Self> {
-        let mut offset = 0;
-
-        // Command
-        let command = &buffer[offset..1];
-        offset += command.len();
-
-        // Command Status
-        let command_status = buffer[offset];
-        offset += 1;
-
-        // Command Data
-        let command_data = if buffer.len() >= offset {
-            &buffer[offset..buffer.len()]
-        } else {
-            &[0]
-        };
+    fn read(buffer: &'b [u8]) -> Option<Self> {
+        // Frame ID and command, then its status and data
+        let ([id, command_high, command_low], rest) = buffer.split_first_chunk::<3>()?;
+        let (&command_status, command_data) = rest.split_first()?;
 
         let command_status = match command_status {
             0 => LocalATCommandResponseStatus::Ok,
@@ -126,7 +260,8 @@ impl<'a, 'b: 'a> FrameData<'b> for LocalATCommandResponse<'a> {
         };
 
         Some(Self {
-            command: [command[0] as _, command[1] as _],
+            id: *id,
+            command: [*command_high as _, *command_low as _],
             status: command_status,
             data: command_data,
         })
@@ -139,15 +274,15 @@ pub struct ModemStatus {
 
 impl<'b> FrameData<'b> for ModemStatus {
     fn frame_type(&self) -> u8 {
-        0x8A
+        MODEM_STATUS
     }
 
-    fn write(self, _buffer: &'b mut [u8]) -> usize {
+    fn write(&self, _buffer: &mut [u8]) -> Option<usize> {
         todo!()
     }
 
-    fn read(self, buffer: &'b [u8]) -> Option<Self> {
-        let status = buffer[0];
+    fn read(buffer: &'b [u8]) -> Option<Self> {
+        let status = *buffer.first()?;
 
         let status = match status {
             0x00 => ModemStatusType::PowerUp,
@@ -175,6 +310,7 @@ impl<'b> FrameData<'b> for ModemStatus {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
 pub enum ModemStatusType {
     PowerUp,
     WatchdogReset,
@@ -197,51 +333,156 @@ pub enum ModemStatusType {
     StackError(u8),
 }
 
+/// Sends data to another radio on the network.
 pub struct TransmitRequest<'a> {
-    destination: u64,
-    destination_small: u16,
-    broadcast_radius: Option<u8>,
-    data: &'a [u8],
+    pub destination: u64,
+    pub destination_small: u16,
+    /// Largest number of hops, or `None` for the network's maximum.
+    pub broadcast_radius: Option<u8>,
+    pub data: &'a [u8],
 }
 
-impl<'a, 'b> FrameData<'b> for TransmitRequest<'a> {
+impl<'a> TransmitRequest<'a> {
+    /// Creates a request to send `data` to the radio with the 64-bit address `destination`.
+    pub fn new(destination: u64, data: &'a [u8]) -> Self {
+        Self {
+            destination,
+            destination_small: UNKNOWN_SMALL_ADDRESS,
+            broadcast_radius: None,
+            data,
+        }
+    }
+}
+
+impl<'a, 'b: 'a> FrameData<'b> for TransmitRequest<'a> {
     fn frame_type(&self) -> u8 {
-        0x10
+        TRANSMIT_REQUEST
     }
 
-    fn write(self, buffer: &'b mut [u8]) -> usize {
-        let mut offset = 0;
+    fn write(&self, buffer: &mut [u8]) -> Option<usize> {
+        let length = 12 + self.data.len();
+        let buffer = buffer.get_mut(..length)?;
 
         // Destination Address
-        let dest_array = self.destination.to_be_bytes();
-        buffer[offset..dest_array.len()].copy_from_slice(&dest_array);
-        offset += dest_array.len();
+        buffer[0..8].copy_from_slice(&self.destination.to_be_bytes());
 
         // 16-Bit Destination Address
-        let dest_array = self.destination_small.to_be_bytes();
-        buffer[offset..dest_array.len()].copy_from_slice(&dest_array);
-        offset += dest_array.len();
+        buffer[8..10].copy_from_slice(&self.destination_small.to_be_bytes());
 
         // Broadcast Radius
-        buffer[offset] = self.broadcast_radius.unwrap_or(0);
-        offset += 1;
+        buffer[10] = self.broadcast_radius.unwrap_or(0);
 
         // Transmit Options
-        buffer[offset] = 0;
-        offset += 1;
+        buffer[11] = 0;
 
         // Data
-        let data_buffer = &mut buffer[offset..];
+        buffer[12..].copy_from_slice(self.data);
 
-        if data_buffer.len() <= self.data.len() {
-            data_buffer[..self.data.len()].copy_from_slice(self.data);
-        }
+        Some(length)
+    }
+
+    fn read(buffer: &'b [u8]) -> Option<Self> {
+        // Destination address, 16-bit destination address, broadcast radius, transmit options, then the data
+        let (destination, rest) = buffer.split_first_chunk::<8>()?;
+        let (destination_small, rest) = rest.split_first_chunk::<2>()?;
+        let [broadcast_radius, _, data @ ..] = rest else {
+            return None;
+        };
+
+        Some(Self {
+            destination: u64::from_be_bytes(*destination),
+            destination_small: u16::from_be_bytes(*destination_small),
+            broadcast_radius: (*broadcast_radius != 0).then_some(*broadcast_radius),
+            data,
+        })
+    }
+}
+
+/// The radio's answer to a [`TransmitRequest`] with a non-zero frame ID.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct TransmitStatus {
+    /// Frame ID of the request.
+    pub id: u8,
+    pub retries: u8,
+    /// 0 if the data was delivered, otherwise the reason it wasn't.
+    pub delivery_status: u8,
+}
+
+impl TransmitStatus {
+    pub fn delivered(&self) -> bool {
+        self.delivery_status == 0
+    }
+}
 
-        offset
+impl FrameData<'_> for TransmitStatus {
+    fn frame_type(&self) -> u8 {
+        TRANSMIT_STATUS
     }
 
-    fn read(self, _buffer: &'b [u8]) -> Option<Self> {
-        todo!()
+    fn write(&self, buffer: &mut [u8]) -> Option<usize> {
+        buffer.get_mut(..6)?.copy_from_slice(&[
+            self.id,
+            0xFF,
+            0xFE,
+            self.retries,
+            self.delivery_status,
+            0,
+        ]);
+
+        Some(6)
+    }
+
+    fn read(buffer: &[u8]) -> Option<Self> {
+        // Frame ID, 16-bit destination address, retries, delivery and discovery status
+        let &[id, _, _, retries, delivery_status, _] = buffer else {
+            return None;
+        };
+
+        Some(Self {
+            id,
+            retries,
+            delivery_status,
+        })
+    }
+}
+
+/// Data received from another radio on the network.
+pub struct ReceivePacket<'a> {
+    pub source: u64,
+    pub source_small: u16,
+    pub options: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a, 'b: 'a> FrameData<'b> for ReceivePacket<'a> {
+    fn frame_type(&self) -> u8 {
+        RECEIVE_PACKET
+    }
+
+    fn write(&self, buffer: &mut [u8]) -> Option<usize> {
+        let length = 11 + self.data.len();
+        let buffer = buffer.get_mut(..length)?;
+
+        buffer[0..8].copy_from_slice(&self.source.to_be_bytes());
+        buffer[8..10].copy_from_slice(&self.source_small.to_be_bytes());
+        buffer[10] = self.options;
+        buffer[11..].copy_from_slice(self.data);
+
+        Some(length)
+    }
+
+    fn read(buffer: &'b [u8]) -> Option<Self> {
+        // Source address, 16-bit source address, receive options, then the data
+        let (source, rest) = buffer.split_first_chunk::<8>()?;
+        let (source_small, rest) = rest.split_first_chunk::<2>()?;
+        let (&options, data) = rest.split_first()?;
+
+        Some(Self {
+            source: u64::from_be_bytes(*source),
+            source_small: u16::from_be_bytes(*source_small),
+            options,
+            data,
+        })
     }
 }
 
@@ -255,3 +496,175 @@ pub struct ExplicitAddressingCommandRequest<'a> {
     broadcast_radius: u8,
     data: &'a [u8],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transmit request from the XBee 3 manual, sending `TxData0A` with frame ID 1.
+    const TRANSMIT_REQUEST_FRAME: [u8; 26] = [
+        0x7E, 0x00, 0x16, 0x10, 0x01, 0x00, 0x13, 0xA2, 0x00, 0x40, 0x0A, 0x01, 0x27, 0xFF, 0xFE,
+        0x00, 0x00, 0x54, 0x78, 0x44, 0x61, 0x74, 0x61, 0x30, 0x41, 0x13,
+    ];
+
+    #[test]
+    fn transmit_request() {
+        let frame = Frame {
+            id: Some(1),
+            data: TransmitRequest::new(0x0013_A200_400A_0127, b"TxData0A"),
+        };
+
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let length = frame.write(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..length], &TRANSMIT_REQUEST_FRAME);
+    }
+
+    #[test]
+    fn association_indication_request() {
+        let frame = Frame {
+            id: Some(1),
+            data: LocalATCommandRequest::new(ASSOCIATION_INDICATION, &[]),
+        };
+
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let length = frame.write(&mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..length],
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, 0x41, 0x49, 0x6C]
+        );
+    }
+
+    #[test]
+    fn pan_id_request() {
+        let pan_id = 0x1234u64.to_be_bytes();
+        let frame = Frame {
+            id: Some(1),
+            data: LocalATCommandRequest::new(PAN_ID, &pan_id),
+        };
+
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let length = frame.write(&mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..length],
+            &[0x7E, 0x00, 0x0C, 0x08, 0x01, 0x49, 0x44, 0, 0, 0, 0, 0, 0, 0x12, 0x34, 0x23]
+        );
+    }
+
+    #[test]
+    fn frame_too_long() {
+        let frame = Frame {
+            id: Some(1),
+            data: TransmitRequest::new(0, b"TxData0A"),
+        };
+
+        assert_eq!(frame.write(&mut [0; 25]), None);
+    }
+
+    #[test]
+    fn read_frames() {
+        let mut reader = FrameReader::new();
+        let mut frames = 0;
+
+        // Noise before the frame, then the frame twice over
+        for byte in [0x00, 0x13]
+            .into_iter()
+            .chain(TRANSMIT_REQUEST_FRAME)
+            .chain(TRANSMIT_REQUEST_FRAME)
+        {
+            if let Some(frame_data) = reader.push(byte) {
+                assert_eq!(frame_data, &TRANSMIT_REQUEST_FRAME[3..25]);
+                frames += 1;
+            }
+        }
+
+        assert_eq!(frames, 2);
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut frame = TRANSMIT_REQUEST_FRAME;
+        frame[20] ^= 0x01;
+
+        let mut reader = FrameReader::new();
+
+        assert!(frame.into_iter().all(|byte| reader.push(byte).is_none()));
+
+        // The reader starts over at the next frame
+        let last = TRANSMIT_REQUEST_FRAME.map(|byte| reader.push(byte).is_some());
+        assert_eq!(last[..25], [false; 25]);
+        assert!(last[25]);
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let mut reader = FrameReader::new();
+
+        // The length is checked as soon as it's known
+        for byte in [START_DELIMITER, 0xFF, 0xFF, 0x90] {
+            assert!(reader.push(byte).is_none());
+        }
+
+        assert!(TRANSMIT_REQUEST_FRAME
+            .into_iter()
+            .any(|byte| reader.push(byte).is_some()));
+    }
+
+    #[test]
+    fn decode_frames() {
+        let mut buffer = [0; MAX_FRAME_LEN];
+
+        let packet = ReceivePacket {
+            source: 0x0013_A200_4052_2BAA,
+            source_small: 0x7D84,
+            options: 0x01,
+            data: b"t",
+        };
+        let length = Frame {
+            id: None,
+            data: packet,
+        }
+        .write(&mut buffer)
+        .unwrap();
+
+        let Some(ReceivedFrame::ReceivePacket(packet)) =
+            ReceivedFrame::decode(&buffer[3..length - 1])
+        else {
+            panic!("expected a receive packet");
+        };
+        assert_eq!(packet.source, 0x0013_A200_4052_2BAA);
+        assert_eq!(packet.source_small, 0x7D84);
+        assert_eq!(packet.data, b"t");
+
+        let Some(ReceivedFrame::ModemStatus(status)) = ReceivedFrame::decode(&[MODEM_STATUS, 0x02])
+        else {
+            panic!("expected a modem status");
+        };
+        assert!(status.status == ModemStatusType::JoinedNetwork);
+
+        let Some(ReceivedFrame::LocalATCommandResponse(response)) =
+            ReceivedFrame::decode(&[LOCAL_AT_COMMAND_RESPONSE, 0x01, b'A', b'I', 0x00, 0x00])
+        else {
+            panic!("expected an AT command response");
+        };
+        assert!(response.command == ASSOCIATION_INDICATION);
+        assert!(response.status == LocalATCommandResponseStatus::Ok);
+        assert_eq!(response.data, &[0x00]);
+
+        let Some(ReceivedFrame::TransmitStatus(status)) =
+            ReceivedFrame::decode(&[TRANSMIT_STATUS, 0x47, 0xFF, 0xFE, 0x00, 0x00, 0x00])
+        else {
+            panic!("expected a transmit status");
+        };
+        assert_eq!(status.id, 0x47);
+        assert!(status.delivered());
+
+        // Truncated and unhandled frames
+        assert!(ReceivedFrame::decode(&[RECEIVE_PACKET, 0x00, 0x13]).is_none());
+        assert!(ReceivedFrame::decode(&[MODEM_STATUS]).is_none());
+        assert!(ReceivedFrame::decode(&[0x95, 0x00]).is_none());
+        assert!(ReceivedFrame::decode(&[]).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::clock::Timestamp;
+use crate::storage::LogRecord;
+
+use super::message::MessageType;
+use super::Payload;
+
+/// Length of an encoded [`LogExportRequest`].
+pub const LOG_EXPORT_REQUEST_LEN: usize = 17;
+
+/// Largest number of records in a [`LogExportChunk`].
+pub const LOG_EXPORT_CHUNK_RECORDS: usize = 5;
+
+/// Length of an exported record.
+const EXPORTED_RECORD_LEN: usize = 14;
+
+/// Asks a chamber for the records it logged in a time range.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct LogExportRequest {
+    /// Start of the time range.
+    pub from: Timestamp,
+    /// End of the time range, which isn't included.
+    pub to: Timestamp,
+}
+
+impl LogExportRequest {
+    /// Decodes a request in the form `L<from><to>`, or `D<from><to>` for a log dump.
+    ///
+    /// Times are big-endian milliseconds since the Unix epoch.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != LOG_EXPORT_REQUEST_LEN
+            || !matches!(
+                MessageType::of(data),
+                Some(MessageType::LogExportRequest | MessageType::LogDumpRequest)
+            )
+        {
+            return None;
+        }
+
+        let time = |bytes: &[u8]| {
+            Some(Timestamp::from_millis(u64::from_be_bytes(
+                bytes.try_into().ok()?,
+            )))
+        };
+
+        Some(Self {
+            from: time(&data[1..9])?,
+            to: time(&data[9..17])?,
+        })
+    }
+}
+
+/// Records sent in answer to a [`LogExportRequest`].
+///
+/// The export ends with a chunk of fewer than [`LOG_EXPORT_CHUNK_RECORDS`]
+/// records, which may be empty.
+#[derive(Clone, Default)]
+pub struct LogExportChunk {
+    pub records: heapless::Vec<LogRecord, LOG_EXPORT_CHUNK_RECORDS>,
+}
+
+impl LogExportChunk {
+    /// Returns whether this is the last chunk of the export.
+    pub fn is_last(&self) -> bool {
+        !self.records.is_full()
+    }
+
+    /// Encodes the chunk as `l<count>` followed by each record.
+    ///
+    /// A record is its time in big-endian milliseconds since the Unix epoch,
+    /// the sensor's address, its flags and its value as a big-endian f32.
+    pub fn encode(&self) -> Payload {
+        let mut payload = Payload::new();
+        let _ = payload.extend_from_slice(&[
+            MessageType::LogExportRecords as u8,
+            self.records.len() as u8,
+        ]);
+
+        for record in &self.records {
+            let mut data = [0; EXPORTED_RECORD_LEN];
+            data[0..8].copy_from_slice(&record.at.millis().to_be_bytes());
+            data[8] = record.address;
+            data[9] = record.flags();
+            data[10..14].copy_from_slice(&record.value.to_be_bytes());
+
+            let _ = payload.extend_from_slice(&data);
+        }
+
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(message_type: MessageType) -> [u8; LOG_EXPORT_REQUEST_LEN] {
+        let mut data = [message_type as u8; LOG_EXPORT_REQUEST_LEN];
+        data[1..9].copy_from_slice(&1_000u64.to_be_bytes());
+        data[9..17].copy_from_slice(&5_000u64.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn decode_requests() {
+        for message_type in [MessageType::LogExportRequest, MessageType::LogDumpRequest] {
+            let request = LogExportRequest::decode(&request(message_type)).unwrap();
+
+            assert_eq!(request.from.millis(), 1_000);
+            assert_eq!(request.to.millis(), 5_000);
+        }
+    }
+
+    #[test]
+    fn malformed_requests() {
+        assert!(LogExportRequest::decode(&request(MessageType::LogExportRecords)).is_none());
+        assert!(LogExportRequest::decode(&request(MessageType::LogExportRequest)[..16]).is_none());
+        assert!(LogExportRequest::decode(&[]).is_none());
+    }
+}
@@ -0,0 +1,101 @@
+/// Type of a message exchanged with the coordinator, the first byte of its payload.
+///
+/// Every message type is listed here, so that no two of them can share a byte.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum MessageType {
+    /// A [`StateReport`](crate::state::StateReport), sent by a chamber.
+    StateReport = b'S',
+    /// A [`TelemetryReport`](crate::telemetry::TelemetryReport), sent by a chamber.
+    TelemetryReport = b'R',
+    /// A [`TimeSyncRequest`](super::time_sync::TimeSyncRequest), sent by a chamber.
+    TimeSyncRequest = b'T',
+    /// A [`TimeSyncResponse`](super::time_sync::TimeSyncResponse), sent by the coordinator.
+    TimeSyncResponse = b't',
+    /// A [`LogExportRequest`](super::log_export::LogExportRequest), sent by the coordinator.
+    LogExportRequest = b'L',
+    /// A request laid out like a [`LogExportRequest`](super::log_export::LogExportRequest),
+    /// sent by the coordinator.
+    ///
+    /// The records are printed over defmt, for a debug probe, rather than sent back.
+    LogDumpRequest = b'D',
+    /// A [`LogExportChunk`](super::log_export::LogExportChunk), sent by a chamber.
+    LogExportRecords = b'l',
+    /// A [`Command::SetPhotoperiod`](super::command::Command::SetPhotoperiod), sent by the coordinator.
+    SetPhotoperiod = b'P',
+    /// A [`Command::SetUtcOffset`](super::command::Command::SetUtcOffset), sent by the coordinator.
+    SetUtcOffset = b'U',
+    /// A [`Command::SetDliTarget`](super::command::Command::SetDliTarget), sent by the coordinator.
+    SetDliTarget = b'I',
+    /// A [`Command::SetGrowLight`](super::command::Command::SetGrowLight), sent by the coordinator.
+    SetGrowLight = b'G',
+    /// A [`Command::SetTime`](super::command::Command::SetTime), sent by the coordinator.
+    SetTime = b'C',
+    /// A [`Command::SetMaintenance`](super::command::Command::SetMaintenance), sent by the coordinator.
+    SetMaintenance = b'M',
+    /// A [`Command::Dispense`](super::command::Command::Dispense), sent by the coordinator.
+    Dispense = b'N',
+    /// A [`Command::StopPump`](super::command::Command::StopPump), sent by the coordinator.
+    StopPump = b'X',
+    /// A [`Command::StartCalibration`](super::command::Command::StartCalibration), sent by the coordinator.
+    StartCalibration = b'K',
+    /// A [`Command::ConfirmCalibration`](super::command::Command::ConfirmCalibration), sent by the coordinator.
+    ConfirmCalibration = b'Y',
+    /// A [`Command::CancelCalibration`](super::command::Command::CancelCalibration), sent by the coordinator.
+    CancelCalibration = b'Z',
+    /// A [`Command::RestoreCalibration`](super::command::Command::RestoreCalibration), sent by the coordinator.
+    RestoreCalibration = b'B',
+}
+
+impl MessageType {
+    /// Every message type.
+    pub const ALL: [MessageType; 19] = [
+        MessageType::StateReport,
+        MessageType::TelemetryReport,
+        MessageType::TimeSyncRequest,
+        MessageType::TimeSyncResponse,
+        MessageType::LogExportRequest,
+        MessageType::LogDumpRequest,
+        MessageType::LogExportRecords,
+        MessageType::SetPhotoperiod,
+        MessageType::SetUtcOffset,
+        MessageType::SetDliTarget,
+        MessageType::SetGrowLight,
+        MessageType::SetTime,
+        MessageType::SetMaintenance,
+        MessageType::Dispense,
+        MessageType::StopPump,
+        MessageType::StartCalibration,
+        MessageType::ConfirmCalibration,
+        MessageType::CancelCalibration,
+        MessageType::RestoreCalibration,
+    ];
+
+    /// Returns the type of the message starting with `byte`, or `None` if there's no such type.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|message_type| *message_type as u8 == byte)
+    }
+
+    /// Returns the type of the message `payload`, or `None` if it's empty or of an unknown type.
+    pub fn of(payload: &[u8]) -> Option<Self> {
+        payload.first().and_then(|byte| Self::from_byte(*byte))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        for message_type in MessageType::ALL {
+            assert!(MessageType::from_byte(message_type as u8) == Some(message_type));
+        }
+
+        assert!(MessageType::from_byte(b'?').is_none());
+        assert!(MessageType::of(&[]).is_none());
+        assert!(MessageType::of(b"t123") == Some(MessageType::TimeSyncResponse));
+    }
+}
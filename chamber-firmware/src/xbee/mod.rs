@@ -1 +1,11 @@
+pub mod command;
 pub mod frame;
+pub mod log_export;
+pub mod message;
+pub mod time_sync;
+
+/// Largest payload of a single unicast transmission.
+pub const MAX_PAYLOAD_LEN: usize = 84;
+
+/// Payload of a message sent or received over the radio.
+pub type Payload = heapless::Vec<u8, MAX_PAYLOAD_LEN>;
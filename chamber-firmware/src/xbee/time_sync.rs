@@ -0,0 +1,400 @@
+use rtic_monotonics::systick::fugit::{Duration, Instant};
+
+use super::message::MessageType;
+use crate::clock::{self, Timestamp};
+
+/// Length of an encoded [`TimeSyncRequest`].
+pub const TIME_SYNC_REQUEST_LEN: usize = 2;
+
+/// Length of an encoded [`TimeSyncResponse`].
+pub const TIME_SYNC_RESPONSE_LEN: usize = 18;
+
+/// Interval at which the clock is resynchronised once it's synchronised.
+pub const TIME_SYNC_INTERVAL: Duration<u32, 1, 1000> =
+    Duration::<u32, 1, 1000>::from_ticks(3_600_000);
+
+/// Interval at which a failed synchronisation is retried.
+pub const TIME_SYNC_RETRY: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(30_000);
+
+/// Time after which an unanswered request is given up on.
+pub const TIME_SYNC_TIMEOUT: Duration<u32, 1, 1000> = Duration::<u32, 1, 1000>::from_ticks(5_000);
+
+/// Longest round trip, in ms, whose time is trusted.
+///
+/// The time is only known to within half the round trip, since the radio's
+/// latency isn't necessarily the same both ways.
+pub const MAX_SYNC_ROUND_TRIP: u32 = 2_000;
+
+/// Asks the coordinator for its time.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct TimeSyncRequest {
+    /// Matches the response to the request.
+    pub sequence: u8,
+}
+
+impl TimeSyncRequest {
+    /// Encodes the request as `T<sequence>`.
+    pub fn encode(&self) -> [u8; TIME_SYNC_REQUEST_LEN] {
+        [MessageType::TimeSyncRequest as u8, self.sequence]
+    }
+}
+
+/// The coordinator's answer to a [`TimeSyncRequest`].
+#[derive(Clone, Copy, defmt::Format)]
+pub struct TimeSyncResponse {
+    pub sequence: u8,
+    /// Coordinator's time when the request arrived.
+    pub received: Timestamp,
+    /// Coordinator's time when the response was sent.
+    pub transmitted: Timestamp,
+}
+
+impl TimeSyncResponse {
+    /// Decodes a response in the form `t<sequence><received><transmitted>`.
+    ///
+    /// Times are big-endian milliseconds since the Unix epoch.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != TIME_SYNC_RESPONSE_LEN
+            || MessageType::of(data) != Some(MessageType::TimeSyncResponse)
+        {
+            return None;
+        }
+
+        let time = |bytes: &[u8]| {
+            Some(Timestamp::from_millis(u64::from_be_bytes(
+                bytes.try_into().ok()?,
+            )))
+        };
+
+        Some(Self {
+            sequence: data[1],
+            received: time(&data[2..10])?,
+            transmitted: time(&data[10..18])?,
+        })
+    }
+}
+
+/// Reason a time sync response wasn't used.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TimeSyncError {
+    /// No request is waiting for a response, or it timed out.
+    Unexpected,
+    /// The response is to an earlier request.
+    WrongSequence { expected: u8, received: u8 },
+    /// The coordinator's times don't make sense.
+    InvalidTimes,
+    /// The coordinator's time is outside the years the RTC can hold.
+    OutOfRange,
+    /// The round trip took too long for the time to be trusted.
+    RoundTripTooLong { round_trip: u32 },
+}
+
+/// The coordinator's time, as worked out from a request and its response.
+#[derive(Clone, Copy)]
+pub struct TimeSyncSample {
+    /// Coordinator's time at `at`.
+    pub time: Timestamp,
+    /// When the response arrived.
+    pub at: Instant<u32, 1, 1000>,
+    /// Time the messages spent on the radio, in ms, not counting the coordinator's processing.
+    pub round_trip: u32,
+    /// How far `time` may be off, in ms.
+    pub accuracy: u32,
+}
+
+impl TimeSyncSample {
+    /// Returns the coordinator's time at `now`.
+    pub fn time_at(&self, now: Instant<u32, 1, 1000>) -> Timestamp {
+        self.time.add_millis(u64::from(
+            now.checked_duration_since(self.at)
+                .map_or(0, |elapsed| elapsed.to_millis()),
+        ))
+    }
+}
+
+/// Synchronises the clock with the coordinator, which is the chambers' source of truth.
+///
+/// The exchange works like NTP's. The request's send time and the response's
+/// arrival time are taken from the monotonic, and the coordinator reports when
+/// it received the request and sent the response. The coordinator's time when
+/// the response arrives is its send time plus half the round trip spent on
+/// the radio, which is also how far off it may be.
+pub struct TimeSync {
+    sequence: u8,
+    /// Sequence and send time of the request waiting for a response.
+    pending: Option<(u8, Instant<u32, 1, 1000>)>,
+    next_sync: Option<Instant<u32, 1, 1000>>,
+    last_sample: Option<TimeSyncSample>,
+}
+
+impl TimeSync {
+    pub const fn new() -> Self {
+        Self {
+            sequence: 0,
+            pending: None,
+            next_sync: None,
+            last_sample: None,
+        }
+    }
+
+    /// Returns the most recent successful synchronisation.
+    pub fn last_sample(&self) -> Option<&TimeSyncSample> {
+        self.last_sample.as_ref()
+    }
+
+    /// Returns whether the clock has been synchronised since boot.
+    pub fn is_synced(&self) -> bool {
+        self.last_sample.is_some()
+    }
+
+    /// Returns when the next request should be sent.
+    ///
+    /// A request is due straight away after boot.
+    pub fn next_sync(&self) -> Option<Instant<u32, 1, 1000>> {
+        self.next_sync
+    }
+
+    /// Returns whether a request should be sent.
+    pub fn is_due(&self, now: Instant<u32, 1, 1000>) -> bool {
+        self.next_sync.is_none_or(|deadline| now >= deadline)
+    }
+
+    /// Starts a new exchange, returning the request to send.
+    ///
+    /// Any earlier request is given up on. If no response arrives within
+    /// [`TIME_SYNC_TIMEOUT`], another request is due after [`TIME_SYNC_RETRY`].
+    pub fn request(&mut self, now: Instant<u32, 1, 1000>) -> TimeSyncRequest {
+        self.sequence = self.sequence.wrapping_add(1);
+        self.pending = Some((self.sequence, now));
+        self.next_sync = Some(now + TIME_SYNC_TIMEOUT + TIME_SYNC_RETRY);
+
+        TimeSyncRequest {
+            sequence: self.sequence,
+        }
+    }
+
+    /// Works out the coordinator's time from a response that arrived at `now`.
+    ///
+    /// The next request is due after [`TIME_SYNC_INTERVAL`] if the response is
+    /// used, or [`TIME_SYNC_RETRY`] if it's from the right request but can't
+    /// be trusted. Times the RTC can't hold aren't trusted, see
+    /// [`clock::can_hold`].
+    pub fn handle_response(
+        &mut self,
+        response: &TimeSyncResponse,
+        now: Instant<u32, 1, 1000>,
+    ) -> Result<TimeSyncSample, TimeSyncError> {
+        let (sequence, sent) = self.pending.ok_or(TimeSyncError::Unexpected)?;
+
+        if response.sequence != sequence {
+            return Err(TimeSyncError::WrongSequence {
+                expected: sequence,
+                received: response.sequence,
+            });
+        }
+
+        let elapsed = now
+            .checked_duration_since(sent)
+            .ok_or(TimeSyncError::Unexpected)?;
+
+        self.pending = None;
+
+        if elapsed > TIME_SYNC_TIMEOUT {
+            return Err(TimeSyncError::Unexpected);
+        }
+
+        self.next_sync = Some(now + TIME_SYNC_RETRY);
+
+        if response.transmitted < response.received {
+            return Err(TimeSyncError::InvalidTimes);
+        }
+
+        // The coordinator's processing time isn't spent on the radio
+        let processing = response.transmitted.millis_since(response.received);
+        let round_trip = u64::from(elapsed.to_millis())
+            .checked_sub(processing)
+            .ok_or(TimeSyncError::InvalidTimes)?;
+        let round_trip = u32::try_from(round_trip).map_err(|_| TimeSyncError::InvalidTimes)?;
+
+        if round_trip > MAX_SYNC_ROUND_TRIP {
+            return Err(TimeSyncError::RoundTripTooLong { round_trip });
+        }
+
+        let sample = TimeSyncSample {
+            time: response.transmitted.add_millis(u64::from(round_trip / 2)),
+            at: now,
+            round_trip,
+            accuracy: round_trip.div_ceil(2),
+        };
+
+        if !clock::can_hold(response.received) || !clock::can_hold(sample.time) {
+            return Err(TimeSyncError::OutOfRange);
+        }
+
+        self.next_sync = Some(now + TIME_SYNC_INTERVAL);
+        self.last_sample = Some(sample);
+
+        Ok(sample)
+    }
+}
+
+impl Default for TimeSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-19T12:00:00Z
+    const COORDINATOR_TIME: u64 = 1_792_411_200_000;
+
+    fn at(millis: u32) -> Instant<u32, 1, 1000> {
+        Instant::<u32, 1, 1000>::from_ticks(millis)
+    }
+
+    fn response(sequence: u8, received: u64, transmitted: u64) -> [u8; TIME_SYNC_RESPONSE_LEN] {
+        let mut data = [0; TIME_SYNC_RESPONSE_LEN];
+        data[0] = MessageType::TimeSyncResponse as u8;
+        data[1] = sequence;
+        data[2..10].copy_from_slice(&received.to_be_bytes());
+        data[10..18].copy_from_slice(&transmitted.to_be_bytes());
+        data
+    }
+
+    /// Sends a request at 1 s, returning the response to it.
+    fn exchange(time_sync: &mut TimeSync, received: u64, transmitted: u64) -> TimeSyncResponse {
+        let request = time_sync.request(at(1_000));
+        TimeSyncResponse::decode(&response(request.sequence, received, transmitted)).unwrap()
+    }
+
+    #[test]
+    fn decode_responses() {
+        let decoded = TimeSyncResponse::decode(&response(7, 1_000, 1_050)).unwrap();
+
+        assert_eq!(decoded.sequence, 7);
+        assert_eq!(decoded.received.millis(), 1_000);
+        assert_eq!(decoded.transmitted.millis(), 1_050);
+    }
+
+    #[test]
+    fn malformed_responses() {
+        let mut wrong_type = response(7, 1_000, 1_050);
+        wrong_type[0] = MessageType::TimeSyncRequest as u8;
+
+        assert!(TimeSyncResponse::decode(&wrong_type).is_none());
+        assert!(TimeSyncResponse::decode(&response(7, 1_000, 1_050)[..17]).is_none());
+        assert!(TimeSyncResponse::decode(&[]).is_none());
+    }
+
+    #[test]
+    fn request_encoding() {
+        let mut time_sync = TimeSync::new();
+
+        assert!(time_sync.is_due(at(0)));
+        assert_eq!(time_sync.request(at(0)).encode(), [b'T', 1]);
+        assert_eq!(time_sync.request(at(0)).encode(), [b'T', 2]);
+        assert!(!time_sync.is_due(at(1)));
+    }
+
+    #[test]
+    fn sample_allows_for_the_radio() {
+        let mut time_sync = TimeSync::new();
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME + 100);
+
+        // 300 ms round trip, of which the coordinator spent 100 ms
+        let Ok(sample) = time_sync.handle_response(&response, at(1_300)) else {
+            panic!("the response wasn't used");
+        };
+
+        assert_eq!(sample.round_trip, 200);
+        assert_eq!(sample.accuracy, 100);
+        assert_eq!(sample.time.millis(), COORDINATOR_TIME + 200);
+        assert_eq!(sample.time_at(at(1_800)).millis(), COORDINATOR_TIME + 700);
+        assert!(time_sync.is_synced());
+        assert!(time_sync.next_sync() == Some(at(1_300) + TIME_SYNC_INTERVAL));
+    }
+
+    #[test]
+    fn responses_must_match_the_request() {
+        let mut time_sync = TimeSync::new();
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME);
+
+        let mut other = response;
+        other.sequence = other.sequence.wrapping_add(1);
+        assert!(time_sync
+            .handle_response(&other, at(1_100))
+            .is_err_and(|error| error
+                == TimeSyncError::WrongSequence {
+                    expected: response.sequence,
+                    received: other.sequence
+                }));
+
+        // The request is still waiting, until it's answered
+        assert!(time_sync.handle_response(&response, at(1_100)).is_ok());
+        assert!(time_sync
+            .handle_response(&response, at(1_100))
+            .is_err_and(|error| error == TimeSyncError::Unexpected));
+    }
+
+    #[test]
+    fn late_responses_are_ignored() {
+        let mut time_sync = TimeSync::new();
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME);
+        let late = at(1_001) + TIME_SYNC_TIMEOUT;
+
+        assert!(time_sync
+            .handle_response(&response, late)
+            .is_err_and(|error| error == TimeSyncError::Unexpected));
+        assert!(!time_sync.is_synced());
+    }
+
+    #[test]
+    fn untrusted_times_are_retried() {
+        let mut time_sync = TimeSync::new();
+
+        // Sent before it was received
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME - 1);
+        assert!(time_sync
+            .handle_response(&response, at(1_100))
+            .is_err_and(|error| error == TimeSyncError::InvalidTimes));
+        assert!(time_sync.next_sync() == Some(at(1_100) + TIME_SYNC_RETRY));
+
+        // The coordinator took longer than the whole round trip
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME + 200);
+        assert!(time_sync
+            .handle_response(&response, at(1_100))
+            .is_err_and(|error| error == TimeSyncError::InvalidTimes));
+
+        let response = exchange(&mut time_sync, COORDINATOR_TIME, COORDINATOR_TIME);
+        let slow = at(1_001 + MAX_SYNC_ROUND_TRIP);
+        assert!(time_sync
+            .handle_response(&response, slow)
+            .is_err_and(|error| error
+                == TimeSyncError::RoundTripTooLong {
+                    round_trip: MAX_SYNC_ROUND_TRIP + 1
+                }));
+
+        assert!(!time_sync.is_synced());
+    }
+
+    #[test]
+    fn times_the_rtc_cant_hold_are_rejected() {
+        let mut time_sync = TimeSync::new();
+
+        // 2100-01-01, 2000-12-31 and the end of time
+        for time in [4_102_444_800_000, 978_307_199_000, u64::MAX - 1_000] {
+            let response = exchange(&mut time_sync, time, time);
+
+            assert!(time_sync
+                .handle_response(&response, at(1_100))
+                .is_err_and(|error| error == TimeSyncError::OutOfRange));
+        }
+
+        // The RTC's last day is fine
+        let response = exchange(&mut time_sync, 4_102_444_798_000, 4_102_444_798_000);
+        assert!(time_sync.handle_response(&response, at(1_100)).is_ok());
+    }
+}